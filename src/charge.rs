@@ -0,0 +1,109 @@
+//! Cation/anion balance of solutions and salts in meq/L
+//!
+//! A dissolved salt is electrically neutral, so a recipe made of correctly
+//! entered salts is balanced as well. A large imbalance points to a wrong
+//! composition (e.g. a typo in a custom salt) or an incomplete water analysis.
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Nutrient, NutrientVector, Salt, SaltKind};
+
+/// Ion balance error from which a salt or solution is reported as suspicious
+pub const IMBALANCE_WARNING: f64 = 0.05;
+
+/// meq of charge per g of a nutrient (1000 · |z| / M); 0 for uncharged nutrients
+pub fn meq_per_gram(nutrient: Nutrient) -> f64 {
+    nutrient.charge().map_or(0.0, |charge| 1000.0 * charge.abs() / nutrient.molar_mass())
+}
+
+/// Charge equivalents of a solution, split into cations and anions
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChargeBalance {
+    /// meq/L per cation
+    pub cations: Vec<(Nutrient, f64)>,
+    /// meq/L per anion
+    pub anions: Vec<(Nutrient, f64)>,
+}
+
+impl ChargeBalance {
+    /// Balance of a solution given in g/L
+    pub fn of(concentrations: &NutrientVector) -> Self {
+        let mut balance = ChargeBalance::default();
+        for (nutrient, g_per_l) in concentrations.iter() {
+            match nutrient.charge() {
+                Some(charge) if charge > 0.0 => balance.cations.push((nutrient, g_per_l * meq_per_gram(nutrient))),
+                Some(_) => balance.anions.push((nutrient, g_per_l * meq_per_gram(nutrient))),
+                None => {}
+            }
+        }
+        balance
+    }
+
+    pub fn cation_sum(&self) -> f64 {
+        self.cations.iter().map(|(_, meq)| meq).sum()
+    }
+
+    pub fn anion_sum(&self) -> f64 {
+        self.anions.iter().map(|(_, meq)| meq).sum()
+    }
+
+    /// Ion balance error (Σcations − Σanions) / (Σcations + Σanions), from −1 to 1
+    pub fn imbalance(&self) -> f64 {
+        let (cations, anions) = (self.cation_sum(), self.anion_sum());
+        if cations + anions > 0.0 {
+            (cations - anions) / (cations + anions)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Ion balance error of a salt's composition, if it can be judged: acids and
+/// bases (unlisted H⁺ / OH⁻) and salts with micronutrients (unknown charge) are skipped
+pub fn salt_imbalance(salt: &Salt) -> Option<f64> {
+    let has_micronutrients = Nutrient::ALL.iter()
+        .any(|&n| n.is_micronutrient() && salt.composition[n] > 0.0);
+    if has_micronutrients || salt.kind != SaltKind::Salt {
+        return None;
+    }
+    let balance = ChargeBalance::of(&salt.composition);
+    (balance.cation_sum() + balance.anion_sum() > 0.0).then(|| balance.imbalance())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula;
+
+    fn salt(formula: &str) -> Salt {
+        Salt::custom(formula.to_string(), formula.to_string(), formula::composition(formula, 1.0).unwrap())
+    }
+
+    #[test]
+    fn single_salt_is_balanced() {
+        // 1 g KNO₃ (101.1 g/mol) holds 9.89 meq of each ion
+        let balance = ChargeBalance::of(&salt("KNO₃").composition);
+        assert!((balance.cation_sum() - 9.891).abs() < 0.01);
+        assert!((balance.anion_sum() - 9.891).abs() < 0.01);
+        for formula in ["KNO₃", "Ca(NO₃)₂·4H₂O", "KH₂PO₄", "(NH₄)₂SO₄", "MgSO₄·7H₂O"] {
+            let imbalance = salt_imbalance(&salt(formula)).unwrap();
+            assert!(imbalance.abs() < 1e-3, "{}: {}", formula, imbalance);
+        }
+    }
+
+    #[test]
+    fn wrong_composition_is_imbalanced() {
+        // Twice the potassium: 2 parts cations against 1 part anions
+        let mut typo = salt("KNO₃");
+        typo.composition[Nutrient::K] *= 2.0;
+        let imbalance = salt_imbalance(&typo).unwrap();
+        assert!((imbalance - 1.0 / 3.0).abs() < 1e-3);
+        assert!(imbalance > IMBALANCE_WARNING);
+    }
+
+    #[test]
+    fn acids_are_not_judged() {
+        assert_eq!(salt_imbalance(&salt("HNO₃").with_kind(SaltKind::Acid)), None);
+    }
+}
@@ -0,0 +1,383 @@
+//! Precipitation rules for salts sharing a stock solution tank
+//!
+//! Each rule names two species that must not both be concentrated in one
+//! tank. In a recipe a rule is broken when the product of their summed tank
+//! concentrations (mmol/L) exceeds the rule's limit, like a solubility
+//! product; some rules apply only above a tank pH (see [`ph`](crate::ph)).
+//! Without amounts (salt manager) any salt that supplies a species counts.
+
+use std::fmt;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::charge::ChargeBalance;
+use crate::models::{Nutrient, NutrientVector, RecipeEntry, Salt, SaltKind};
+use crate::ph;
+
+/// Mass fraction from which a salt counts as a source of a species when amounts are unknown
+const PRESENCE_FRACTION: f64 = 0.01;
+
+/// Concentrations below this are solver noise (mmol/L)
+const CONCENTRATION_TOLERANCE: f64 = 1e-6;
+
+/// Ratios of the first to the second concentration, relative to the square
+/// root of the product limit, at which [`Rule::steps`] places its corners
+const STEP_FACTORS: [f64; 3] = [0.1, 1.0, 10.0];
+
+/// Constituent of a salt that takes part in a precipitation rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Species {
+    Calcium,
+    Magnesium,
+    Iron,
+    Phosphate,
+    Sulfate,
+    Ammonium,
+    /// Free mineral acid such as HNO₃
+    Acid,
+    /// Free hydroxide or carbonate such as KOH
+    Base,
+}
+
+impl Species {
+    pub fn label(self) -> &'static str {
+        match self {
+            Species::Calcium => "Ca²⁺",
+            Species::Magnesium => "Mg²⁺",
+            Species::Iron => "Fe",
+            Species::Phosphate => "PO₄³⁻",
+            Species::Sulfate => "SO₄²⁻",
+            Species::Ammonium => "NH₄⁺",
+            Species::Acid => "Säure",
+            Species::Base => "Lauge",
+        }
+    }
+
+    /// g of the species per g of salt; phosphate and sulfate count as P and S,
+    /// acids and bases count with their whole mass
+    pub fn fraction(self, salt: &Salt) -> f64 {
+        match self {
+            Species::Calcium => salt.composition[Nutrient::Ca],
+            Species::Magnesium => salt.composition[Nutrient::Mg],
+            Species::Iron => salt.composition[Nutrient::Fe],
+            Species::Phosphate => salt.composition[Nutrient::P],
+            Species::Sulfate => salt.composition[Nutrient::S],
+            Species::Ammonium => salt.composition[Nutrient::Nh4],
+            Species::Acid => if salt.kind == SaltKind::Acid { 1.0 } else { 0.0 },
+            Species::Base => if salt.kind == SaltKind::Base { 1.0 } else { 0.0 },
+        }
+    }
+
+    /// mmol of the ion per g of salt; phosphate and sulfate count once per P
+    /// and S, acids as free H⁺ and bases as OH⁻ or HCO₃⁻
+    pub fn millimoles(self, salt: &Salt) -> f64 {
+        let balance = || ChargeBalance::of(&salt.composition);
+        match self {
+            Species::Acid if salt.kind == SaltKind::Acid => {
+                let balance = balance();
+                (balance.anion_sum() - balance.cation_sum()).max(0.0)
+            }
+            Species::Base if salt.kind == SaltKind::Base => {
+                let balance = balance();
+                let bicarbonate = salt.composition[Nutrient::Hco3] * 1000.0 / Nutrient::Hco3.molar_mass();
+                (balance.cation_sum() - balance.anion_sum() + bicarbonate).max(0.0)
+            }
+            Species::Acid | Species::Base => 0.0,
+            _ => {
+                let nutrient = self.nutrient();
+                salt.composition[nutrient] * 1000.0 / nutrient.molar_mass()
+            }
+        }
+    }
+
+    fn nutrient(self) -> Nutrient {
+        match self {
+            Species::Calcium => Nutrient::Ca,
+            Species::Magnesium => Nutrient::Mg,
+            Species::Iron => Nutrient::Fe,
+            Species::Phosphate => Nutrient::P,
+            Species::Sulfate => Nutrient::S,
+            Species::Ammonium | Species::Acid | Species::Base => Nutrient::Nh4,
+        }
+    }
+
+    pub fn is_supplied_by(self, salt: &Salt) -> bool {
+        self.fraction(salt) > PRESENCE_FRACTION
+    }
+}
+
+/// Two species that precipitate (or react) when both are concentrated in one tank
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rule {
+    pub name: &'static str,
+    pub first: Species,
+    pub second: Species,
+    /// Largest product of the two tank concentrations, in (mmol/L)²
+    pub product_limit: f64,
+    /// Tank pH above which the rule applies; `None` at any pH
+    pub above_ph: Option<f64>,
+}
+
+impl Rule {
+    /// Whether tank concentrations (mmol/L) at the given tank pH break the rule
+    pub fn is_broken(&self, first: f64, second: f64, ph: f64) -> bool {
+        first > CONCENTRATION_TOLERANCE
+            && second > CONCENTRATION_TOLERANCE
+            && first * second > self.product_limit * (1.0 + CONCENTRATION_TOLERANCE)
+            && self.above_ph.is_none_or(|limit| ph > limit)
+    }
+
+    /// Upper limits (first, second) in mmol/L of boxes inside the tolerated
+    /// region; the optimizer keeps each tank in one of them. The outer two
+    /// leave one species unlimited while the other is absent
+    pub fn steps(&self) -> Vec<(f64, f64)> {
+        let root = self.product_limit.sqrt();
+        let mut steps = vec![(0.0, f64::INFINITY)];
+        steps.extend(STEP_FACTORS.iter().map(|factor| (root * factor, root / factor)));
+        steps.push((f64::INFINITY, 0.0));
+        steps
+    }
+}
+
+/// Stored by name; the limits always come from [`RULES`]
+impl Serialize for Rule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        RULES.into_iter()
+            .find(|rule| rule.name == name)
+            .ok_or_else(|| de::Error::custom(format!("unbekannte Fällungsregel \"{}\"", name)))
+    }
+}
+
+/// Rules checked for every tank
+///
+/// The limits are conservative for acidic, concentrated stock solutions.
+/// Magnesium phosphate is left out: Mg²⁺ stays dissolved next to H₂PO₄⁻, so
+/// MgSO₄ and KH₂PO₄ share the usual tank B.
+pub const RULES: [Rule; 6] = [
+    Rule { name: "Gips", first: Species::Calcium, second: Species::Sulfate, product_limit: 150.0, above_ph: None },
+    Rule { name: "Calciumphosphat", first: Species::Calcium, second: Species::Phosphate, product_limit: 8.0, above_ph: None },
+    Rule { name: "Eisenphosphat (bei hohem pH)", first: Species::Iron, second: Species::Phosphate, product_limit: 15.0, above_ph: Some(6.0) },
+    Rule { name: "Ammonium mit Säure", first: Species::Ammonium, second: Species::Acid, product_limit: 1e5, above_ph: None },
+    Rule { name: "Ammoniak", first: Species::Ammonium, second: Species::Base, product_limit: 5.0, above_ph: None },
+    Rule { name: "Kalk", first: Species::Calcium, second: Species::Base, product_limit: 10.0, above_ph: None },
+];
+
+/// A rule broken in one tank, with the salts supplying each species
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conflict {
+    pub rule: Rule,
+    pub tank: String,
+    pub first: Vec<String>,
+    pub second: Vec<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Stammlösung {}: {} ({} + {}) aus {} mit {}",
+            self.tank,
+            self.rule.name,
+            self.rule.first.label(),
+            self.rule.second.label(),
+            self.first.join(", "),
+            self.second.join(", ")
+        )
+    }
+}
+
+/// Reads stored conflicts, leaving out those of rules that were removed or renamed
+pub fn known_conflicts<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Conflict>, D::Error> {
+    #[derive(Deserialize)]
+    struct Stored {
+        rule: String,
+        tank: String,
+        first: Vec<String>,
+        second: Vec<String>,
+    }
+    Ok(Vec::<Stored>::deserialize(deserializer)?
+        .into_iter()
+        .filter_map(|stored| {
+            let rule = RULES.into_iter().find(|rule| rule.name == stored.rule)?;
+            Some(Conflict { rule, tank: stored.tank, first: stored.first, second: stored.second })
+        })
+        .collect())
+}
+
+/// False if the two salts break a rule whenever they share a tank
+pub fn compatible(a: &Salt, b: &Salt) -> bool {
+    RULES.iter().all(|rule| {
+        !(rule.first.is_supplied_by(a) && rule.second.is_supplied_by(b)
+            || rule.second.is_supplied_by(a) && rule.first.is_supplied_by(b))
+    })
+}
+
+/// Rules broken by the enabled salts assigned to `tank`, regardless of amounts
+pub fn presence_conflicts(salts: &[Salt], tank: &str) -> Vec<Conflict> {
+    let tank_salts: Vec<&Salt> = salts.iter()
+        .filter(|s| s.stock_solution == tank && s.enabled)
+        .collect();
+    RULES.iter()
+        .filter_map(|rule| {
+            let sources = |species: Species| -> Vec<String> {
+                tank_salts.iter()
+                    .filter(|s| species.is_supplied_by(s))
+                    .map(|s| s.name.clone())
+                    .collect()
+            };
+            let (first, second) = (sources(rule.first), sources(rule.second));
+            (!first.is_empty() && !second.is_empty()).then(|| Conflict {
+                rule: *rule,
+                tank: tank.to_string(),
+                first,
+                second,
+            })
+        })
+        .collect()
+}
+
+/// Rules broken by a recipe, using the summed concentrations and the estimated pH of each tank
+pub fn recipe_conflicts(recipe: &[RecipeEntry], salts: &[&Salt]) -> Vec<Conflict> {
+    let mut tanks: Vec<&str> = recipe.iter().map(|e| e.tank.as_str()).collect();
+    tanks.sort_unstable();
+    tanks.dedup();
+
+    let mut conflicts = Vec::new();
+    for tank in tanks {
+        let entries: Vec<(&Salt, f64)> = recipe.iter()
+            .filter(|e| e.tank == tank)
+            .filter_map(|e| salts.iter().find(|s| s.name == e.salt).map(|s| (*s, e.amount)))
+            .collect();
+        let mut nutrients = NutrientVector::zero();
+        for (salt, amount) in entries.iter() {
+            for (nutrient, fraction) in salt.composition.iter() {
+                nutrients[nutrient] += amount * fraction;
+            }
+        }
+        let ph = ph::estimate_ph(&nutrients);
+        for rule in RULES.iter() {
+            let concentration = |species: Species| -> f64 {
+                entries.iter().map(|(salt, amount)| amount * species.millimoles(salt)).sum()
+            };
+            if rule.is_broken(concentration(rule.first), concentration(rule.second), ph) {
+                let sources = |species: Species| -> Vec<String> {
+                    entries.iter()
+                        .filter(|(salt, amount)| amount * species.millimoles(salt) > CONCENTRATION_TOLERANCE)
+                        .map(|(salt, _)| salt.name.clone())
+                        .collect()
+                };
+                conflicts.push(Conflict {
+                    rule: *rule,
+                    tank: tank.to_string(),
+                    first: sources(rule.first),
+                    second: sources(rule.second),
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula;
+
+    fn salt(formula: &str, kind: SaltKind, purity: f64) -> Salt {
+        let composition = formula::composition(formula, purity).unwrap();
+        Salt::custom(formula.to_string(), formula.to_string(), composition).with_kind(kind)
+    }
+
+    fn fe_edta() -> Salt {
+        Salt::custom("Fe-EDTA 13 %".to_string(), String::new(), NutrientVector::from_pairs(&[(Nutrient::Fe, 0.13)]))
+    }
+
+    /// Names of the rules broken by the given amounts (g/L) in one tank
+    fn broken(amounts: &[(&Salt, f64)]) -> Vec<&'static str> {
+        let recipe: Vec<RecipeEntry> = amounts.iter()
+            .map(|(salt, amount)| RecipeEntry { salt: salt.name.clone(), tank: "A".to_string(), amount: *amount })
+            .collect();
+        let salts: Vec<&Salt> = amounts.iter().map(|(salt, _)| *salt).collect();
+        recipe_conflicts(&recipe, &salts).into_iter().map(|c| c.rule.name).collect()
+    }
+
+    #[test]
+    fn gypsum_uses_the_summed_calcium_of_the_tank() {
+        let calcium_nitrate = salt("Ca(NO3)2·4H2O", SaltKind::Salt, 1.0);
+        let calcium_chloride = salt("CaCl2·2H2O", SaltKind::Salt, 1.0);
+        let potassium_sulfate = salt("K2SO4", SaltKind::Salt, 1.0);
+        assert_eq!(broken(&[(&calcium_nitrate, 100.0), (&potassium_sulfate, 50.0)]), ["Gips"]);
+        // Each calcium salt alone stays below the limit, both together do not
+        assert!(broken(&[(&calcium_nitrate, 0.5), (&potassium_sulfate, 10.0)]).is_empty());
+        assert!(broken(&[(&calcium_chloride, 0.3), (&potassium_sulfate, 10.0)]).is_empty());
+        assert_eq!(broken(&[(&calcium_nitrate, 0.5), (&calcium_chloride, 0.3), (&potassium_sulfate, 10.0)]), ["Gips"]);
+    }
+
+    #[test]
+    fn calcium_phosphate() {
+        let calcium_nitrate = salt("Ca(NO3)2·4H2O", SaltKind::Salt, 1.0);
+        let monopotassium_phosphate = salt("KH2PO4", SaltKind::Salt, 1.0);
+        assert_eq!(broken(&[(&calcium_nitrate, 50.0), (&monopotassium_phosphate, 5.0)]), ["Calciumphosphat"]);
+        assert!(broken(&[(&calcium_nitrate, 0.01), (&monopotassium_phosphate, 5.0)]).is_empty());
+    }
+
+    #[test]
+    fn magnesium_sulfate_and_monopotassium_phosphate_share_a_tank() {
+        let magnesium_sulfate = salt("MgSO4·7H2O", SaltKind::Salt, 1.0);
+        let monopotassium_phosphate = salt("KH2PO4", SaltKind::Salt, 1.0);
+        assert!(broken(&[(&magnesium_sulfate, 50.0), (&monopotassium_phosphate, 20.0)]).is_empty());
+        assert!(compatible(&magnesium_sulfate, &monopotassium_phosphate));
+    }
+
+    #[test]
+    fn iron_phosphate_only_above_the_ph_limit() {
+        let iron = fe_edta();
+        // H₂PO₄⁻ keeps the tank acidic, HPO₄²⁻ makes it alkaline
+        let monopotassium_phosphate = salt("KH2PO4", SaltKind::Salt, 1.0);
+        let dipotassium_phosphate = salt("K2HPO4", SaltKind::Salt, 1.0);
+        assert!(broken(&[(&iron, 5.0), (&monopotassium_phosphate, 20.0)]).is_empty());
+        assert_eq!(broken(&[(&iron, 5.0), (&dipotassium_phosphate, 20.0)]), ["Eisenphosphat (bei hohem pH)"]);
+        assert!(broken(&[(&iron, 0.01), (&dipotassium_phosphate, 1.0)]).is_empty());
+    }
+
+    #[test]
+    fn ammonium_with_acid() {
+        let ammonium_sulfate = salt("(NH4)2SO4", SaltKind::Salt, 1.0);
+        let nitric_acid = salt("HNO3", SaltKind::Acid, 0.65);
+        assert_eq!(broken(&[(&ammonium_sulfate, 200.0), (&nitric_acid, 100.0)]), ["Ammonium mit Säure"]);
+        assert!(broken(&[(&ammonium_sulfate, 200.0), (&nitric_acid, 1.0)]).is_empty());
+    }
+
+    #[test]
+    fn ammonia() {
+        let ammonium_chloride = salt("NH4Cl", SaltKind::Salt, 1.0);
+        let potash_lye = salt("KOH", SaltKind::Base, 0.5);
+        assert_eq!(broken(&[(&ammonium_chloride, 10.0), (&potash_lye, 2.0)]), ["Ammoniak"]);
+        assert!(broken(&[(&ammonium_chloride, 10.0)]).is_empty());
+    }
+
+    #[test]
+    fn lime() {
+        let calcium_nitrate = salt("Ca(NO3)2·4H2O", SaltKind::Salt, 1.0);
+        let potassium_bicarbonate = salt("KHCO3", SaltKind::Base, 1.0);
+        assert_eq!(broken(&[(&calcium_nitrate, 10.0), (&potassium_bicarbonate, 5.0)]), ["Kalk"]);
+        assert!(broken(&[(&calcium_nitrate, 0.01), (&potassium_bicarbonate, 0.5)]).is_empty());
+    }
+
+    #[test]
+    fn steps_stay_within_the_product_limit() {
+        for rule in RULES {
+            for (first, second) in rule.steps() {
+                let (first, second) = (first.min(1e9), second.min(1e9));
+                assert!(!rule.is_broken(first, second, 14.0), "{}: {} · {}", rule.name, first, second);
+            }
+        }
+    }
+}
@@ -1,137 +1,210 @@
-/// Main fertilizer optimizer component
+//! Main fertilizer optimizer component
 
 use dioxus::prelude::*;
-use crate::models::{ComparisonEntry, OptimizationResult};
-use crate::optimizer::optimize_recipe;
-use crate::data::get_predefined_salts;
-use crate::components::UnifiedSaltManager;
+use crate::models::{ComparisonEntry, Nutrient, NutrientTargets, OptimizationResult, Range, RecipeCost, Salt, TargetBasis, UnitSystem};
+use crate::optimizer::{DisplayUnits, Objective, OptimizationOptions, OptimizationProblem};
+use crate::charge::{ChargeBalance, IMBALANCE_WARNING};
+use crate::diagnosis::{diagnose, Infeasibility, Unsolvable};
+use crate::sensitivity::{self, Sensitivity};
+use crate::persistence::{self, Workspace, WORKSPACE_VERSION};
+use crate::share;
+use crate::components::{download_file, ParetoPanel, SweepPanel, UnifiedSaltManager, WaterProfile};
 
+/// Tank load from which the results warn about the solubility limit
+const TANK_LOAD_WARNING: f64 = 0.9;
+
+/// File name offered when exporting the workspace
+const WORKSPACE_FILE_NAME: &str = "naehrloesung.json";
+
+/// Copies text received from Rust to the clipboard; browsers may refuse silently
+const CLIPBOARD_SCRIPT: &str = r#"
+    const text = await dioxus.recv();
+    await navigator.clipboard.writeText(text);
+"#;
+
+/// `opened_link` holds the code of a share link the page was opened with or
+/// that was pasted into the address bar, until the user takes it over or discards it
 #[component]
-pub fn FertilizerOptimizer() -> Element {
-    // Macronutrient parameters (g/L)
-    let min_n = use_signal(|| 40.0);
-    let max_n = use_signal(|| 40.0);
-    let nh4_percentage = use_signal(|| 50.0);
-    let no3_percentage = use_signal(|| 50.0);
-    let nh4_ratio = move || nh4_percentage() / 100.0;
-    let min_k = use_signal(|| 15.0);
-    let max_k = use_signal(|| 25.0);
-    let min_p = use_signal(|| 4.0);
-    let max_p = use_signal(|| 8.0);
-    let min_ca = use_signal(|| 10.0);
-    let max_ca = use_signal(|| 15.0);
-    let min_mg = use_signal(|| 4.0);
-    let max_mg = use_signal(|| 5.0);
-    let min_s = use_signal(|| 20.0);
-    let max_s = use_signal(|| 25.0);
-    let min_cl = use_signal(|| 0.0);
-    let max_cl = use_signal(|| 75.0);
-    
-    // Micronutrient parameters (mg/L)
-    let min_fe = use_signal(|| 0.0);
-    let max_fe = use_signal(|| 5.0);
-    let min_mn = use_signal(|| 0.0);
-    let max_mn = use_signal(|| 2.0);
-    let min_zn = use_signal(|| 0.0);
-    let max_zn = use_signal(|| 0.5);
-    let min_cu = use_signal(|| 0.0);
-    let max_cu = use_signal(|| 0.2);
-    let min_b = use_signal(|| 0.0);
-    let max_b = use_signal(|| 0.5);
-    let min_mo = use_signal(|| 0.0);
-    let max_mo = use_signal(|| 0.1);
+pub fn FertilizerOptimizer(opened_link: Signal<Option<String>>) -> Element {
+    // Workspace of the last session, or the defaults on first load
+    let (stored, load_error) = use_hook(|| match persistence::load() {
+        Ok(workspace) => (workspace.unwrap_or_default(), None),
+        Err(e) => (Workspace::default(), Some(format!("{:#}", e))),
+    });
+    let mut workspace_error = use_signal(|| load_error);
+    // Link to the current recipe, shown after "Link teilen"
+    let mut share_link = use_signal(|| None::<String>);
+    // Workspace a share link replaced, until the change is confirmed or undone
+    let mut replaced_workspace = use_signal(|| None::<Workspace>);
+
+    // Nutrient targets (g/L for macronutrients, mg/L for micronutrients)
+    let mut targets = use_signal(|| stored.targets.clone());
+    let nh4_percentage = move || targets().nh4_ratio.unwrap_or(0.0) * 100.0;
+    let mut options = use_signal(|| stored.options.clone());
+    let show_targets = move || options().objective == Objective::Deviation;
+    let mut water = use_signal(|| stored.water.clone());
     
     // UI state
     let mut show_salt_manager = use_signal(|| false);
     let mut show_micronutrients = use_signal(|| false);
+    let result_units = use_signal(UnitSystem::default);
+    let mut comparison_units = use_signal(UnitSystem::default);
     
     // Salt and stock solution management
-    let salts = use_signal(|| get_predefined_salts());
-    let stock_solutions = use_signal(|| vec!["A".to_string(), "B".to_string()]);
+    let mut salts = use_signal(|| stored.salts.clone());
+    let mut stock_solutions = use_signal(|| stored.stock_solutions.clone());
     
     // Results
     let mut result = use_signal(|| None::<OptimizationResult>);
     let mut current_result = use_signal(|| None::<OptimizationResult>);
     let mut error_msg = use_signal(|| None::<String>);
-    let mut comparison_history = use_signal(|| Vec::<ComparisonEntry>::new());
+    // Why the targets cannot be met, once the user asked for it
+    let mut infeasibility = use_signal(|| None::<Infeasibility>);
+    // True when the targets cannot all be met
+    let mut unsolvable = use_signal(|| false);
+    // True when the exact bounds failed and the closest recipe is shown instead
+    let mut best_effort = use_signal(|| false);
+    let mut comparison_history = use_signal(|| stored.comparison_history.clone());
+    // Shadow prices and reduced costs of the current recipe, computed on request
+    let mut sensitivity_report = use_signal(|| None::<Result<Sensitivity, String>>);
+
+    let build_problem = move || {
+        OptimizationProblem::new(targets())
+            .salts(salts())
+            .options(options())
+            .water(water())
+            .tanks(stock_solutions())
+    };
+    // Basis and units deviations and diagnoses are described in
+    let display_units = move || DisplayUnits::of(&targets(), options().dilution_ratio);
 
-    // Initial optimization on component mount
+    // Real-time optimization on mount and whenever targets, water or salts change
     use_effect(move || {
-        let enabled_salts: Vec<_> = salts().into_iter().filter(|s| s.enabled).collect();
-        if enabled_salts.is_empty() {
+        sensitivity_report.set(None);
+        if !salts().iter().any(|s| s.enabled) {
             error_msg.set(Some("Keine Salze ausgewählt".to_string()));
+            infeasibility.set(None);
+            unsolvable.set(false);
             return;
         }
         
-        let optimization_result = optimize_recipe(
-            min_n(), max_n(), nh4_ratio(),
-            min_k(), max_k(), min_p(), max_p(),
-            min_ca(), max_ca(), min_mg(), max_mg(),
-            min_s(), max_s(), min_cl(), max_cl(),
-            min_fe(), max_fe(), min_mn(), max_mn(),
-            min_zn(), max_zn(), min_cu(), max_cu(),
-            min_b(), max_b(), min_mo(), max_mo(),
-            false, &enabled_salts
-        );
+        let problem = build_problem();
         
-        match optimization_result {
+        match problem.solve() {
             Ok(res) => {
                 result.set(Some(res.clone()));
                 current_result.set(Some(res));
                 error_msg.set(None);
+                infeasibility.set(None);
+                unsolvable.set(false);
+                best_effort.set(false);
             }
             Err(e) => {
+                let is_unsolvable = e.is::<Unsolvable>();
                 error_msg.set(Some(format!("Nicht lösbar: {}", e)));
+                infeasibility.set(None);
+                unsolvable.set(is_unsolvable);
+                best_effort.set(false);
+                // Show the nearest achievable recipe instead of an empty result
+                if is_unsolvable {
+                    if let Ok(res) = problem.objective(Objective::Deviation).solve() {
+                        result.set(Some(res.clone()));
+                        current_result.set(Some(res));
+                        best_effort.set(true);
+                    }
+                }
             }
         }
     });
 
-    // Real-time optimization when parameters change
+    let current_workspace = move || Workspace {
+        version: WORKSPACE_VERSION,
+        salts: salts(),
+        stock_solutions: stock_solutions(),
+        targets: targets(),
+        options: options(),
+        water: water(),
+        comparison_history: comparison_history(),
+    };
+
+    let mut apply_workspace = move |workspace: Workspace| {
+        salts.set(workspace.salts);
+        stock_solutions.set(workspace.stock_solutions);
+        targets.set(workspace.targets);
+        options.set(workspace.options);
+        water.set(workspace.water);
+        comparison_history.set(workspace.comparison_history);
+    };
+
+    // Saves the workspace whenever any part of it changes
     use_effect(move || {
-        let _deps = (
-            min_n(), max_n(), nh4_percentage(),
-            min_k(), max_k(), min_p(), max_p(),
-            min_ca(), max_ca(), min_mg(), max_mg(),
-            min_s(), max_s(), min_cl(), max_cl(),
-            min_fe(), max_fe(), min_mn(), max_mn(),
-            min_zn(), max_zn(), min_cu(), max_cu(),
-            min_b(), max_b(), min_mo(), max_mo(),
-            salts().len() // Trigger on salt changes
-        );
-        
-        let enabled_salts: Vec<_> = salts().into_iter().filter(|s| s.enabled).collect();
-        if enabled_salts.is_empty() {
-            error_msg.set(Some("Keine Salze ausgewählt".to_string()));
-            return;
+        if let Err(e) = persistence::save(&current_workspace()) {
+            workspace_error.set(Some(e.to_string()));
         }
-        
-        let optimization_result = optimize_recipe(
-            min_n(), max_n(), nh4_ratio(),
-            min_k(), max_k(), min_p(), max_p(),
-            min_ca(), max_ca(), min_mg(), max_mg(),
-            min_s(), max_s(), min_cl(), max_cl(),
-            min_fe(), max_fe(), min_mn(), max_mn(),
-            min_zn(), max_zn(), min_cu(), max_cu(),
-            min_b(), max_b(), min_mo(), max_mo(),
-            false, &enabled_salts
-        );
-        
-        match optimization_result {
-            Ok(res) => {
-                current_result.set(Some(res));
-                error_msg.set(None);
+    });
+
+    let reset_workspace = move |_| {
+        apply_workspace(Workspace::default());
+        workspace_error.set(persistence::clear().err().map(|e| e.to_string()));
+    };
+
+    let export_workspace = move |_| match current_workspace().to_pretty_json() {
+        Ok(json) => download_file(WORKSPACE_FILE_NAME, "application/json", json),
+        Err(e) => workspace_error.set(Some(format!("Export fehlgeschlagen: {}", e))),
+    };
+
+    // Backs up the current workspace, then shows the shared recipe
+    let open_shared_recipe = move |_| {
+        let Some(code) = opened_link() else { return };
+        opened_link.set(None);
+        let current = current_workspace();
+        match share::decode(&code, &current) {
+            Ok(shared) => {
+                workspace_error.set(persistence::backup(&current).err().map(|e| e.to_string()));
+                apply_workspace(shared);
+                replaced_workspace.set(Some(current));
             }
-            Err(e) => {
-                error_msg.set(Some(format!("Nicht lösbar: {}", e)));
+            Err(e) => workspace_error.set(Some(format!("Der geteilte Link ist ungültig: {:#}", e))),
+        }
+    };
+
+    let undo_shared_recipe = move |_| {
+        if let Some(previous) = replaced_workspace() {
+            apply_workspace(previous);
+        }
+        replaced_workspace.set(None);
+    };
+
+    let share_recipe = move |_| match share::encode(&current_workspace()) {
+        Ok(code) => {
+            let link = share::link(&code);
+            copy_to_clipboard(&link);
+            share_link.set(Some(link));
+        }
+        Err(e) => workspace_error.set(Some(format!("Link konnte nicht erstellt werden: {}", e))),
+    };
+
+    let import_workspace = move |evt: FormEvent| async move {
+        let Some(file) = evt.files().into_iter().next() else { return };
+        let imported = match file.read_string().await {
+            Ok(json) => Workspace::from_json(&json),
+            Err(e) => Err(anyhow::anyhow!("Datei nicht lesbar: {}", e)),
+        };
+        match imported {
+            Ok(workspace) => {
+                apply_workspace(workspace);
+                workspace_error.set(None);
             }
+            Err(e) => workspace_error.set(Some(format!("Import von {} fehlgeschlagen: {:#}", file.name(), e))),
         }
-    });
+    };
 
     let save_recipe = move |_| {
         if let Some(res) = current_result() {
             let entry = ComparisonEntry {
                 result: res.clone(),
                 timestamp: format!("{:.0}% NH₄⁺", nh4_percentage()),
+                infeasible: best_effort().then(|| infeasibility().map_or_else(|| Unsolvable.to_string(), |i| i.describe(&display_units()))),
             };
             comparison_history.with_mut(|history| {
                 history.push(entry);
@@ -144,6 +217,11 @@ pub fn FertilizerOptimizer() -> Element {
         comparison_history.set(Vec::new());
     };
 
+    let analyze_sensitivity = move || {
+        let report = sensitivity::analyze(&build_problem()).map_err(|e| e.to_string());
+        sensitivity_report.set(Some(report));
+    };
+
     rsx! {
         div { class: "container",
             header { class: "header",
@@ -156,7 +234,7 @@ pub fn FertilizerOptimizer() -> Element {
                         "Die Parameter können links eingestellt werden, wobei die Berechnung in Echtzeit erfolgt. Die 'Optimale Rezeptur' zeigt die berechneten Salzmengen in g/L für Stammlösungen A und B. Der 'Vergleich der Nährlösungs-Rezepturen' dokumentiert mittels ‘Rezeptur speichern’ verschiedene NH₄⁺-Anteile mit den resultierenden Nährstoffkonzentrationen und ermöglicht den direkten Vergleich gespeicherter Rezepturen."
                     }
                     p { class: "subtitle-demo", 
-                        "Klicken Sie in das NH₄⁺-Feld und nutzen die Pfeiltasten ↑/↓, um zu beobachten, wie sich die 'Optimale Rezeptur' und der 'Vergleich der Nährlösungs-Rezepturen' in Echtzeit verändern."
+                        "Klicken Sie in das NH₄⁺-Feld und nutzen die Pfeiltasten ↑/↓, um zu beobachten, wie sich die 'Optimale Rezeptur' und der 'Vergleich der Nährlösungs-Rezepturen' in Echtzeit verändern. Die 'Parameterstudie' berechnet eine ganze Reihe auf einmal."
                     }
                     p { class: "subtitle-usage",
                         "Neu: Neben den Makronährstoffen werden nun auch Mikronährstoffe in der Optimierung berücksichtigt. Zudem haben Sie die Möglichkeit, eigene Nährsalze hinzuzufügen und die Datenbank individuell zu erweitern."
@@ -172,6 +250,59 @@ pub fn FertilizerOptimizer() -> Element {
                     onclick: move |_| show_salt_manager.set(!show_salt_manager()),
                     if show_salt_manager() { "✓ Nährsalz & Stammlösungen" } else { "Nährsalz & Stammlösungen" }
                 }
+                button {
+                    class: "toolbar-btn",
+                    title: "Salze, Stammlösungen, Zielwerte, Wasser und Vergleich werden im Browser gespeichert",
+                    onclick: reset_workspace,
+                    "Arbeitsbereich zurücksetzen"
+                }
+                button {
+                    class: "toolbar-btn",
+                    title: "Salze, Stammlösungen, Zielwerte, Optionen, Wasser und Vergleich als JSON-Datei speichern",
+                    onclick: export_workspace,
+                    "⬇ Exportieren"
+                }
+                button {
+                    class: "toolbar-btn",
+                    title: "Zielwerte, Optionen, Wasser, Stammlösungen und Salze als Link; der Vergleich bleibt lokal",
+                    onclick: share_recipe,
+                    "🔗 Link teilen"
+                }
+                label {
+                    class: "toolbar-btn",
+                    title: "Arbeitsbereich aus einer JSON-Datei laden; ersetzt den aktuellen",
+                    "⬆ Importieren"
+                    input {
+                        r#type: "file",
+                        accept: ".json,application/json",
+                        class: "hidden-file-input",
+                        onchange: import_workspace,
+                    }
+                }
+            }
+            if let Some(message) = workspace_error() {
+                div { class: "cost-warning", "⚠️ {message}" }
+            }
+            if opened_link().is_some() {
+                div { class: "share-link",
+                    small { "Geteilte Rezeptur öffnen? Ziele, Optionen, Wasser, Stammlösungen und Salze werden ersetzt; eigene Salze bleiben abgeschaltet erhalten." }
+                    button { class: "analyze-btn", onclick: open_shared_recipe, "Übernehmen" }
+                    button { class: "analyze-btn", onclick: move |_| opened_link.set(None), "Verwerfen" }
+                }
+            }
+            if replaced_workspace().is_some() {
+                div { class: "share-link",
+                    small { "Geteilte Rezeptur übernommen; der vorherige Arbeitsbereich liegt unter \"{persistence::BACKUP_KEY}\"." }
+                    button { class: "analyze-btn", onclick: undo_shared_recipe, "Rückgängig" }
+                    button { class: "analyze-btn", onclick: move |_| replaced_workspace.set(None), "Schließen" }
+                }
+            }
+            if let Some(link) = share_link() {
+                div { class: "share-link",
+                    small { "Link in die Zwischenablage kopiert – wer ihn öffnet, sieht dieselbe Rezeptur:" }
+                    input { r#type: "text", readonly: true, value: "{link}" }
+                    button { class: "analyze-btn", onclick: move |_| share_link.set(None), "Schließen" }
+                }
             }
 
             // Unified Salt & Stock Solution Manager
@@ -185,11 +316,41 @@ pub fn FertilizerOptimizer() -> Element {
                     div { class: "error-banner-content",
                         div { class: "error-icon", "⚠️" }
                         div { class: "error-text",
-                            div { class: "error-title", "Optimierung nicht möglich" }
+                            div { class: "error-title",
+                                if best_effort() {
+                                    "Zielwerte nicht exakt erreichbar – nächstgelegene Rezeptur wird angezeigt"
+                                } else {
+                                    "Optimierung nicht möglich"
+                                }
+                            }
                             div { class: "error-message", "{error}" }
                         }
-                        div { class: "error-hint",
-                            "💡 Tipp: Passen Sie die Nährstoffbereiche an oder aktivieren Sie zusätzliche Salze"
+                        if unsolvable() && infeasibility().is_none() {
+                            div { class: "error-hint",
+                                button {
+                                    class: "analyze-btn",
+                                    title: "Sucht die widersprüchlichen Grenzen; braucht je Grenze eine eigene Optimierung",
+                                    onclick: move |_| {
+                                        let diagnosis = diagnose(&build_problem());
+                                        error_msg.set(Some(format!("Nicht lösbar: {}", diagnosis.describe(&display_units()))));
+                                        infeasibility.set(Some(diagnosis));
+                                    },
+                                    "Ursache ermitteln"
+                                }
+                            }
+                        } else if let Some(diagnosis) = infeasibility().filter(|d| !d.relaxations.is_empty()) {
+                            div { class: "error-hint",
+                                "💡 Kleinste Lockerung, die eine Lösung ermöglicht:"
+                                ul { class: "relaxation-list",
+                                    for relaxation in diagnosis.relaxations.iter() {
+                                        li { "{relaxation.describe(&display_units())}" }
+                                    }
+                                }
+                            }
+                        } else {
+                            div { class: "error-hint",
+                                "💡 Tipp: Passen Sie die Nährstoffbereiche an oder aktivieren Sie zusätzliche Salze"
+                            }
                         }
                     }
                 }
@@ -207,26 +368,105 @@ pub fn FertilizerOptimizer() -> Element {
                                 if show_micronutrients() { "✓ Mikronährstoffe" } else { "Mikronährstoffe" }
                             }
                         }
+
+                        div { class: "input-group",
+                            label { "Zielangaben" }
+                            div { class: "range-inputs",
+                                select {
+                                    class: "objective-select",
+                                    onchange: move |evt| {
+                                        let basis = if evt.value() == "final" { TargetBasis::Final } else { TargetBasis::Stock };
+                                        let system = UnitSystem::of(&targets());
+                                        let rebased = targets().rebase(basis, |n| system.unit_for(n, basis), options().dilution_ratio);
+                                        targets.set(rebased);
+                                    },
+                                    option { value: "stock", selected: targets().basis == TargetBasis::Stock, "{TargetBasis::Stock.label()}" }
+                                    option { value: "final", selected: targets().basis == TargetBasis::Final, "{TargetBasis::Final.label()}" }
+                                }
+                                {render_unit_system_select(UnitSystem::of(&targets()), move |system| {
+                                    let basis = targets().basis;
+                                    let rebased = targets().rebase(basis, |n| system.unit_for(n, basis), options().dilution_ratio);
+                                    targets.set(rebased);
+                                })}
+                            }
+                        }
+
+                        div { class: "input-group",
+                            label { "Zielfunktion" }
+                            select {
+                                class: "objective-select",
+                                onchange: move |evt| {
+                                    if let Some(objective) = evt.value().parse::<usize>().ok().and_then(|i| Objective::ALL.get(i)) {
+                                        options.with_mut(|o| o.objective = *objective);
+                                    }
+                                },
+                                for (i, objective) in Objective::ALL.into_iter().enumerate() {
+                                    option { value: "{i}", selected: options().objective == objective, "{objective.label()}" }
+                                }
+                            }
+                            if show_targets() {
+                                small { "Zielwert leer lassen, um jeden Wert innerhalb von Min/Max zu akzeptieren" }
+                            }
+                        }
+
+                        div { class: "input-group",
+                            label { "Ansatztemperatur (°C)" }
+                            input {
+                                r#type: "number",
+                                step: "1",
+                                value: "{options().temperature}",
+                                oninput: move |evt| {
+                                    if let Ok(temperature) = evt.value().parse::<f64>() {
+                                        options.with_mut(|o| o.temperature = temperature);
+                                    }
+                                }
+                            }
+                            label { class: "checkbox-label",
+                                input {
+                                    r#type: "checkbox",
+                                    checked: options().split_salts,
+                                    onchange: move |evt| options.with_mut(|o| o.split_salts = evt.checked())
+                                }
+                                "Salze bei Bedarf auf mehrere Stammlösungen aufteilen"
+                            }
+                            label { class: "checkbox-label",
+                                input {
+                                    r#type: "checkbox",
+                                    checked: options().auto_assign_tanks,
+                                    onchange: move |evt| options.with_mut(|o| o.auto_assign_tanks = evt.checked())
+                                }
+                                "Salze automatisch ohne Ausfällungen auf die Stammlösungen verteilen"
+                            }
+                            small { "Die Löslichkeit begrenzt die Salzmenge je Stammlösung" }
+                        }
+
+                        {render_charge_tolerance_input(options)}
+                        {render_salt_usage_input(options)}
                         
                         // Macronutrient inputs
-                        {render_nutrient_input("Stickstoff (g l⁻¹)", "N", min_n, max_n)}
-                        {render_nitrogen_ratio_input(nh4_percentage, no3_percentage)}
-                        {render_nutrient_input("Kalium (g l⁻¹)", "K", min_k, max_k)}
-                        {render_nutrient_input("Phosphor (g l⁻¹)", "P", min_p, max_p)}
-                        {render_nutrient_input("Kalzium (g l⁻¹)", "Ca", min_ca, max_ca)}
-                        {render_nutrient_input("Magnesium (g l⁻¹)", "Mg", min_mg, max_mg)}
-                        {render_nutrient_input("Schwefel (g l⁻¹)", "S", min_s, max_s)}
-                        {render_nutrient_input("Chlorid (g l⁻¹)", "Cl", min_cl, max_cl)}
+                        {render_nitrogen_input(targets)}
+                        {render_nitrogen_ratio_input(targets)}
+                        for nutrient in Nutrient::ALL.into_iter().filter(|n| n.is_macronutrient() && !n.is_nitrogen()) {
+                            {render_nutrient_input(nutrient, targets, show_targets())}
+                        }
+                        {render_ec_input(targets)}
+                        {render_range_input(
+                            "pH der Nährlösung".to_string(),
+                            targets().ph,
+                            move |ph| targets.with_mut(|t| t.ph = ph),
+                            Some(rsx! { small { "Erfordert freigegebene Säuren oder Laugen; Min 0 lässt den pH frei" } }),
+                        )}
                         
                         // Micronutrient inputs (collapsible)
                         if show_micronutrients() {
-                            h3 { "Mikronährstoffe (mg l⁻¹)" }
-                            {render_nutrient_input("Eisen (mg l⁻¹)", "Fe", min_fe, max_fe)}
-                            {render_nutrient_input("Mangan (mg l⁻¹)", "Mn", min_mn, max_mn)}
-                            {render_nutrient_input("Zink (mg l⁻¹)", "Zn", min_zn, max_zn)}
-                            {render_nutrient_input("Kupfer (mg l⁻¹)", "Cu", min_cu, max_cu)}
-                            {render_nutrient_input("Bor (mg l⁻¹)", "B", min_b, max_b)}
-                            {render_nutrient_input("Molybdän (mg l⁻¹)", "Mo", min_mo, max_mo)}
+                            h3 { "Mikronährstoffe" }
+                            for nutrient in Nutrient::ALL.into_iter().filter(|n| n.is_micronutrient()) {
+                                {render_nutrient_input(nutrient, targets, show_targets())}
+                            }
+                            h3 { "Weitere Bestandteile" }
+                            for nutrient in Nutrient::ALL.into_iter().filter(|n| n.is_other_element()) {
+                                {render_nutrient_input(nutrient, targets, show_targets())}
+                            }
                         }
 
                         if let Some(_) = result() {
@@ -235,14 +475,33 @@ pub fn FertilizerOptimizer() -> Element {
                             }
                         }
                     }
+
+                    WaterProfile { water, options, stock_solutions }
                 }
 
                 // Right column - Results
                 div { class: "right-column",
                     if let Some(res) = result() {
                         div { class: "results-section",
-                            h2 { "Optimale Rezeptur" }
+                            h2 { if res.deviations.is_empty() { "Optimale Rezeptur" } else { "Nächstgelegene Rezeptur" } }
                             
+                            if !res.conflicts.is_empty() {
+                                div { class: "incompatibility-warning",
+                                    div { class: "warning-header",
+                                        span { class: "warning-icon", "⚠️" }
+                                        span { class: "warning-title", "Rezeptur so nicht mischbar" }
+                                    }
+                                    ul {
+                                        for conflict in res.conflicts.iter() {
+                                            li { "{conflict}" }
+                                        }
+                                    }
+                                    div { class: "warning-action",
+                                        "→ Salze umverteilen oder die automatische Verteilung aktivieren"
+                                    }
+                                }
+                            }
+
                             div { class: "recipe-table",
                                 h3 { "Nährsalz in Gramm pro 1 Liter Stammlösung" }
                                 table {
@@ -258,54 +517,49 @@ pub fn FertilizerOptimizer() -> Element {
                                     tbody {
                                         // Display all salts from the recipe, grouped by stock solution
                                         for solution in stock_solutions().iter() {
-                                            for (name, amount) in res.recipe.iter() {
-                                                // Find the salt to check its stock solution assignment
-                                                if let Some(salt) = salts().iter().find(|s| s.name == *name) {
-                                                    if salt.stock_solution == *solution {
-                                                        tr { key: "{solution}-{name}",
-                                                            td { class: "salt-name", "{name}" }
-                                                            // Create cells for each solution
-                                                            for sol in stock_solutions().iter() {
-                                                                if sol == solution {
-                                                                    td { class: "amount", "{amount:.2}" }
-                                                                } else {
-                                                                    td { class: "amount", "—" }
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                            
-                                            // Add fixed micronutrient mixes if they're assigned to this solution
-                                            if solution == "A" && salts().iter().any(|s| s.name == "Ferty 72" && s.enabled && s.stock_solution == "A") {
-                                                tr { key: "{solution}-ferty72",
-                                                    td { class: "salt-name", "Ferty 72" }
+                                            for entry in res.recipe.iter().filter(|e| e.tank == *solution) {
+                                                tr { key: "{solution}-{entry.salt}",
+                                                    td { class: "salt-name", "{entry.salt}" }
+                                                    // Create cells for each solution
                                                     for sol in stock_solutions().iter() {
-                                                        if sol == "A" {
-                                                            td { class: "amount", "0.30" }
+                                                        if sol == solution {
+                                                            td { class: "amount", "{format_amount(entry.amount, &entry.salt, &salts())}" }
                                                         } else {
                                                             td { class: "amount", "—" }
                                                         }
                                                     }
                                                 }
                                             }
-                                            if solution == "B" && salts().iter().any(|s| s.name == "Ferty 10" && s.enabled && s.stock_solution == "B") {
-                                                tr { key: "{solution}-ferty10",
-                                                    td { class: "salt-name", "Ferty 10" }
-                                                    for sol in stock_solutions().iter() {
-                                                        if sol == "B" {
-                                                            td { class: "amount", "2.24" }
-                                                        } else {
-                                                            td { class: "amount", "—" }
-                                                        }
-                                                    }
-                                                }
+                                        }
+                                    }
+                                    tfoot {
+                                        tr { class: "tank-load-row",
+                                            td { class: "salt-name", "Löslichkeit ausgeschöpft" }
+                                            for sol in stock_solutions().iter() {
+                                                {render_tank_load(res.tank_loads.get(sol).copied())}
                                             }
                                         }
                                     }
                                 }
                             }
+
+                            if !res.deviations.is_empty() {
+                                div { class: "deviation-report",
+                                    h3 { "Abweichungen von den Zielwerten" }
+                                    ul {
+                                        for deviation in res.deviations.iter() {
+                                            li { "{deviation.describe(&display_units())}" }
+                                        }
+                                    }
+                                }
+                            }
+
+                            {render_final_concentrations(&res, options().dilution_ratio, show_micronutrients(), result_units)}
+                            {render_charge_balance(&res.charge_balance)}
+                            {render_cost(&res.cost)}
+                            if matches!(options().objective, Objective::Mass | Objective::Cost) && !best_effort() {
+                                {render_sensitivity(sensitivity_report(), analyze_sensitivity)}
+                            }
                         }
                     }
 
@@ -313,6 +567,7 @@ pub fn FertilizerOptimizer() -> Element {
                     div { class: "comparison-section",
                         div { class: "comparison-header",
                             h2 { "Vergleich der Nährlösungs-Rezepturen" }
+                            {render_unit_system_select(comparison_units(), move |system| comparison_units.set(system))}
                             if !comparison_history().is_empty() {
                                 button { class: "clear-btn", onclick: clear_history,
                                     "Verlauf löschen"
@@ -324,23 +579,12 @@ pub fn FertilizerOptimizer() -> Element {
                             table {
                                 thead {
                                     tr {
-                                        th { "NH₄⁺-Anteil" }
-                                        th { "NH₄⁺ (g l⁻¹)" }
-                                        th { "NO₃⁻ (g l⁻¹)" }
-                                        th { "K⁺ (g l⁻¹)" }
-                                        th { "P (g l⁻¹)" }
-                                        th { "Ca²⁺ (g l⁻¹)" }
-                                        th { "Mg²⁺ (g l⁻¹)" }
-                                        th { "S (g l⁻¹)" }
-                                        th { "Cl⁻ (g l⁻¹)" }
-                                        if show_micronutrients() {
-                                            th { "Fe (mg l⁻¹)" }
-                                            th { "Mn (mg l⁻¹)" }
-                                            th { "Zn (mg l⁻¹)" }
-                                            th { "Cu (mg l⁻¹)" }
-                                            th { "B (mg l⁻¹)" }
-                                            th { "Mo (mg l⁻¹)" }
+                                        th { "Variante" }
+                                        for nutrient in visible_nutrients(show_micronutrients()) {
+                                            {render_nutrient_header(nutrient, comparison_units())}
                                         }
+                                        th { "EC (mS cm⁻¹)" }
+                                        th { "pH" }
                                         th { "Status" }
                                     }
                                 }
@@ -349,45 +593,19 @@ pub fn FertilizerOptimizer() -> Element {
                                     for entry in comparison_history().iter() {
                                         tr {
                                             td { class: "ratio-cell", "{entry.timestamp}" }
-                                            td { class: "nutrient-cell nh4", "{format_value(entry.result.nh4_actual)}" }
-                                            td { class: "nutrient-cell no3", "{format_value(entry.result.no3_actual)}" }
-                                            td { class: "nutrient-cell k", "{format_value(entry.result.k_actual)}" }
-                                            td { class: "nutrient-cell p", "{format_value(entry.result.p_actual)}" }
-                                            td { class: "nutrient-cell ca", "{format_value(entry.result.ca_actual)}" }
-                                            td { class: "nutrient-cell mg", "{format_value(entry.result.mg_actual)}" }
-                                            td { class: "nutrient-cell s", "{format_value(entry.result.s_actual)}" }
-                                            td { class: "nutrient-cell cl", "{format_value(entry.result.cl_actual)}" }
-                                            if show_micronutrients() {
-                                                td { class: "nutrient-cell fe", "{format_value(entry.result.fe_actual)}" }
-                                                td { class: "nutrient-cell mn", "{format_value(entry.result.mn_actual)}" }
-                                                td { class: "nutrient-cell zn", "{format_value(entry.result.zn_actual)}" }
-                                                td { class: "nutrient-cell cu", "{format_value(entry.result.cu_actual)}" }
-                                                td { class: "nutrient-cell b", "{format_value(entry.result.b_actual)}" }
-                                                td { class: "nutrient-cell mo", "{format_value(entry.result.mo_actual)}" }
+                                            {render_nutrient_cells(&entry.result, show_micronutrients(), comparison_units())}
+                                            if let Some(reason) = &entry.infeasible {
+                                                td { class: "status-infeasible", title: "{reason}", "✗ Nicht lösbar" }
+                                            } else {
+                                                td { class: "status-saved", "💾 Gespeichert" }
                                             }
-                                            td { class: "status-saved", "💾 Gespeichert" }
                                         }
                                     }
                                     // Show current live result
                                     if let Some(current) = current_result() {
                                         tr { class: "current-row",
                                             td { class: "ratio-cell current", "{nh4_percentage():.0} % NH₄⁺" }
-                                            td { class: "nutrient-cell nh4", "{format_value(current.nh4_actual)}" }
-                                            td { class: "nutrient-cell no3", "{format_value(current.no3_actual)}" }
-                                            td { class: "nutrient-cell k", "{format_value(current.k_actual)}" }
-                                            td { class: "nutrient-cell p", "{format_value(current.p_actual)}" }
-                                            td { class: "nutrient-cell ca", "{format_value(current.ca_actual)}" }
-                                            td { class: "nutrient-cell mg", "{format_value(current.mg_actual)}" }
-                                            td { class: "nutrient-cell s", "{format_value(current.s_actual)}" }
-                                            td { class: "nutrient-cell cl", "{format_value(current.cl_actual)}" }
-                                            if show_micronutrients() {
-                                                td { class: "nutrient-cell fe", "{format_value(current.fe_actual)}" }
-                                                td { class: "nutrient-cell mn", "{format_value(current.mn_actual)}" }
-                                                td { class: "nutrient-cell zn", "{format_value(current.zn_actual)}" }
-                                                td { class: "nutrient-cell cu", "{format_value(current.cu_actual)}" }
-                                                td { class: "nutrient-cell b", "{format_value(current.b_actual)}" }
-                                                td { class: "nutrient-cell mo", "{format_value(current.mo_actual)}" }
-                                            }
+                                            {render_nutrient_cells(&current, show_micronutrients(), comparison_units())}
                                             td { class: "status-live", "🔄 Live" }
                                         }
                                     }
@@ -395,14 +613,17 @@ pub fn FertilizerOptimizer() -> Element {
                             }
                         }
                     }
+
+                    SweepPanel { targets, options, salts, water, stock_solutions, comparison_history }
+                    ParetoPanel { targets, options, salts, water, stock_solutions, comparison_history }
                 }
             }
         }
     }
 }
 
-// Helper function to render a nutrient input group
-fn render_nutrient_input(label: &str, _symbol: &str, mut min: Signal<f64>, mut max: Signal<f64>) -> Element {
+// Helper function to render a min/max input group
+fn render_range_input(label: String, range: Range, on_change: impl FnMut(Range) + Copy + 'static, extra: Option<Element>) -> Element {
     rsx! {
         div { class: "input-group",
             label { "{label}" }
@@ -412,10 +633,11 @@ fn render_nutrient_input(label: &str, _symbol: &str, mut min: Signal<f64>, mut m
                     input {
                         r#type: "number",
                         step: "0.1",
-                        value: "{min}",
+                        value: "{range.min}",
                         oninput: move |evt| {
                             if let Ok(val) = evt.value().parse::<f64>() {
-                                min.set(val);
+                                let mut on_change = on_change;
+                                on_change(Range { min: val, ..range });
                             }
                         }
                     }
@@ -425,10 +647,82 @@ fn render_nutrient_input(label: &str, _symbol: &str, mut min: Signal<f64>, mut m
                     input {
                         r#type: "number",
                         step: "0.1",
-                        value: "{max}",
+                        placeholder: "∞",
+                        value: if range.max.is_finite() { "{range.max}" } else { "" },
                         oninput: move |evt| {
-                            if let Ok(val) = evt.value().parse::<f64>() {
-                                max.set(val);
+                            // An empty field leaves the maximum open
+                            let parsed = match evt.value().trim() {
+                                "" => Ok(f64::INFINITY),
+                                text => text.parse::<f64>(),
+                            };
+                            if let Ok(val) = parsed {
+                                let mut on_change = on_change;
+                                on_change(Range { max: val, ..range });
+                            }
+                        }
+                    }
+                }
+            }
+            {extra}
+        }
+    }
+}
+
+// Helper function to render the input group of a single nutrient
+fn render_nutrient_input(nutrient: Nutrient, mut targets: Signal<NutrientTargets>, show_target: bool) -> Element {
+    let target = targets().get(nutrient);
+    let label = format!("{} ({})", nutrient.name(), target.unit.label_for(nutrient));
+    let target_input = show_target.then(|| render_target_input(nutrient, targets));
+    render_range_input(label, target.range, move |range| {
+        targets.with_mut(|t| t.set_range(nutrient, range));
+    }, target_input)
+}
+
+// Helper function to render preferred value and weight of a nutrient (deviation objective)
+fn render_target_input(nutrient: Nutrient, mut targets: Signal<NutrientTargets>) -> Element {
+    let target = targets().get(nutrient);
+    let preferred = target.target.map(|v| v.to_string()).unwrap_or_default();
+    let weight = target.weight_or_default();
+    rsx! {
+        div { class: "range-inputs target-inputs",
+            div { class: "range-field",
+                label { "Ziel" }
+                input {
+                    r#type: "number",
+                    step: "0.1",
+                    placeholder: "–",
+                    value: "{preferred}",
+                    oninput: move |evt| {
+                        let value = evt.value();
+                        let parsed = match value.trim() {
+                            "" => Some(None),
+                            text => text.parse::<f64>().ok().map(Some),
+                        };
+                        if let Some(preferred) = parsed {
+                            targets.with_mut(|t| {
+                                let mut target = t.get(nutrient);
+                                target.target = preferred;
+                                t.set(nutrient, target);
+                            });
+                        }
+                    }
+                }
+            }
+            div { class: "range-field",
+                label { "Gewicht" }
+                input {
+                    r#type: "number",
+                    step: "0.1",
+                    min: "0",
+                    value: "{weight}",
+                    oninput: move |evt| {
+                        if let Ok(val) = evt.value().parse::<f64>() {
+                            if val >= 0.0 {
+                                targets.with_mut(|t| {
+                                    let mut target = t.get(nutrient);
+                                    target.weight = Some(val);
+                                    t.set(nutrient, target);
+                                });
                             }
                         }
                     }
@@ -438,6 +732,223 @@ fn render_nutrient_input(label: &str, _symbol: &str, mut min: Signal<f64>, mut m
     }
 }
 
+// Helper function to render the total nitrogen input group
+fn render_nitrogen_input(mut targets: Signal<NutrientTargets>) -> Element {
+    let label = format!("Stickstoff ({})", targets().nitrogen_unit().label());
+    render_range_input(label, targets().nitrogen, move |range| {
+        targets.with_mut(|t| t.nitrogen = range);
+    }, None)
+}
+
+// Nutrients shown as comparison columns; micronutrients and other elements only on demand
+fn visible_nutrients(show_micronutrients: bool) -> impl Iterator<Item = Nutrient> {
+    Nutrient::ALL.into_iter().filter(move |n| show_micronutrients || n.is_macronutrient())
+}
+
+// Comparison column header with the unit of the stock solution
+fn render_nutrient_header(nutrient: Nutrient, system: UnitSystem) -> Element {
+    let unit = system.unit_for(nutrient, TargetBasis::Stock);
+    rsx! {
+        th { key: "{nutrient.key()}", "{unit.symbol(nutrient)} ({unit.label()})" }
+    }
+}
+
+// Helper function to render one comparison cell per nutrient
+fn render_nutrient_cells(result: &OptimizationResult, show_micronutrients: bool, system: UnitSystem) -> Element {
+    let cells: Vec<(Nutrient, f64)> = visible_nutrients(show_micronutrients)
+        .map(|n| (n, system.unit_for(n, TargetBasis::Stock).from_grams_per_liter(n, result.totals[n])))
+        .collect();
+    rsx! {
+        for (nutrient, value) in cells {
+            td { key: "{nutrient.key()}", class: "nutrient-cell {nutrient.key()}", "{format_value(value)}" }
+        }
+        td { class: "nutrient-cell ec", "{result.ec:.2}" }
+        td { class: "nutrient-cell ph", "{result.ph:.1}" }
+    }
+}
+
+// Share of a tank's solubility capacity; close to the limit the stock may crystallise when cold
+fn render_tank_load(load: Option<f64>) -> Element {
+    match load {
+        Some(load) => {
+            let percent = load * 100.0;
+            rsx! {
+                td { class: if load > TANK_LOAD_WARNING { "amount load-warning" } else { "amount" }, "{percent:.0} %" }
+            }
+        }
+        None => rsx! { td { class: "amount", "—" } },
+    }
+}
+
+// Formatted value and unit label of one concentration cell
+type Cell = (String, &'static str);
+
+// Concentrations of the recipe in the stock solution and after dilution
+fn render_final_concentrations(result: &OptimizationResult, dilution_ratio: f64, show_micronutrients: bool, mut units: Signal<UnitSystem>) -> Element {
+    let system = units();
+    let cell = |n: Nutrient, basis: TargetBasis, g_per_l: f64| -> Cell {
+        let unit = system.unit_for(n, basis);
+        (format_value(unit.from_grams_per_liter(n, g_per_l)), unit.label())
+    };
+    let rows: Vec<(Nutrient, &str, Cell, Cell)> = visible_nutrients(show_micronutrients)
+        .map(|n| (
+            n,
+            system.unit_for(n, TargetBasis::Stock).symbol(n),
+            cell(n, TargetBasis::Stock, result.totals[n]),
+            cell(n, TargetBasis::Final, result.final_totals[n]),
+        ))
+        .collect();
+    rsx! {
+        div { class: "recipe-table concentration-table",
+            div { class: "comparison-header",
+                h3 { "Nährstoffgehalte" }
+                {render_unit_system_select(system, move |system| units.set(system))}
+            }
+            table {
+                thead {
+                    tr {
+                        th { "" }
+                        for (nutrient, symbol, _, _) in rows.iter() {
+                            th { key: "{nutrient.key()}", "{symbol}" }
+                        }
+                    }
+                }
+                tbody {
+                    tr {
+                        td { class: "salt-name", "Stammlösung 1:{dilution_ratio}" }
+                        for (nutrient, _, (stock, unit), _) in rows.iter() {
+                            td { key: "{nutrient.key()}", class: "amount", title: "{unit}",
+                                "{stock}"
+                                span { class: "cell-unit", " {unit}" }
+                            }
+                        }
+                    }
+                    tr {
+                        td { class: "salt-name", "Nährlösung" }
+                        for (nutrient, _, _, (final_value, unit)) in rows.iter() {
+                            td { key: "{nutrient.key()}", class: "amount", title: "{unit}",
+                                "{final_value}"
+                                span { class: "cell-unit", " {unit}" }
+                            }
+                        }
+                    }
+                }
+            }
+            div { class: "ec-estimate", "Geschätzte EC der Nährlösung: {result.ec:.2} mS cm⁻¹ · pH {result.ph:.1}" }
+        }
+    }
+}
+
+// Select for the unit system of one view
+fn render_unit_system_select(current: UnitSystem, on_change: impl FnMut(UnitSystem) + 'static) -> Element {
+    let mut on_change = on_change;
+    rsx! {
+        select {
+            class: "unit-select",
+            onchange: move |evt| {
+                if let Some(system) = evt.value().parse::<usize>().ok().and_then(|i| UnitSystem::ALL.get(i)) {
+                    on_change(*system);
+                }
+            },
+            for (i, system) in UnitSystem::ALL.into_iter().enumerate() {
+                option { value: "{i}", selected: current == system, "{system.label()}" }
+            }
+        }
+    }
+}
+
+// Cations and anions of the final solution; a large gap points to a wrong salt composition
+fn render_charge_balance(balance: &ChargeBalance) -> Element {
+    let imbalance = balance.imbalance() * 100.0;
+    let (cations, anions) = (balance.cation_sum(), balance.anion_sum());
+    let ions: Vec<(Nutrient, f64)> = balance.cations.iter().chain(balance.anions.iter())
+        .filter(|(_, meq)| *meq > 0.0)
+        .copied()
+        .collect();
+    rsx! {
+        div { class: "charge-balance",
+            h3 { "Ionenbilanz (meq l⁻¹)" }
+            div { class: "charge-ions",
+                for (nutrient, meq) in ions {
+                    span { key: "{nutrient.key()}", class: "charge-ion", "{nutrient.symbol()} {meq:.2}" }
+                }
+            }
+            div { class: if imbalance.abs() > IMBALANCE_WARNING * 100.0 { "charge-sum load-warning" } else { "charge-sum" },
+                "Kationen {cations:.2} · Anionen {anions:.2} · Abweichung {imbalance:+.1} %"
+            }
+        }
+    }
+}
+
+// Helper function to render the recipe cost per currency
+fn render_cost(cost: &RecipeCost) -> Element {
+    if cost.per_liter_stock.is_empty() && cost.unpriced_salts.is_empty() {
+        return rsx! {};
+    }
+    let lines: Vec<(String, f64, f64)> = cost.per_liter_stock.iter()
+        .map(|(currency, &stock)| (currency.clone(), stock, cost.per_1000_l_final.get(currency).copied().unwrap_or(0.0)))
+        .collect();
+    let unpriced = cost.unpriced_salts.join(", ");
+    rsx! {
+        div { class: "cost-report",
+            h3 { "Kosten" }
+            for (currency, stock, final_solution) in lines {
+                div { key: "{currency}", class: "cost-line",
+                    "{stock:.3} {currency} pro Liter Stammlösung · {final_solution:.2} {currency} pro 1000 l Nährlösung"
+                }
+            }
+            if !cost.unpriced_salts.is_empty() {
+                small { class: "cost-warning", "⚠️ Ohne Preis (nicht berücksichtigt): {unpriced}" }
+            }
+        }
+    }
+}
+
+// Binding bounds and unused salts of the recipe, or a button to analyse them
+fn render_sensitivity(report: Option<Result<Sensitivity, String>>, mut on_analyze: impl FnMut() + 'static) -> Element {
+    rsx! {
+        div { class: "sensitivity-report",
+            h3 { "Was die Rezeptur bestimmt" }
+            match report {
+                None => rsx! {
+                    button { class: "analyze-btn", onclick: move |_| on_analyze(), "🔍 Sensitivität berechnen" }
+                    small { "Löst die Rezeptur für jede Grenze und jedes ungenutzte Salz erneut; bei gemischt-ganzzahligen Optionen kann das dauern" }
+                },
+                Some(Err(message)) => rsx! {
+                    div { class: "cost-warning", "⚠️ {message}" }
+                },
+                Some(Ok(sensitivity)) => rsx! {
+                    h4 { "Bindende Grenzen" }
+                    if sensitivity.bounds.is_empty() {
+                        p { "Keine Grenze begrenzt die Rezeptur" }
+                    }
+                    ul {
+                        for price in sensitivity.bounds.iter() {
+                            li { "{sensitivity.describe_bound(price)}" }
+                        }
+                    }
+                    if !sensitivity.unused_salts.is_empty() {
+                        h4 { "Ungenutzte Salze" }
+                        ul {
+                            for cost in sensitivity.unused_salts.iter() {
+                                li { "{sensitivity.describe_salt(cost)}" }
+                            }
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+// Amount of a salt in g/L; liquids also by volume
+fn format_amount(amount: f64, salt_name: &str, salts: &[Salt]) -> String {
+    match salts.iter().find(|s| s.name == salt_name).and_then(|s| s.density) {
+        Some(density) => format!("{:.2} ({:.2} ml)", amount, amount / density),
+        None => format!("{:.2}", amount),
+    }
+}
+
 // Helper function to format numbers, removing -0.000
 fn format_value(val: f64) -> String {
     if val.abs() < 0.0001 {
@@ -447,8 +958,107 @@ fn format_value(val: f64) -> String {
     }
 }
 
+// Upper EC limit of the final solution; an empty field leaves the EC free
+fn render_ec_input(mut targets: Signal<NutrientTargets>) -> Element {
+    let value = targets().ec_max.map(|ec| ec.to_string()).unwrap_or_default();
+    rsx! {
+        div { class: "input-group",
+            label { "EC max (mS cm⁻¹)" }
+            input {
+                r#type: "number",
+                min: "0",
+                step: "0.1",
+                placeholder: "—",
+                value: "{value}",
+                oninput: move |evt| {
+                    let ec_max = evt.value().trim().parse::<f64>().ok().filter(|ec| *ec > 0.0);
+                    targets.with_mut(|t| t.ec_max = ec_max);
+                }
+            }
+            small { "Geschätzt aus der Ionenzusammensetzung der Nährlösung inkl. Rohwasser" }
+        }
+    }
+}
+
+// Largest accepted ion balance error; an empty field leaves the balance unchecked
+fn render_charge_tolerance_input(mut options: Signal<OptimizationOptions>) -> Element {
+    let value = options().charge_balance_tolerance.map(|t| (t * 100.0).to_string()).unwrap_or_default();
+    rsx! {
+        div { class: "input-group",
+            label { "Max. Ionenbilanzfehler (%)" }
+            input {
+                r#type: "number",
+                min: "0",
+                step: "1",
+                placeholder: "—",
+                value: "{value}",
+                oninput: move |evt| {
+                    let tolerance = evt.value().trim().parse::<f64>().ok().filter(|t| *t >= 0.0).map(|t| t / 100.0);
+                    options.with_mut(|o| o.charge_balance_tolerance = tolerance);
+                }
+            }
+            small { "Kationen und Anionen der Nährlösung müssen sich bis auf diesen Anteil ausgleichen" }
+        }
+    }
+}
+
+// Practical limits on the salts; empty fields leave them open
+fn render_salt_usage_input(mut options: Signal<OptimizationOptions>) -> Element {
+    let max_salts = options().max_salts.map(|n| n.to_string()).unwrap_or_default();
+    let min_weighing = options().min_weighing.map(|g| g.to_string()).unwrap_or_default();
+    rsx! {
+        div { class: "input-group",
+            label { "Max. Anzahl Salze" }
+            input {
+                r#type: "number",
+                min: "1",
+                step: "1",
+                placeholder: "—",
+                value: "{max_salts}",
+                oninput: move |evt| {
+                    let max_salts = evt.value().trim().parse::<usize>().ok().filter(|n| *n > 0);
+                    options.with_mut(|o| o.max_salts = max_salts);
+                }
+            }
+            div { class: "ratio-inputs",
+                div { class: "ratio-field",
+                    label { "Mindesteinwaage (g)" }
+                    input {
+                        r#type: "number",
+                        min: "0",
+                        step: "0.1",
+                        placeholder: "—",
+                        value: "{min_weighing}",
+                        oninput: move |evt| {
+                            let min_weighing = evt.value().trim().parse::<f64>().ok().filter(|g| *g > 0.0);
+                            options.with_mut(|o| o.min_weighing = min_weighing);
+                        }
+                    }
+                }
+                div { class: "ratio-field",
+                    label { "Ansatzvolumen (l)" }
+                    input {
+                        r#type: "number",
+                        min: "1",
+                        step: "10",
+                        value: "{options().batch_volume}",
+                        oninput: move |evt| {
+                            if let Some(volume) = evt.value().parse::<f64>().ok().filter(|v| *v > 0.0) {
+                                options.with_mut(|o| o.batch_volume = volume);
+                            }
+                        }
+                    }
+                }
+            }
+            small { "Jedes Salz wird je Stammlösung mit mindestens dieser Menge oder gar nicht eingewogen; bei gleich guten Rezepturen gewinnt die mit weniger Salzen" }
+        }
+    }
+}
+
 // Helper function to render nitrogen ratio input
-fn render_nitrogen_ratio_input(mut nh4: Signal<f64>, mut no3: Signal<f64>) -> Element {
+fn render_nitrogen_ratio_input(mut targets: Signal<NutrientTargets>) -> Element {
+    let nh4 = targets().nh4_ratio.unwrap_or(0.0) * 100.0;
+    let no3 = 100.0 - nh4;
     rsx! {
         div { class: "input-group",
             label { "Stickstoff-Verhältnis (%)" }
@@ -463,9 +1073,8 @@ fn render_nitrogen_ratio_input(mut nh4: Signal<f64>, mut no3: Signal<f64>) -> El
                         value: "{nh4}",
                         oninput: move |evt| {
                             if let Ok(val) = evt.value().parse::<f64>() {
-                                if val >= 0.0 && val <= 100.0 {
-                                    nh4.set(val);
-                                    no3.set(100.0 - val);
+                                if (0.0..=100.0).contains(&val) {
+                                    targets.with_mut(|t| t.nh4_ratio = Some(val / 100.0));
                                 }
                             }
                         }
@@ -481,9 +1090,8 @@ fn render_nitrogen_ratio_input(mut nh4: Signal<f64>, mut no3: Signal<f64>) -> El
                         value: "{no3}",
                         oninput: move |evt| {
                             if let Ok(val) = evt.value().parse::<f64>() {
-                                if val >= 0.0 && val <= 100.0 {
-                                    no3.set(val);
-                                    nh4.set(100.0 - val);
+                                if (0.0..=100.0).contains(&val) {
+                                    targets.with_mut(|t| t.nh4_ratio = Some(1.0 - val / 100.0));
                                 }
                             }
                         }
@@ -494,3 +1102,8 @@ fn render_nitrogen_ratio_input(mut nh4: Signal<f64>, mut no3: Signal<f64>) -> El
         }
     }
 }
+
+fn copy_to_clipboard(text: &str) {
+    let copy = document::eval(CLIPBOARD_SCRIPT);
+    let _ = copy.send(text);
+}
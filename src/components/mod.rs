@@ -1,8 +1,36 @@
-/// UI components module
+//! UI components module
+
+use dioxus::prelude::*;
 
 pub mod fertilizer_optimizer;
+pub mod pareto_panel;
+pub mod salt_csv_import;
+pub mod sweep_panel;
 pub mod unified_salt_manager;
+pub mod water_profile;
 
 pub use fertilizer_optimizer::FertilizerOptimizer;
+pub use pareto_panel::ParetoPanel;
+pub use salt_csv_import::SaltCsvImport;
+pub use sweep_panel::SweepPanel;
 pub use unified_salt_manager::UnifiedSaltManager;
+pub use water_profile::WaterProfile;
+
+
+/// Offers text received as `[file name, MIME type, content]` as a download
+const DOWNLOAD_SCRIPT: &str = r#"
+    const [name, type, content] = await dioxus.recv();
+    const url = URL.createObjectURL(new Blob([content], { type }));
+    const link = document.createElement("a");
+    link.href = url;
+    link.download = name;
+    link.click();
+    URL.revokeObjectURL(url);
+"#;
 
+/// Lets the browser save `content` as a file
+pub(crate) fn download_file(name: &str, mime: &str, content: String) {
+    let download = document::eval(DOWNLOAD_SCRIPT);
+    // The script waits for its data; sending only fails outside a renderer
+    let _ = download.send((name, mime, content));
+}
@@ -0,0 +1,189 @@
+//! Pareto exploration: trade-off between two criteria as a selectable scatter plot
+
+use dioxus::prelude::*;
+use crate::models::{ComparisonEntry, Nutrient, NutrientTargets, Salt, WaterAnalysis};
+use crate::optimizer::{format_number, Criterion, OptimizationOptions, OptimizationProblem};
+use crate::pareto::{pareto_front, ParetoPoint, MAX_POINTS};
+
+/// Plot size in SVG units and the margin left for the axis labels
+const PLOT_WIDTH: f64 = 600.0;
+const PLOT_HEIGHT: f64 = 300.0;
+const PLOT_MARGIN: f64 = 45.0;
+
+#[component]
+pub fn ParetoPanel(
+    targets: Signal<NutrientTargets>,
+    options: Signal<OptimizationOptions>,
+    salts: Signal<Vec<Salt>>,
+    water: Signal<WaterAnalysis>,
+    stock_solutions: Signal<Vec<String>>,
+    comparison_history: Signal<Vec<ComparisonEntry>>,
+) -> Element {
+    let mut first = use_signal(|| Criterion::Cost);
+    let mut second = use_signal(|| Criterion::Nutrient(Nutrient::Cl));
+    let mut point_count = use_signal(|| 11usize);
+    // Criteria of the computed front, fixed when it was computed
+    let mut front = use_signal(|| None::<(Criterion, Criterion, Vec<ParetoPoint>)>);
+    let mut selected = use_signal(|| None::<usize>);
+    let mut error = use_signal(|| None::<String>);
+
+    let compute_front = move |_| {
+        let problem = OptimizationProblem::new(targets())
+            .salts(salts())
+            .options(options())
+            .water(water())
+            .tanks(stock_solutions());
+        match pareto_front(&problem, first(), second(), point_count()) {
+            Ok(points) => {
+                front.set(Some((first(), second(), points)));
+                error.set(None);
+            }
+            Err(e) => {
+                front.set(None);
+                error.set(Some(e.to_string()));
+            }
+        }
+        selected.set(None);
+    };
+
+    let adopt_selected = move |_| {
+        let Some((first, second, points)) = front() else { return };
+        let Some(point) = selected().and_then(|i| points.get(i).cloned()) else { return };
+        comparison_history.with_mut(|history| history.push(ComparisonEntry {
+            timestamp: format!("{} · {}", describe(first, point.first), describe(second, point.second)),
+            result: point.result,
+            infeasible: None,
+        }));
+    };
+
+    rsx! {
+        div { class: "sweep-section",
+            h2 { "Zielkonflikte (Pareto-Front)" }
+            div { class: "sweep-controls",
+                {render_criterion_select("Kriterium 1", first(), move |c| first.set(c))}
+                {render_criterion_select("Kriterium 2", second(), move |c| second.set(c))}
+                div { class: "input-group",
+                    label { "Punkte" }
+                    input {
+                        r#type: "number",
+                        min: "2",
+                        max: "{MAX_POINTS}",
+                        step: "1",
+                        value: "{point_count()}",
+                        oninput: move |evt| {
+                            if let Ok(count) = evt.value().parse::<usize>() {
+                                point_count.set(count);
+                            }
+                        }
+                    }
+                }
+                button { class: "analyze-btn", onclick: compute_front, "⚖️ Front berechnen" }
+            }
+            small { "Minimiert Kriterium 1, während Kriterium 2 schrittweise begrenzt wird; nur Rezepturen, die keine andere in beiden Kriterien übertrifft" }
+            if let Some(message) = error() {
+                div { class: "cost-warning", "⚠️ {message}" }
+            }
+            if let Some((first, second, points)) = front() {
+                {render_pareto_plot(first, second, &points, selected)}
+                if let Some(point) = selected().and_then(|i| points.get(i).cloned()) {
+                    div { class: "pareto-selection",
+                        h4 { "{describe(first, point.first)} · {describe(second, point.second)}" }
+                        ul {
+                            for entry in point.result.recipe.iter() {
+                                li { key: "{entry.tank}-{entry.salt}", "SL {entry.tank}: {entry.salt} {entry.amount:.2} g l⁻¹" }
+                            }
+                        }
+                        button { class: "analyze-btn", onclick: adopt_selected, "In den Vergleich übernehmen" }
+                    }
+                } else {
+                    small { "Punkt anklicken, um die Rezeptur zu sehen" }
+                }
+            }
+        }
+    }
+}
+
+// Criterion value with its unit, e.g. "Cl⁻ 12.5 g l⁻¹"
+fn describe(criterion: Criterion, value: f64) -> String {
+    let number = format_number(criterion.display_value(value));
+    match criterion.unit_label() {
+        "" => format!("{} {}", criterion.label(), number),
+        unit => format!("{} {} {}", criterion.label(), number, unit),
+    }
+}
+
+fn axis_label(criterion: Criterion) -> String {
+    match criterion.unit_label() {
+        "" => criterion.label().to_string(),
+        unit => format!("{} ({})", criterion.label(), unit),
+    }
+}
+
+fn render_criterion_select(label: &'static str, current: Criterion, mut on_change: impl FnMut(Criterion) + 'static) -> Element {
+    rsx! {
+        div { class: "input-group",
+            label { "{label}" }
+            select {
+                onchange: move |evt| {
+                    if let Some(criterion) = evt.value().parse::<usize>().ok().and_then(|i| Criterion::all().get(i).copied()) {
+                        on_change(criterion);
+                    }
+                },
+                for (i, criterion) in Criterion::all().into_iter().enumerate() {
+                    option { value: "{i}", selected: criterion == current, "{criterion.label()}" }
+                }
+            }
+        }
+    }
+}
+
+// Scatter plot of the front; the selected recipe is highlighted
+fn render_pareto_plot(first: Criterion, second: Criterion, points: &[ParetoPoint], mut selected: Signal<Option<usize>>) -> Element {
+    let xs: Vec<f64> = points.iter().map(|p| first.display_value(p.first)).collect();
+    let ys: Vec<f64> = points.iter().map(|p| second.display_value(p.second)).collect();
+    let range = |values: &[f64]| {
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        // A single point or a flat front still needs a non-empty axis
+        if max - min > 1e-9 { (min, max) } else { (min - 0.5, max + 0.5) }
+    };
+    let ((x_min, x_max), (y_min, y_max)) = (range(&xs), range(&ys));
+    let (left, right, top, bottom) = (PLOT_MARGIN, PLOT_WIDTH - PLOT_MARGIN / 2.0, PLOT_MARGIN / 2.0, PLOT_HEIGHT - PLOT_MARGIN);
+    let px = move |x: f64| left + (x - x_min) / (x_max - x_min) * (right - left);
+    let py = move |y: f64| bottom - (y - y_min) / (y_max - y_min) * (bottom - top);
+
+    let markers: Vec<(usize, f64, f64)> = xs.iter().zip(&ys).enumerate().map(|(i, (&x, &y))| (i, px(x), py(y))).collect();
+    let path = markers.iter().map(|(_, x, y)| format!("{:.1},{:.1}", x, y)).collect::<Vec<_>>().join(" ");
+    let labels = [
+        (format_number(x_min), format_number(x_max)),
+        (format_number(y_min), format_number(y_max)),
+    ];
+
+    rsx! {
+        div { class: "sweep-chart",
+            svg {
+                view_box: "0 0 {PLOT_WIDTH} {PLOT_HEIGHT}",
+                width: "100%",
+                line { x1: "{left}", y1: "{bottom}", x2: "{right}", y2: "{bottom}", stroke: "#94a3b8" }
+                line { x1: "{left}", y1: "{top}", x2: "{left}", y2: "{bottom}", stroke: "#94a3b8" }
+                text { x: "{left}", y: "{bottom + 14.0}", text_anchor: "middle", font_size: "10", fill: "#94a3b8", "{labels[0].0}" }
+                text { x: "{right}", y: "{bottom + 14.0}", text_anchor: "middle", font_size: "10", fill: "#94a3b8", "{labels[0].1}" }
+                text { x: "{left - 4.0}", y: "{bottom}", text_anchor: "end", font_size: "10", fill: "#94a3b8", "{labels[1].0}" }
+                text { x: "{left - 4.0}", y: "{top + 4.0}", text_anchor: "end", font_size: "10", fill: "#94a3b8", "{labels[1].1}" }
+                text { x: "{(left + right) / 2.0}", y: "{PLOT_HEIGHT - 8.0}", text_anchor: "middle", font_size: "11", fill: "#cbd5e1", "{axis_label(first)}" }
+                text { x: "{left}", y: "{top - 8.0}", font_size: "11", fill: "#cbd5e1", "{axis_label(second)}" }
+                polyline { points: "{path}", fill: "none", stroke: "#6366f1", stroke_width: "1.5", stroke_dasharray: "4 3" }
+                for (i, x, y) in markers {
+                    circle {
+                        key: "{i}",
+                        class: if selected() == Some(i) { "pareto-point selected" } else { "pareto-point" },
+                        cx: "{x}",
+                        cy: "{y}",
+                        r: "6",
+                        onclick: move |_| selected.set(Some(i)),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Bulk import of salts from a CSV file with column mapping and preview
+
+use std::collections::BTreeMap;
+
+use dioxus::prelude::*;
+use crate::models::{Nutrient, Salt};
+use crate::salt_csv::{self, ColumnMapping, ContentForm, ContentUnit, ImportRow, Table};
+
+#[component]
+pub fn SaltCsvImport(salts: Signal<Vec<Salt>>, on_close: EventHandler<()>) -> Element {
+    let mut table = use_signal(|| None::<Table>);
+    let mut mapping = use_signal(ColumnMapping::default);
+    // Rows the user ticked or unticked, by index; the others keep their default
+    let mut choices = use_signal(BTreeMap::<usize, bool>::new);
+    let mut error = use_signal(|| None::<String>);
+
+    let rows = use_memo(move || match table() {
+        Some(table) => salt_csv::preview(&table, &mapping(), &salts.read()),
+        None => Vec::new(),
+    });
+    let is_chosen = move |i: usize, row: &ImportRow| row.salt.is_ok() && choices.read().get(&i).copied().unwrap_or(row.selected_by_default());
+    let chosen_count = rows().iter().enumerate().filter(|(i, row)| is_chosen(*i, row)).count();
+
+    let load_file = move |evt: FormEvent| async move {
+        let Some(file) = evt.files().into_iter().next() else { return };
+        let parsed = match file.read_string().await {
+            Ok(text) => Table::parse(&text),
+            Err(e) => Err(anyhow::anyhow!("Datei nicht lesbar: {}", e)),
+        };
+        match parsed {
+            Ok(parsed) => {
+                mapping.set(ColumnMapping::guess(&parsed));
+                table.set(Some(parsed));
+                error.set(None);
+            }
+            Err(e) => {
+                table.set(None);
+                error.set(Some(format!("{} konnte nicht gelesen werden: {:#}", file.name(), e)));
+            }
+        }
+        choices.set(BTreeMap::new());
+    };
+
+    let take_over = move |_| {
+        let chosen: Vec<Salt> = rows().into_iter()
+            .enumerate()
+            .filter(|(i, row)| is_chosen(*i, row))
+            .filter_map(|(_, row)| row.salt.ok())
+            .collect();
+        salts.with_mut(|library| salt_csv::add_to_library(library, chosen));
+        table.set(None);
+        choices.set(BTreeMap::new());
+        on_close.call(());
+    };
+
+    rsx! {
+        div { class: "add-salt-form-compact csv-import",
+            h4 { "Salze aus CSV importieren" }
+            small { "Tabellen aus Excel oder LibreOffice als CSV speichern; Komma, Semikolon und Tabulator werden erkannt" }
+            div { class: "csv-import-file",
+                input {
+                    r#type: "file",
+                    accept: ".csv,.tsv,.txt,text/csv,text/tab-separated-values",
+                    onchange: load_file,
+                }
+            }
+            if let Some(message) = error() {
+                div { class: "cost-warning", "⚠️ {message}" }
+            }
+
+            if let Some(table) = table() {
+                div { class: "csv-mapping",
+                    {render_column_select("Name", &table.headers, mapping().name, move |c| mapping.with_mut(|m| m.name = c))}
+                    {render_column_select("Formel", &table.headers, mapping().formula, move |c| mapping.with_mut(|m| m.formula = c))}
+                    {render_column_select("Art", &table.headers, mapping().kind, move |c| mapping.with_mut(|m| m.kind = c))}
+                    {render_column_select("Preis (pro kg)", &table.headers, mapping().price, move |c| mapping.with_mut(|m| m.price = c))}
+                    {render_column_select("Währung", &table.headers, mapping().currency, move |c| mapping.with_mut(|m| m.currency = c))}
+                    {render_column_select("Löslichkeit (g l⁻¹)", &table.headers, mapping().solubility, move |c| mapping.with_mut(|m| m.solubility = c))}
+                    {render_column_select("Dichte (g ml⁻¹)", &table.headers, mapping().density, move |c| mapping.with_mut(|m| m.density = c))}
+                    {render_column_select("Dosis min (g l⁻¹)", &table.headers, mapping().dose_min, move |c| mapping.with_mut(|m| m.dose_min = c))}
+                    {render_column_select("Dosis max (g l⁻¹)", &table.headers, mapping().dose_max, move |c| mapping.with_mut(|m| m.dose_max = c))}
+                    div { class: "input-group",
+                        label { "Gehalte in" }
+                        select {
+                            onchange: move |evt| {
+                                if let Some(unit) = evt.value().parse::<usize>().ok().and_then(|i| ContentUnit::ALL.get(i).copied()) {
+                                    mapping.with_mut(|m| m.unit = unit);
+                                }
+                            },
+                            for (i, unit) in ContentUnit::ALL.into_iter().enumerate() {
+                                option { value: "{i}", selected: unit == mapping().unit, "{unit.label()}" }
+                            }
+                        }
+                    }
+                }
+                details { class: "nutrient-details",
+                    summary { "Nährstoffspalten ({mapping().nutrients.len()} zugeordnet)" }
+                    div { class: "csv-mapping",
+                        for nutrient in Nutrient::ALL {
+                            {render_nutrient_select(nutrient, &table.headers, mapping)}
+                        }
+                    }
+                    small { "Ohne Nährstoffspalten wird die Zusammensetzung aus der Formel berechnet" }
+                }
+
+                div { class: "comparison-table csv-preview",
+                    table {
+                        thead {
+                            tr {
+                                th { "" }
+                                th { "Zeile" }
+                                th { "Name" }
+                                th { "Zusammensetzung" }
+                                th { "Preis" }
+                                th { "Hinweis" }
+                            }
+                        }
+                        tbody {
+                            for (i, row) in rows().into_iter().enumerate() {
+                                tr { key: "{row.line}", class: if row.salt.is_err() { "csv-row-error" } else { "" },
+                                    td {
+                                        input {
+                                            r#type: "checkbox",
+                                            disabled: row.salt.is_err(),
+                                            checked: is_chosen(i, &row),
+                                            onchange: move |evt| { choices.with_mut(|c| c.insert(i, evt.checked())); }
+                                        }
+                                    }
+                                    td { "{row.line}" }
+                                    match &row.salt {
+                                        Ok(salt) => rsx! {
+                                            td { "{salt.name}" }
+                                            td { "{composition_summary(salt)}" }
+                                            td { {salt.price.as_ref().map(|p| format!("{:.2} {}", p.per_kg, p.currency)).unwrap_or_else(|| "—".to_string())} }
+                                            td { {row.duplicate.clone().unwrap_or_default()} }
+                                        },
+                                        Err(message) => rsx! {
+                                            td { colspan: "3", "" }
+                                            td { "⚠️ {message}" }
+                                        },
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                small { "Zeilen mit Hinweis sind nicht vorausgewählt; ein angehaktes Salz mit vorhandenem Namen überschreibt dessen Werte, Stammlösung und Dosis bleiben ohne eigene Angabe erhalten" }
+            }
+
+            div { class: "form-actions-compact",
+                button {
+                    class: "btn-add",
+                    disabled: chosen_count == 0,
+                    onclick: take_over,
+                    "{chosen_count} Salze übernehmen"
+                }
+                button { class: "btn-cancel", onclick: move |_| on_close.call(()), "Abbrechen" }
+            }
+        }
+    }
+}
+
+// e.g. "K⁺ 38.7 % · NO₃⁻ 61.3 %"
+fn composition_summary(salt: &Salt) -> String {
+    salt.composition.non_zero()
+        .map(|(nutrient, fraction)| format!("{} {:.1} %", nutrient.symbol(), fraction * 100.0))
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+fn render_column_select(label: &'static str, headers: &[String], current: Option<usize>, mut on_change: impl FnMut(Option<usize>) + 'static) -> Element {
+    rsx! {
+        div { class: "input-group",
+            label { "{label}" }
+            select {
+                onchange: move |evt| on_change(evt.value().parse::<usize>().ok()),
+                option { value: "", selected: current.is_none(), "—" }
+                for (i, header) in headers.iter().enumerate() {
+                    option { value: "{i}", selected: current == Some(i), "{header}" }
+                }
+            }
+        }
+    }
+}
+
+// Column of a nutrient and, where there is a choice, the form its content is given in
+fn render_nutrient_select(nutrient: Nutrient, headers: &[String], mut mapping: Signal<ColumnMapping>) -> Element {
+    let current = mapping().nutrients.get(&nutrient).copied();
+    let forms = ContentForm::available(nutrient);
+    let form_select = (forms.len() > 1).then(|| rsx! {
+        select {
+            disabled: current.is_none(),
+            onchange: move |evt| {
+                let form = evt.value().parse::<usize>().ok().and_then(|i| ContentForm::available(nutrient).get(i).copied());
+                mapping.with_mut(|m| {
+                    if let (Some(form), Some(entry)) = (form, m.nutrients.get_mut(&nutrient)) {
+                        entry.1 = form;
+                    }
+                });
+            },
+            for (i, form) in forms.iter().enumerate() {
+                option { value: "{i}", selected: current.is_some_and(|(_, f)| f == *form), "{form.label(nutrient)}" }
+            }
+        }
+    });
+
+    rsx! {
+        div { class: "input-group",
+            label { "{nutrient.symbol()}" }
+            div { class: "csv-nutrient-column",
+                select {
+                    onchange: move |evt| {
+                        let column = evt.value().parse::<usize>().ok();
+                        mapping.with_mut(|m| match column {
+                            Some(column) => {
+                                let form = m.nutrients.get(&nutrient).map(|&(_, form)| form).unwrap_or_default();
+                                m.nutrients.insert(nutrient, (column, form));
+                            }
+                            None => {
+                                m.nutrients.remove(&nutrient);
+                            }
+                        });
+                    },
+                    option { value: "", selected: current.is_none(), "—" }
+                    for (i, header) in headers.iter().enumerate() {
+                        option { value: "{i}", selected: current.is_some_and(|(c, _)| c == i), "{header}" }
+                    }
+                }
+                {form_select}
+            }
+        }
+    }
+}
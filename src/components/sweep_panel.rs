@@ -0,0 +1,205 @@
+//! Parameter sweep: a whole comparison series and a chart in one go
+
+use dioxus::prelude::*;
+use crate::models::{ComparisonEntry, NutrientTargets, Salt, WaterAnalysis};
+use crate::optimizer::{format_number, OptimizationOptions, OptimizationProblem};
+use crate::sweep::{Sweep, SweepParameter, SweepPoint};
+
+/// Chart size in SVG units and the margin left for the axis labels
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 260.0;
+const CHART_MARGIN: f64 = 40.0;
+
+/// Line colours of the salts, repeated when there are more salts
+const CHART_COLORS: [&str; 8] = ["#10b981", "#6366f1", "#f59e0b", "#ec4899", "#06b6d4", "#a3e635", "#f97316", "#8b5cf6"];
+
+#[component]
+pub fn SweepPanel(
+    targets: Signal<NutrientTargets>,
+    options: Signal<OptimizationOptions>,
+    salts: Signal<Vec<Salt>>,
+    water: Signal<WaterAnalysis>,
+    stock_solutions: Signal<Vec<String>>,
+    comparison_history: Signal<Vec<ComparisonEntry>>,
+) -> Element {
+    let mut sweep = use_signal(|| Sweep { parameter: SweepParameter::Nh4Ratio, from: 0.0, to: 100.0, step: 10.0 });
+    let mut points = use_signal(Vec::<SweepPoint>::new);
+    let mut error = use_signal(|| None::<String>);
+    // X axis label of the series shown in the chart, fixed when it was computed
+    let mut chart_label = use_signal(String::new);
+
+    let run_sweep = move |_| {
+        let problem = OptimizationProblem::new(targets())
+            .salts(salts())
+            .options(options())
+            .water(water())
+            .tanks(stock_solutions());
+        let sweep = sweep();
+        match sweep.run(&problem) {
+            Ok(series) => {
+                let entries = series.iter().filter_map(|point| {
+                    point.result.clone().map(|result| ComparisonEntry {
+                        result,
+                        timestamp: sweep.parameter.describe(&targets(), point.value),
+                        infeasible: point.error.clone(),
+                    })
+                });
+                comparison_history.with_mut(|history| history.extend(entries));
+                chart_label.set(format!("{} ({})", sweep.parameter.label(), sweep.parameter.unit_label(&targets())));
+                points.set(series);
+                error.set(None);
+            }
+            Err(e) => error.set(Some(e.to_string())),
+        }
+    };
+
+    let parameters = SweepParameter::all();
+    let unit = sweep().parameter.unit_label(&targets());
+    rsx! {
+        div { class: "sweep-section",
+            h2 { "Parameterstudie" }
+            div { class: "sweep-controls",
+                div { class: "input-group",
+                    label { "Parameter" }
+                    select {
+                        onchange: move |evt| {
+                            if let Some(parameter) = evt.value().parse::<usize>().ok().and_then(|i| SweepParameter::all().get(i).copied()) {
+                                sweep.set(default_sweep(parameter, &targets()));
+                            }
+                        },
+                        for (i, parameter) in parameters.into_iter().enumerate() {
+                            option { value: "{i}", selected: sweep().parameter == parameter, "{parameter.label()}" }
+                        }
+                    }
+                }
+                {render_sweep_input("Von", unit.clone(), sweep().from, move |v| sweep.with_mut(|s| s.from = v))}
+                {render_sweep_input("Bis", unit.clone(), sweep().to, move |v| sweep.with_mut(|s| s.to = v))}
+                {render_sweep_input("Schritt", unit, sweep().step, move |v| sweep.with_mut(|s| s.step = v))}
+                button { class: "analyze-btn", onclick: run_sweep, "📈 Reihe berechnen" }
+            }
+            small { "Jeder Punkt wird mit den übrigen Einstellungen gelöst und in den Vergleich übernommen; nicht lösbare Punkte zeigen die nächstgelegene Rezeptur" }
+            if let Some(message) = error() {
+                div { class: "cost-warning", "⚠️ {message}" }
+            }
+            if !points().is_empty() {
+                {render_sweep_chart(&points(), &chart_label())}
+            }
+        }
+    }
+}
+
+// Start, end and step around the current value of a parameter
+fn default_sweep(parameter: SweepParameter, targets: &NutrientTargets) -> Sweep {
+    let current = parameter.value(targets);
+    let (from, to, step) = match parameter {
+        SweepParameter::Nh4Ratio => (0.0, 100.0, 10.0),
+        SweepParameter::EcMax if !current.is_finite() => (1.0, 3.0, 0.25),
+        _ if current.is_finite() && current > 0.0 => (current * 0.5, current * 1.5, current * 0.1),
+        _ => (0.0, 10.0, 1.0),
+    };
+    let round = |v: f64| (v * 1e4).round() / 1e4;
+    Sweep { parameter, from: round(from), to: round(to), step: round(step) }
+}
+
+fn render_sweep_input(label: &'static str, unit: String, value: f64, mut on_change: impl FnMut(f64) + 'static) -> Element {
+    rsx! {
+        div { class: "input-group",
+            label { "{label} ({unit})" }
+            input {
+                r#type: "number",
+                step: "any",
+                value: "{value}",
+                oninput: move |evt| {
+                    if let Ok(value) = evt.value().parse::<f64>() {
+                        on_change(value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Salt amounts over the swept values; infeasible points are marked in red
+fn render_sweep_chart(points: &[SweepPoint], x_label: &str) -> Element {
+    // Every salt used anywhere in the series, amounts summed over the tanks
+    let mut salt_names: Vec<String> = Vec::new();
+    for point in points {
+        for entry in point.result.iter().flat_map(|r| r.recipe.iter()) {
+            if !salt_names.contains(&entry.salt) {
+                salt_names.push(entry.salt.clone());
+            }
+        }
+    }
+    let amount = |point: &SweepPoint, salt: &str| -> Option<f64> {
+        point.result.as_ref().map(|r| r.recipe.iter().filter(|e| e.salt == salt).map(|e| e.amount).sum())
+    };
+
+    let x_min = points.iter().map(|p| p.value).fold(f64::INFINITY, f64::min);
+    let x_max = points.iter().map(|p| p.value).fold(f64::NEG_INFINITY, f64::max);
+    let y_max = points.iter()
+        .flat_map(|p| salt_names.iter().filter_map(move |s| amount(p, s)))
+        .fold(0.0, f64::max)
+        .max(1e-6);
+    let x = move |value: f64| {
+        let span = if x_max > x_min { x_max - x_min } else { 1.0 };
+        CHART_MARGIN + (value - x_min) / span * (CHART_WIDTH - 2.0 * CHART_MARGIN)
+    };
+    let y = move |amount: f64| CHART_HEIGHT - CHART_MARGIN - amount / y_max * (CHART_HEIGHT - 2.0 * CHART_MARGIN);
+
+    let lines: Vec<(String, &'static str, String)> = salt_names.iter()
+        .enumerate()
+        .map(|(i, salt)| {
+            let path = points.iter()
+                .filter_map(|p| amount(p, salt).map(|a| format!("{:.1},{:.1}", x(p.value), y(a))))
+                .collect::<Vec<_>>()
+                .join(" ");
+            (salt.clone(), CHART_COLORS[i % CHART_COLORS.len()], path)
+        })
+        .collect();
+    let infeasible: Vec<f64> = points.iter().filter(|p| !p.is_feasible()).map(|p| x(p.value)).collect();
+    // Label at most about ten points so that the values stay readable
+    let ticks: Vec<(f64, String)> = points.iter()
+        .step_by(points.len().div_ceil(10).max(1))
+        .map(|p| (x(p.value), format_number(p.value)))
+        .collect();
+    let (top, bottom, left, right) = (CHART_MARGIN, CHART_HEIGHT - CHART_MARGIN, CHART_MARGIN, CHART_WIDTH - CHART_MARGIN);
+    let y_top_label = format!("{:.1}", y_max);
+
+    rsx! {
+        div { class: "sweep-chart",
+            svg {
+                view_box: "0 0 {CHART_WIDTH} {CHART_HEIGHT}",
+                width: "100%",
+                for px in infeasible {
+                    line { x1: "{px}", y1: "{top}", x2: "{px}", y2: "{bottom}", stroke: "#ef4444", stroke_width: "8", stroke_opacity: "0.25" }
+                }
+                line { x1: "{left}", y1: "{bottom}", x2: "{right}", y2: "{bottom}", stroke: "#94a3b8" }
+                line { x1: "{left}", y1: "{top}", x2: "{left}", y2: "{bottom}", stroke: "#94a3b8" }
+                text { x: "{left - 4.0}", y: "{top + 4.0}", text_anchor: "end", font_size: "10", fill: "#94a3b8", "{y_top_label}" }
+                text { x: "{left - 4.0}", y: "{bottom}", text_anchor: "end", font_size: "10", fill: "#94a3b8", "0" }
+                for (px, label) in ticks {
+                    text { x: "{px}", y: "{bottom + 14.0}", text_anchor: "middle", font_size: "10", fill: "#94a3b8", "{label}" }
+                }
+                text { x: "{CHART_WIDTH / 2.0}", y: "{CHART_HEIGHT - 6.0}", text_anchor: "middle", font_size: "11", fill: "#cbd5e1", "{x_label}" }
+                text { x: "4", y: "{top - 12.0}", font_size: "11", fill: "#cbd5e1", "g l⁻¹ Stammlösung" }
+                for (salt, color, path) in lines.iter() {
+                    polyline { key: "{salt}", points: "{path}", fill: "none", stroke: "{color}", stroke_width: "2" }
+                }
+            }
+            div { class: "sweep-legend",
+                for (salt, color, _) in lines.iter() {
+                    span { key: "{salt}", class: "sweep-legend-item",
+                        span { class: "sweep-swatch", style: "background: {color}" }
+                        "{salt}"
+                    }
+                }
+                if points.iter().any(|p| !p.is_feasible()) {
+                    span { class: "sweep-legend-item",
+                        span { class: "sweep-swatch infeasible" }
+                        "nicht lösbar (nächstgelegene Rezeptur)"
+                    }
+                }
+            }
+        }
+    }
+}
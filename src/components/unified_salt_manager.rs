@@ -1,53 +1,85 @@
-/// Unified salt and stock solution manager with modern tile design
+//! Unified salt and stock solution manager with modern tile design
 
 use dioxus::prelude::*;
-use crate::models::Salt;
-
-/// Check if a stock solution has incompatible salt combinations (Ca/Mg + Phosphate)
-fn check_incompatible_salts(salts: &[Salt], solution: &str) -> Option<(Vec<String>, Vec<String>)> {
-    let solution_salts: Vec<&Salt> = salts.iter()
-        .filter(|s| s.stock_solution == solution && s.enabled)
-        .collect();
-    
-    let ca_mg_salts: Vec<String> = solution_salts.iter()
-        .filter(|s| s.ca > 0.01 || s.mg > 0.01)
-        .map(|s| s.name.clone())
-        .collect();
-    
-    let phosphate_salts: Vec<String> = solution_salts.iter()
-        .filter(|s| s.p > 0.01)
-        .map(|s| s.name.clone())
-        .collect();
-    
-    if !ca_mg_salts.is_empty() && !phosphate_salts.is_empty() {
-        Some((ca_mg_salts, phosphate_salts))
-    } else {
-        None
-    }
-}
+use crate::charge::{salt_imbalance, IMBALANCE_WARNING};
+use crate::compatibility::presence_conflicts;
+use crate::data::{predefined_salt, reset_salt};
+use anyhow::{bail, Result};
+use crate::formula;
+use crate::persistence::validate_salt;
+use crate::models::{Nutrient, NutrientVector, Price, Range, Salt, SaltKind, Solubility};
+use crate::salt_csv;
+use crate::components::{download_file, SaltCsvImport};
 
 #[component]
 pub fn UnifiedSaltManager(salts: Signal<Vec<Salt>>, stock_solutions: Signal<Vec<String>>) -> Element {
     let mut dragged_salt_idx = use_signal(|| None::<usize>);
     let mut show_add_form = use_signal(|| false);
+    let mut show_csv_import = use_signal(|| false);
+    // Salt whose values fill the form; `None` while adding a new one
+    let mut editing = use_signal(|| None::<usize>);
+    // Per salt, whether it is predefined and differs from the salt database
+    let edited = use_memo(move || salts().iter().map(|salt| reset_salt(salt).is_some_and(|default| default != *salt)).collect::<Vec<_>>());
     
-    // Form state for adding custom salts
-    let mut new_salt_name = use_signal(|| String::new());
-    let mut new_salt_formula = use_signal(|| String::new());
-    let mut new_nh4 = use_signal(|| String::from("0.0"));
-    let mut new_no3 = use_signal(|| String::from("0.0"));
-    let mut new_p = use_signal(|| String::from("0.0"));
-    let mut new_k = use_signal(|| String::from("0.0"));
-    let mut new_ca = use_signal(|| String::from("0.0"));
-    let mut new_mg = use_signal(|| String::from("0.0"));
-    let mut new_s = use_signal(|| String::from("0.0"));
-    let mut new_cl = use_signal(|| String::from("0.0"));
-    let mut new_fe = use_signal(|| String::from("0.0"));
-    let mut new_mn = use_signal(|| String::from("0.0"));
-    let mut new_zn = use_signal(|| String::from("0.0"));
-    let mut new_cu = use_signal(|| String::from("0.0"));
-    let mut new_b = use_signal(|| String::from("0.0"));
-    let mut new_mo = use_signal(|| String::from("0.0"));
+    // Form state for adding or editing salts
+    let mut new_salt_name = use_signal(String::new);
+    let mut new_salt_formula = use_signal(String::new);
+    let mut new_fractions = use_signal(|| vec![String::from("0.0"); Nutrient::COUNT]);
+    let mut new_price = use_signal(String::new);
+    let mut new_currency = use_signal(|| String::from("EUR"));
+    let mut new_kind = use_signal(SaltKind::default);
+    let mut new_density = use_signal(String::new);
+    let mut new_purity = use_signal(|| String::from("100"));
+    // Why the form could not be saved
+    let mut form_error = use_signal(|| None::<String>);
+
+    // Fills the composition from the formula and purity when the formula can be parsed
+    let mut prefill_fractions = move || {
+        let purity = new_purity().parse::<f64>().ok().filter(|p| *p > 0.0).unwrap_or(100.0) / 100.0;
+        if let Ok(composition) = formula::composition(&new_salt_formula(), purity) {
+            new_fractions.set(Nutrient::ALL.iter().map(|&n| format_fraction(composition[n])).collect());
+        }
+    };
+
+    let mut close_form = move || {
+        new_salt_name.set(String::new());
+        new_salt_formula.set(String::new());
+        new_fractions.set(vec![String::from("0.0"); Nutrient::COUNT]);
+        new_price.set(String::new());
+        new_currency.set(String::from("EUR"));
+        new_kind.set(SaltKind::default());
+        new_density.set(String::new());
+        new_purity.set(String::from("100"));
+        form_error.set(None);
+        editing.set(None);
+        show_add_form.set(false);
+    };
+
+    let mut fill_form = move |salt: &Salt| {
+        new_salt_name.set(salt.name.clone());
+        new_salt_formula.set(salt.formula.clone());
+        new_fractions.set(Nutrient::ALL.iter().map(|&n| format_fraction(salt.composition[n])).collect());
+        new_price.set(salt.price.as_ref().map(|p| p.per_kg.to_string()).unwrap_or_default());
+        new_currency.set(salt.price.as_ref().map_or("EUR".to_string(), |p| p.currency.clone()));
+        new_kind.set(salt.kind);
+        new_density.set(salt.density.map(|d| d.to_string()).unwrap_or_default());
+        new_purity.set(stored_purity(salt));
+    };
+
+    let mut edit_salt = move |idx: usize| {
+        let Some(salt) = salts.read().get(idx).cloned() else { return };
+        fill_form(&salt);
+        editing.set(Some(idx));
+        show_add_form.set(true);
+    };
+
+    // Database values of a predefined salt; its tank, state and dose stay
+    let reset_edited_salt = move |_| {
+        let Some(idx) = editing() else { return };
+        let Some(default) = salts.read().get(idx).and_then(reset_salt) else { return };
+        fill_form(&default);
+        salts.with_mut(|s| s[idx] = default);
+    };
 
     let add_solution = move |_| {
         stock_solutions.with_mut(|solutions| {
@@ -78,55 +110,100 @@ pub fn UnifiedSaltManager(salts: Signal<Vec<Salt>>, stock_solutions: Signal<Vec<
     };
 
     let mut delete_salt = move |idx: usize| {
+        // The form would otherwise edit whichever salt moves into the index
+        if editing().is_some() {
+            close_form();
+        }
         salts.with_mut(|s| {
             s.remove(idx);
         });
     };
 
-    let add_custom_salt = move |_| {
-        let name = new_salt_name();
-        let formula = new_salt_formula();
-        
-        if name.trim().is_empty() {
+    // Empty or invalid price removes it; the currency is kept per salt
+    let mut set_price = move |idx: usize, per_kg: &str| {
+        salts.with_mut(|s| {
+            if let Some(salt) = s.get_mut(idx) {
+                let currency = salt.price.as_ref().map_or("EUR".to_string(), |p| p.currency.clone());
+                salt.price = per_kg.parse::<f64>().ok()
+                    .filter(|v| *v >= 0.0)
+                    .map(|v| Price::new(v, &currency));
+            }
+        });
+    };
+
+    let mut set_dose = move |idx: usize, dose: Range| {
+        salts.with_mut(|s| {
+            if let Some(salt) = s.get_mut(idx) {
+                salt.dose = dose;
+            }
+        });
+    };
+
+    let mut set_currency = move |idx: usize, currency: String| {
+        salts.with_mut(|s| {
+            if let Some(price) = s.get_mut(idx).and_then(|salt| salt.price.as_mut()) {
+                price.currency = currency;
+            }
+        });
+    };
+
+    // Rescales a temperature curve so it passes through the new reference value
+    let mut set_solubility = move |idx: usize, g_per_l: &str| {
+        salts.with_mut(|s| {
+            if let Some(salt) = s.get_mut(idx) {
+                let value = g_per_l.parse::<f64>().ok().filter(|v| *v > 0.0);
+                salt.solubility = match (value, salt.solubility.take()) {
+                    (None, _) => None,
+                    (Some(v), Some(curve)) if curve.points.len() > 1 => {
+                        let factor = v / curve.at(Solubility::REFERENCE_TEMPERATURE);
+                        Some(Solubility { points: curve.points.iter().map(|&(t, g)| (t, g * factor)).collect() })
+                    }
+                    (Some(v), _) => Some(Solubility::at_reference(v)),
+                };
+            }
+        });
+    };
+
+    // Refuses values the workspace could not be loaded with again
+    let save_salt = move |_| {
+        let salt = match form_salt(&new_salt_name(), &new_salt_formula(), &new_fractions(), new_kind(), &new_density(), &new_price(), &new_currency()) {
+            Ok(salt) => salt,
+            Err(e) => {
+                form_error.set(Some(e.to_string()));
+                return;
+            }
+        };
+        // Tank, state, dose and solubility are edited elsewhere and kept; a
+        // predefined salt under a name not in the database becomes custom
+        let salt = match editing().and_then(|idx| salts.read().get(idx).cloned()) {
+            Some(current) => Salt {
+                is_custom: current.is_custom || predefined_salt(&salt.name).is_none(),
+                enabled: current.enabled,
+                stock_solution: current.stock_solution,
+                dose: current.dose,
+                solubility: current.solubility,
+                ..salt
+            },
+            None => salt,
+        };
+        let others: Vec<Salt> = salts.read().iter()
+            .enumerate()
+            .filter(|(i, _)| Some(*i) != editing())
+            .map(|(_, s)| s.clone())
+            .collect();
+        if let Err(e) = validate_salt(&salt, &others) {
+            form_error.set(Some(e.to_string()));
             return;
         }
-
-        let nh4 = new_nh4().parse().unwrap_or(0.0);
-        let no3 = new_no3().parse().unwrap_or(0.0);
-        let p = new_p().parse().unwrap_or(0.0);
-        let k = new_k().parse().unwrap_or(0.0);
-        let ca = new_ca().parse().unwrap_or(0.0);
-        let mg = new_mg().parse().unwrap_or(0.0);
-        let s = new_s().parse().unwrap_or(0.0);
-        let cl = new_cl().parse().unwrap_or(0.0);
-        let fe = new_fe().parse().unwrap_or(0.0);
-        let mn = new_mn().parse().unwrap_or(0.0);
-        let zn = new_zn().parse().unwrap_or(0.0);
-        let cu = new_cu().parse().unwrap_or(0.0);
-        let b = new_b().parse().unwrap_or(0.0);
-        let mo = new_mo().parse().unwrap_or(0.0);
-
-        let custom_salt = Salt::custom(name, formula, nh4, no3, p, k, ca, mg, s, cl, fe, mn, zn, cu, b, mo);
-        salts.with_mut(|s| s.push(custom_salt));
-
-        // Reset form
-        new_salt_name.set(String::new());
-        new_salt_formula.set(String::new());
-        new_nh4.set(String::from("0.0"));
-        new_no3.set(String::from("0.0"));
-        new_p.set(String::from("0.0"));
-        new_k.set(String::from("0.0"));
-        new_ca.set(String::from("0.0"));
-        new_mg.set(String::from("0.0"));
-        new_s.set(String::from("0.0"));
-        new_cl.set(String::from("0.0"));
-        new_fe.set(String::from("0.0"));
-        new_mn.set(String::from("0.0"));
-        new_zn.set(String::from("0.0"));
-        new_cu.set(String::from("0.0"));
-        new_b.set(String::from("0.0"));
-        new_mo.set(String::from("0.0"));
-        show_add_form.set(false);
+        match editing() {
+            Some(idx) => salts.with_mut(|s| {
+                if let Some(current) = s.get_mut(idx) {
+                    *current = salt;
+                }
+            }),
+            None => salts.with_mut(|s| s.push(salt)),
+        }
+        close_form();
     };
 
     rsx! {
@@ -147,10 +224,27 @@ pub fn UnifiedSaltManager(salts: Signal<Vec<Salt>>, stock_solutions: Signal<Vec<
                         h3 { "🧂 Verfügbare Nährsalze" }
                         button {
                             class: "add-salt-text-btn",
-                            onclick: move |_| show_add_form.set(!show_add_form()),
+                            onclick: move |_| {
+                                // While editing, this switches to an empty form
+                                let adding = show_add_form() && editing().is_none();
+                                close_form();
+                                show_add_form.set(!adding);
+                            },
                             title: "Benutzerdefiniertes Salz hinzufügen",
                             "Neues Nährsalz ➕"
                         }
+                        button {
+                            class: "add-salt-text-btn",
+                            onclick: move |_| show_csv_import.set(!show_csv_import()),
+                            title: "Salze aus einer CSV-Datei übernehmen, z.B. aus einer Produktliste des Händlers",
+                            "CSV importieren"
+                        }
+                        button {
+                            class: "add-salt-text-btn",
+                            onclick: move |_| download_file("salze.csv", "text/csv", salt_csv::export(&salts())),
+                            title: "Alle Salze als CSV-Datei speichern",
+                            "CSV exportieren"
+                        }
                     }
                     
                     // Tile grid for unassigned/available salts
@@ -188,10 +282,20 @@ pub fn UnifiedSaltManager(salts: Signal<Vec<Salt>>, stock_solutions: Signal<Vec<
                                         div { class: "salt-info",
                                             div { class: "salt-name", "{salt.name}" }
                                             div { class: "salt-formula", "{salt.formula}" }
+                                            if edited().get(idx).copied().unwrap_or(false) {
+                                                div { class: "salt-edited", title: "Abweichend von der Salzdatenbank; über ✏ zurücksetzbar", "bearbeitet" }
+                                            }
+                                            {render_charge_warning(salt)}
                                         }
                                     }
                                     
                                     div { class: "salt-tile-actions",
+                                        button {
+                                            class: "tile-action-btn",
+                                            onclick: move |_| edit_salt(idx),
+                                            title: "Bearbeiten",
+                                            "✏"
+                                        }
                                         button {
                                             class: "tile-action-btn toggle",
                                             onclick: move |_| toggle_salt(idx),
@@ -222,10 +326,108 @@ pub fn UnifiedSaltManager(salts: Signal<Vec<Salt>>, stock_solutions: Signal<Vec<
                         }
                     }
                     
+                    // Price list for the cost objective
+                    details { class: "nutrient-details",
+                        summary { "💶 Preise (pro kg) & Löslichkeit (g l⁻¹ bei 20 °C)" }
+                        div { class: "price-list",
+                            for (idx, salt) in salts().iter().enumerate() {
+                                div { key: "{idx}", class: "price-row",
+                                    span { "{salt.name}" }
+                                    input {
+                                        r#type: "number",
+                                        min: "0",
+                                        step: "0.01",
+                                        placeholder: "—",
+                                        value: salt.price.as_ref().map(|p| p.per_kg.to_string()).unwrap_or_default(),
+                                        oninput: move |evt| set_price(idx, &evt.value())
+                                    }
+                                    input {
+                                        r#type: "text",
+                                        disabled: salt.price.is_none(),
+                                        value: salt.price.as_ref().map_or("EUR".to_string(), |p| p.currency.clone()),
+                                        oninput: move |evt| set_currency(idx, evt.value().trim().to_string())
+                                    }
+                                    input {
+                                        r#type: "number",
+                                        min: "0",
+                                        step: "1",
+                                        placeholder: "∞",
+                                        title: "Löslichkeit in g l⁻¹ bei 20 °C",
+                                        value: salt.solubility.as_ref().map(|s| format!("{:.0}", s.at(Solubility::REFERENCE_TEMPERATURE))).unwrap_or_default(),
+                                        oninput: move |evt| set_solubility(idx, &evt.value())
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    // Dose limits honoured by the optimizer
+                    details { class: "nutrient-details",
+                        summary { "⚖️ Dosierung (g l⁻¹ Stammlösung)" }
+                        div { class: "price-list",
+                            for (idx, salt) in salts().iter().enumerate() {
+                                div { key: "{idx}", class: "price-row",
+                                    span { "{salt.name}" }
+                                    input {
+                                        r#type: "number",
+                                        min: "0",
+                                        step: "any",
+                                        placeholder: "0",
+                                        title: if salt.has_fixed_dose() { "Feste Menge" } else { "Mindestmenge" },
+                                        value: if salt.dose.min > 0.0 { salt.dose.min.to_string() } else { String::new() },
+                                        oninput: {
+                                            let dose = salt.dose;
+                                            let fixed = salt.has_fixed_dose();
+                                            move |evt: FormEvent| {
+                                                let min = evt.value().trim().parse::<f64>().ok().filter(|v| *v >= 0.0).unwrap_or(0.0);
+                                                let max = if fixed { min } else { dose.max };
+                                                set_dose(idx, Range::new(min, max));
+                                            }
+                                        }
+                                    }
+                                    input {
+                                        r#type: "number",
+                                        min: "0",
+                                        step: "any",
+                                        placeholder: "∞",
+                                        title: "Höchstmenge",
+                                        disabled: salt.has_fixed_dose(),
+                                        value: if salt.dose.max.is_finite() { salt.dose.max.to_string() } else { String::new() },
+                                        oninput: {
+                                            let dose = salt.dose;
+                                            move |evt: FormEvent| {
+                                                let max = evt.value().trim().parse::<f64>().ok().filter(|v| *v >= 0.0).unwrap_or(f64::INFINITY);
+                                                set_dose(idx, Range::new(dose.min, max));
+                                            }
+                                        }
+                                    }
+                                    label { class: "checkbox-label",
+                                        input {
+                                            r#type: "checkbox",
+                                            checked: salt.has_fixed_dose(),
+                                            onchange: {
+                                                let dose = salt.dose;
+                                                move |evt: FormEvent| {
+                                                    let dose = if evt.checked() { Range::exact(dose.min) } else { Range::new(dose.min, f64::INFINITY) };
+                                                    set_dose(idx, dose);
+                                                }
+                                            }
+                                        }
+                                        "fest"
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    if show_csv_import() {
+                        SaltCsvImport { salts, on_close: move |_| show_csv_import.set(false) }
+                    }
+
                     // Add custom salt form
                     if show_add_form() {
                         div { class: "add-salt-form-compact",
-                            h4 { "Neues Salz hinzufügen" }
+                            h4 { if editing().is_some() { "Salz bearbeiten" } else { "Neues Salz hinzufügen" } }
                             div { class: "form-row-compact",
                                 input {
                                     r#type: "text",
@@ -235,33 +437,93 @@ pub fn UnifiedSaltManager(salts: Signal<Vec<Salt>>, stock_solutions: Signal<Vec<
                                 }
                                 input {
                                     r#type: "text",
-                                    placeholder: "Formel",
+                                    placeholder: "Formel (z.B. Ca(NO₃)₂·4H₂O)",
                                     value: "{new_salt_formula}",
-                                    oninput: move |evt| new_salt_formula.set(evt.value())
+                                    oninput: move |evt| {
+                                        new_salt_formula.set(evt.value());
+                                        prefill_fractions();
+                                    }
+                                }
+                                input {
+                                    r#type: "number",
+                                    min: "0",
+                                    max: "100",
+                                    step: "any",
+                                    title: "Reinheit bzw. Gehalt in %",
+                                    placeholder: "Reinheit %",
+                                    value: "{new_purity}",
+                                    oninput: move |evt| {
+                                        new_purity.set(evt.value());
+                                        prefill_fractions();
+                                    }
                                 }
                             }
+                            {render_formula_status(&new_salt_formula())}
                             details { class: "nutrient-details",
                                 summary { "Nährstoffzusammensetzung eingeben" }
                                 div { class: "compact-nutrient-grid",
-                                    input { r#type: "number", placeholder: "NH₄⁺", value: "{new_nh4}", oninput: move |evt| new_nh4.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "NO₃⁻", value: "{new_no3}", oninput: move |evt| new_no3.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "P", value: "{new_p}", oninput: move |evt| new_p.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "K", value: "{new_k}", oninput: move |evt| new_k.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "Ca", value: "{new_ca}", oninput: move |evt| new_ca.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "Mg", value: "{new_mg}", oninput: move |evt| new_mg.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "S", value: "{new_s}", oninput: move |evt| new_s.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "Cl", value: "{new_cl}", oninput: move |evt| new_cl.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "Fe", value: "{new_fe}", oninput: move |evt| new_fe.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "Mn", value: "{new_mn}", oninput: move |evt| new_mn.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "Zn", value: "{new_zn}", oninput: move |evt| new_zn.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "Cu", value: "{new_cu}", oninput: move |evt| new_cu.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "B", value: "{new_b}", oninput: move |evt| new_b.set(evt.value()) }
-                                    input { r#type: "number", placeholder: "Mo", value: "{new_mo}", oninput: move |evt| new_mo.set(evt.value()) }
+                                    for nutrient in Nutrient::ALL {
+                                        input {
+                                            key: "{nutrient.key()}",
+                                            r#type: "number",
+                                            placeholder: "{nutrient.symbol()}",
+                                            title: "{nutrient.name()}",
+                                            value: "{new_fractions()[nutrient as usize]}",
+                                            oninput: move |evt| new_fractions.with_mut(|f| f[nutrient as usize] = evt.value())
+                                        }
+                                    }
+                                }
+                            }
+                            div { class: "form-row-compact",
+                                select {
+                                    onchange: move |evt| {
+                                        if let Some(kind) = evt.value().parse::<usize>().ok().and_then(|i| SaltKind::ALL.get(i)) {
+                                            new_kind.set(*kind);
+                                        }
+                                    },
+                                    for (i, kind) in SaltKind::ALL.into_iter().enumerate() {
+                                        option { value: "{i}", selected: new_kind() == kind, "{kind.label()}" }
+                                    }
+                                }
+                                input {
+                                    r#type: "number",
+                                    min: "0",
+                                    step: "0.01",
+                                    placeholder: "Dichte g/ml (nur Flüssigkeiten)",
+                                    value: "{new_density}",
+                                    oninput: move |evt| new_density.set(evt.value())
+                                }
+                            }
+                            {render_charge_warning(&Salt::custom(String::new(), new_salt_formula(), parse_composition(&new_fractions())).with_kind(new_kind()))}
+                            div { class: "form-row-compact",
+                                input {
+                                    r#type: "number",
+                                    min: "0",
+                                    placeholder: "Preis pro kg (optional)",
+                                    value: "{new_price}",
+                                    oninput: move |evt| new_price.set(evt.value())
                                 }
+                                input {
+                                    r#type: "text",
+                                    placeholder: "Währung",
+                                    value: "{new_currency}",
+                                    oninput: move |evt| new_currency.set(evt.value())
+                                }
+                            }
+                            if let Some(message) = form_error() {
+                                div { class: "cost-warning", "⚠️ {message}" }
                             }
                             div { class: "form-actions-compact",
-                                button { class: "btn-add", onclick: add_custom_salt, "Hinzufügen" }
-                                button { class: "btn-cancel", onclick: move |_| show_add_form.set(false), "Abbrechen" }
+                                button { class: "btn-add", onclick: save_salt, if editing().is_some() { "Speichern" } else { "Hinzufügen" } }
+                                if editing().and_then(|idx| salts.read().get(idx).and_then(reset_salt)).is_some() {
+                                    button {
+                                        class: "btn-cancel",
+                                        onclick: reset_edited_salt,
+                                        title: "Werte aus der Salzdatenbank übernehmen; Stammlösung und Dosis bleiben",
+                                        "Standardwerte"
+                                    }
+                                }
+                                button { class: "btn-cancel", onclick: move |_| close_form(), "Abbrechen" }
                             }
                         }
                     }
@@ -271,8 +533,8 @@ pub fn UnifiedSaltManager(salts: Signal<Vec<Salt>>, stock_solutions: Signal<Vec<
                 div { class: "stock-solutions-section",
                     for solution in stock_solutions().iter() {
                         {
-                            let incompatible = check_incompatible_salts(&salts(), solution);
-                            let has_warning = incompatible.is_some();
+                            let conflicts = presence_conflicts(&salts(), solution);
+                            let has_warning = !conflicts.is_empty();
                             
                             rsx! {
                                 div { 
@@ -295,23 +557,26 @@ pub fn UnifiedSaltManager(salts: Signal<Vec<Salt>>, stock_solutions: Signal<Vec<
                                     }
                                     
                                     // Incompatibility warning
-                                    if let Some((ca_mg, phosphates)) = incompatible {
+                                    if has_warning {
                                         div { class: "incompatibility-warning",
                                             div { class: "warning-header",
                                                 span { class: "warning-icon", "⚠️" }
                                                 span { class: "warning-title", "Inkompatible Kombination!" }
                                             }
                                             div { class: "warning-message",
-                                                "Ca/Mg-Salze reagieren mit Phosphat-Salzen und bilden unlösliche Ausfällungen."
+                                                "Diese Salze bilden in konzentrierter Lösung unlösliche Ausfällungen."
                                             }
-                                            div { class: "warning-details",
-                                                div { class: "salt-list",
-                                                    strong { "Ca/Mg: " }
-                                                    span { "{ca_mg.join(\", \")}" }
-                                                }
-                                                div { class: "salt-list",
-                                                    strong { "Phosphat: " }
-                                                    span { "{phosphates.join(\", \")}" }
+                                            for conflict in conflicts.iter() {
+                                                div { key: "{conflict.rule.name}", class: "warning-details",
+                                                    strong { "{conflict.rule.name}" }
+                                                    div { class: "salt-list",
+                                                        strong { "{conflict.rule.first.label()}: " }
+                                                        span { "{conflict.first.join(\", \")}" }
+                                                    }
+                                                    div { class: "salt-list",
+                                                        strong { "{conflict.rule.second.label()}: " }
+                                                        span { "{conflict.second.join(\", \")}" }
+                                                    }
                                                 }
                                             }
                                             div { class: "warning-action",
@@ -357,6 +622,19 @@ pub fn UnifiedSaltManager(salts: Signal<Vec<Salt>>, stock_solutions: Signal<Vec<
                                                 div { class: "salt-info",
                                                     div { class: "salt-name", "{salt.name}" }
                                                     div { class: "salt-formula", "{salt.formula}" }
+                                                    if edited().get(idx).copied().unwrap_or(false) {
+                                                div { class: "salt-edited", title: "Abweichend von der Salzdatenbank; über ✏ zurücksetzbar", "bearbeitet" }
+                                            }
+                                                    {render_charge_warning(salt)}
+                                                }
+                                            }
+
+                                            div { class: "salt-tile-actions",
+                                                button {
+                                                    class: "tile-action-btn",
+                                                    onclick: move |_| edit_salt(idx),
+                                                    title: "Bearbeiten",
+                                                    "✏"
                                                 }
                                             }
                                             
@@ -393,6 +671,97 @@ pub fn UnifiedSaltManager(salts: Signal<Vec<Salt>>, stock_solutions: Signal<Vec<
     }
 }
 
+// Composition of the add form; unparsable fields count as zero
+// Salt from the form fields; numbers that do not parse are an error, an
+// empty price or density leaves it out
+fn form_salt(name: &str, formula: &str, fractions: &[String], kind: SaltKind, density: &str, price: &str, currency: &str) -> Result<Salt> {
+    let mut problems = Vec::new();
+    let mut number = |text: &str, label: String| match text.trim() {
+        "" => None,
+        text => {
+            let value = text.parse::<f64>().ok();
+            if value.is_none() {
+                problems.push(format!("{}: keine Zahl", label));
+            }
+            value
+        }
+    };
+    let mut composition = NutrientVector::zero();
+    for nutrient in Nutrient::ALL {
+        composition[nutrient] = number(&fractions[nutrient as usize], format!("Anteil {}", nutrient.symbol())).unwrap_or(0.0);
+    }
+    let density = number(density, "Dichte".to_string());
+    let per_kg = number(price, "Preis".to_string());
+    if !problems.is_empty() {
+        bail!("{}", problems.join("; "));
+    }
+
+    let mut salt = Salt::custom(name.trim().to_string(), formula.to_string(), composition).with_kind(kind);
+    salt.density = density;
+    if let Some(per_kg) = per_kg {
+        let currency = if currency.trim().is_empty() { "EUR" } else { currency.trim() };
+        salt = salt.with_price(Price::new(per_kg, currency));
+    }
+    Ok(salt)
+}
+
+fn parse_composition(fractions: &[String]) -> NutrientVector {
+    let mut composition = NutrientVector::zero();
+    for nutrient in Nutrient::ALL {
+        composition[nutrient] = fractions[nutrient as usize].parse().unwrap_or(0.0);
+    }
+    composition
+}
+
+// Purity in % at which the formula gives the stored composition, so that
+// editing the formula keeps a technical grade; 100 if it does not fit
+fn stored_purity(salt: &Salt) -> String {
+    let total = |composition: &NutrientVector| composition.iter().map(|(_, fraction)| fraction).sum::<f64>();
+    let purity = formula::composition(&salt.formula, 1.0).ok()
+        .map(|pure| total(&salt.composition) / total(&pure))
+        .filter(|p| p.is_finite() && *p > 0.0 && *p < 1.0 - 1e-6)
+        .unwrap_or(1.0);
+    ((purity * 1000.0).round() / 10.0).to_string()
+}
+
+// Fractions are shown with six decimals, trailing zeros removed
+fn format_fraction(fraction: f64) -> String {
+    if fraction == 0.0 {
+        return String::from("0.0");
+    }
+    let text = format!("{:.6}", fraction);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+// Molar mass of a parsable formula, or why it cannot be parsed
+fn render_formula_status(text: &str) -> Element {
+    if text.trim().is_empty() {
+        return rsx! {};
+    }
+    match formula::parse(text) {
+        Ok(parsed) => {
+            let molar_mass = parsed.molar_mass();
+            rsx! { small { class: "formula-status", "Molmasse {molar_mass:.2} g mol⁻¹ – Zusammensetzung aus der Formel übernommen" } }
+        }
+        Err(e) => rsx! { small { class: "formula-status formula-error", "{e} – Zusammensetzung bitte von Hand eingeben" } },
+    }
+}
+
+// Flags compositions whose cations and anions do not balance, usually a typo
+fn render_charge_warning(salt: &Salt) -> Element {
+    match salt_imbalance(salt).filter(|i| i.abs() > IMBALANCE_WARNING) {
+        Some(imbalance) => {
+            let percent = imbalance * 100.0;
+            rsx! {
+                div { class: "salt-charge-warning", title: "Kationen und Anionen gleichen sich nicht aus – Zusammensetzung prüfen",
+                    "⚠️ Ionenbilanz {percent:+.0} %"
+                }
+            }
+        }
+        None => rsx! {},
+    }
+}
+
 fn get_next_solution_letter(solutions: &[String]) -> String {
     let letters = "ABCDEFGHIJ";
     for letter in letters.chars() {
@@ -0,0 +1,111 @@
+//! Source water analysis and injector dilution panel
+
+use dioxus::prelude::*;
+use crate::models::{Nutrient, WaterAnalysis};
+use crate::optimizer::OptimizationOptions;
+
+#[component]
+pub fn WaterProfile(water: Signal<WaterAnalysis>, options: Signal<OptimizationOptions>, stock_solutions: Signal<Vec<String>>) -> Element {
+    rsx! {
+        div { class: "input-section water-profile",
+            h2 { "Rohwasser & Verdünnung" }
+
+            div { class: "input-group",
+                label { "Verdünnung (1:x)" }
+                input {
+                    r#type: "number",
+                    min: "1",
+                    step: "1",
+                    value: "{options().dilution_ratio}",
+                    oninput: move |evt| {
+                        if let Ok(ratio) = evt.value().parse::<f64>() {
+                            if ratio >= 1.0 {
+                                options.with_mut(|o| o.dilution_ratio = ratio);
+                            }
+                        }
+                    }
+                }
+                small { "Bezugsverdünnung für Nährlösungs-Ziele, Rohwasseranteil und Kosten" }
+            }
+
+            div { class: "input-group",
+                label { "Verdünnung je Stammlösung" }
+                div { class: "water-grid",
+                    for tank in stock_solutions() {
+                        {render_tank_dilution(tank, options)}
+                    }
+                }
+                small { "Leer lassen, um die Bezugsverdünnung zu verwenden" }
+            }
+
+            h3 { "Wasseranalyse (mg l⁻¹)" }
+            div { class: "water-grid",
+                for nutrient in Nutrient::ALL {
+                    {render_water_input(nutrient, water)}
+                }
+            }
+            if !water().is_pure() {
+                small { "Die Rohwassergehalte werden angerechnet, die Salze liefern nur den Rest" }
+            }
+        }
+    }
+}
+
+// Dilution of one injector channel; an empty field falls back to the reference
+fn render_tank_dilution(tank: String, mut options: Signal<OptimizationOptions>) -> Element {
+    let value = options().tank_dilutions.get(&tank).map(|d| d.to_string()).unwrap_or_default();
+    let reference = options().dilution_ratio;
+    rsx! {
+        div { key: "{tank}", class: "water-field",
+            label { "SL {tank}" }
+            input {
+                r#type: "number",
+                min: "1",
+                step: "1",
+                placeholder: "{reference}",
+                value: "{value}",
+                oninput: move |evt| {
+                    let parsed = evt.value().trim().parse::<f64>().ok().filter(|d| *d >= 1.0);
+                    options.with_mut(|o| match parsed {
+                        Some(ratio) => {
+                            o.tank_dilutions.insert(tank.clone(), ratio);
+                        }
+                        None => {
+                            o.tank_dilutions.remove(&tank);
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+// Sulfur is entered as sulfate, the form water analyses report it in
+fn render_water_input(nutrient: Nutrient, mut water: Signal<WaterAnalysis>) -> Element {
+    let is_sulfate = nutrient == Nutrient::S;
+    let label = if is_sulfate { "SO₄²⁻" } else { nutrient.symbol() };
+    let value = if is_sulfate { water().sulfate() } else { water().concentrations[nutrient] };
+    let value = if value == 0.0 { String::new() } else { format!("{}", (value * 1000.0).round() / 1000.0) };
+    rsx! {
+        div { key: "{nutrient.key()}", class: "water-field",
+            label { title: "{nutrient.name()}", "{label}" }
+            input {
+                r#type: "number",
+                min: "0",
+                step: "any",
+                placeholder: "0",
+                value: "{value}",
+                oninput: move |evt| {
+                    let mg_per_l = evt.value().parse::<f64>().unwrap_or(0.0).max(0.0);
+                    water.with_mut(|w| {
+                        if is_sulfate {
+                            w.set_sulfate(mg_per_l);
+                        } else {
+                            w.concentrations[nutrient] = mg_per_l;
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
@@ -1,51 +1,99 @@
-/// Salt database for fertilizer optimization
+//! Salt database for fertilizer optimization
 
-use crate::models::Salt;
+use crate::formula;
+use crate::models::{Nutrient::*, NutrientVector, Range, Salt, SaltKind, Solubility};
 
 /// Returns the list of predefined salts with their nutrient compositions
-/// Note: Micronutrient values (Fe, Mn, Zn, Cu, B, Mo) are set to 0.0 for macronutrient salts
+/// Compositions are computed from the formulas, except for technical-grade
+/// products and mixes given by their label values; micronutrients (Fe, Mn, Zn, Cu, B, Mo)
+/// are absent from the macronutrient salts
 /// Users can add micronutrient-specific salts as custom entries
+/// Solubilities are approximate values (g per litre of water) from common
+/// fertilizer solubility tables; curves are given where the temperature matters
+/// Acids and bases start unassigned and disabled; they are only needed for a pH target
 pub fn get_predefined_salts() -> Vec<Salt> {
     vec![
         // Stock Solution A - Calcium and Magnesium salts
+        // Technical grade as sold for fertigation, which carries some
+        // ammonium nitrate: label N 15.5 % (14.4 % NO₃-N, 1.1 % NH₄-N)
         Salt::predefined("Ca(NO₃)₂·4H₂O", "Ca(NO₃)₂·4H₂O", "A",
-            0.0142, 0.6375, 0.0, 0.0, 0.169717, 0.0, 0.0, 0.0,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
-        Salt::predefined("Mg(NO₃)₂·6H₂O", "Mg(NO₃)₂·6H₂O", "A",
-            0.0, 0.483645, 0.0, 0.0, 0.0, 0.094792, 0.0, 0.0,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
-        Salt::predefined("CaCl₂·2H₂O", "CaCl₂·2H₂O", "A",
-            0.0, 0.0, 0.0, 0.0, 0.272625, 0.0, 0.0, 0.482287,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+            NutrientVector::from_pairs(&[(Nh4, 0.0142), (No3, 0.6375), (Ca, 0.169717)]))
+            .with_solubility(Solubility::at_reference(1200.0)),
+        from_formula("Mg(NO₃)₂·6H₂O", "Mg(NO₃)₂·6H₂O", "A", 1.0)
+            .with_solubility(Solubility::at_reference(1250.0)),
+        from_formula("CaCl₂·2H₂O", "CaCl₂·2H₂O", "A", 1.0)
+            .with_solubility(Solubility::at_reference(970.0)),
+        // Chelated trace element mix. UNVERIFIED: typical values for such
+        // mixes, not yet checked against the manufacturer's data sheet.
+        // Dosed at the fixed 0.30 g/L of the original recipe, since the
+        // default targets leave the micronutrients open
         Salt::predefined("Ferty 72", "Micronutrient Mix", "A",
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+            NutrientVector::from_pairs(&[(Fe, 0.070), (Mn, 0.035), (Zn, 0.007), (Cu, 0.003), (B, 0.007), (Mo, 0.002)]))
+            .with_dose(Range::exact(0.30)),
         
         // Stock Solution B - Phosphate and other salts
-        Salt::predefined("KNO₃", "KNO₃", "B",
-            0.0, 0.613282, 0.0, 0.386718, 0.0, 0.0, 0.0, 0.0,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
-        Salt::predefined("(NH₄)₂SO₄", "(NH₄)₂SO₄", "B",
-            0.273031, 0.0, 0.0, 0.0, 0.0, 0.0, 0.242661, 0.0,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
-        Salt::predefined("NH₄H₂PO₄", "NH₄H₂PO₄", "B",
-            0.156827, 0.0, 0.269281, 0.0, 0.0, 0.0, 0.0, 0.0,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
-        Salt::predefined("NH₄Cl", "NH₄Cl", "B",
-            0.337247, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.662753,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
-        Salt::predefined("KH₂PO₄", "KH₂PO₄", "B",
-            0.0, 0.0, 0.227609, 0.287308, 0.0, 0.0, 0.0, 0.0,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
-        Salt::predefined("K₂SO₄", "K₂SO₄", "B",
-            0.0, 0.0, 0.0, 0.448740, 0.0, 0.0, 0.184010, 0.0,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
-        Salt::predefined("MgSO₄·7H₂O", "MgSO₄·7H₂O", "B",
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.098612, 0.130096, 0.0,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+        from_formula("KNO₃", "KNO₃", "B", 1.0)
+            .with_solubility(Solubility::curve(&[(0.0, 133.0), (10.0, 209.0), (20.0, 316.0), (30.0, 458.0), (40.0, 639.0)])),
+        from_formula("(NH₄)₂SO₄", "(NH₄)₂SO₄", "B", 1.0)
+            .with_solubility(Solubility::curve(&[(0.0, 706.0), (20.0, 754.0), (40.0, 812.0)])),
+        from_formula("NH₄H₂PO₄", "NH₄H₂PO₄", "B", 1.0)
+            .with_solubility(Solubility::curve(&[(0.0, 227.0), (20.0, 368.0), (40.0, 564.0)])),
+        from_formula("NH₄Cl", "NH₄Cl", "B", 1.0)
+            .with_solubility(Solubility::curve(&[(0.0, 294.0), (20.0, 372.0), (40.0, 458.0)])),
+        from_formula("KH₂PO₄", "KH₂PO₄", "B", 1.0)
+            .with_solubility(Solubility::curve(&[(0.0, 148.0), (20.0, 226.0), (40.0, 335.0)])),
+        from_formula("K₂SO₄", "K₂SO₄", "B", 1.0)
+            .with_solubility(Solubility::curve(&[(0.0, 74.0), (20.0, 111.0), (40.0, 148.0)])),
+        from_formula("MgSO₄·7H₂O", "MgSO₄·7H₂O", "B", 1.0)
+            .with_solubility(Solubility::at_reference(710.0)),
+        // Trace element mix with potassium, magnesium and sulfur (10 % K₂O,
+        // 5 % MgO, 10 % SO₃). UNVERIFIED like Ferty 72 above; fixed at the
+        // original 2.24 g/L
         Salt::predefined("Ferty 10", "Micronutrient Mix", "B",
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+            NutrientVector::from_pairs(&[
+                (K, 0.0830), (Mg, 0.0302), (S, 0.0400),
+                (Fe, 0.0035), (Mn, 0.0040), (Zn, 0.0010), (Cu, 0.0004), (B, 0.0010), (Mo, 0.0003),
+            ]))
+            .with_dose(Range::exact(2.24)),
+
+        // pH adjustment - technical grade solutions, fractions per g of solution
+        ph_adjuster(from_formula("Salpetersäure 65 %", "HNO₃", "Unassigned", 0.65)
+            .with_kind(SaltKind::Acid)
+            .liquid(1.39)),
+        ph_adjuster(from_formula("Phosphorsäure 75 %", "H₃PO₄", "Unassigned", 0.75)
+            .with_kind(SaltKind::Acid)
+            .liquid(1.58)),
+        ph_adjuster(from_formula("Schwefelsäure 38 %", "H₂SO₄", "Unassigned", 0.38)
+            .with_kind(SaltKind::Acid)
+            .liquid(1.29)),
+        ph_adjuster(from_formula("Kalilauge 50 %", "KOH", "Unassigned", 0.5)
+            .with_kind(SaltKind::Base)
+            .liquid(1.51)),
+        ph_adjuster(from_formula("KHCO₃", "KHCO₃", "Unassigned", 1.0)
+            .with_kind(SaltKind::Base)
+            .with_solubility(Solubility::at_reference(224.0))),
     ]
 }
 
+/// Predefined product whose composition follows from its formula and purity
+fn from_formula(name: &str, formula: &str, stock_solution: &str, purity: f64) -> Salt {
+    let composition = formula::composition(formula, purity)
+        .unwrap_or_else(|e| panic!("invalid predefined formula {}: {}", formula, e));
+    Salt::predefined(name, formula, stock_solution, composition)
+}
+
+/// Database entry of a predefined salt, by name
+pub fn predefined_salt(name: &str) -> Option<Salt> {
+    get_predefined_salts().into_iter().find(|salt| salt.name == name)
+}
+
+/// A predefined salt with its database values, keeping its tank, enabled
+/// state and dose; `None` for custom salts
+pub fn reset_salt(salt: &Salt) -> Option<Salt> {
+    let default = predefined_salt(&salt.name).filter(|_| !salt.is_custom)?;
+    Some(Salt { enabled: salt.enabled, stock_solution: salt.stock_solution.clone(), dose: salt.dose, ..default })
+}
+
+fn ph_adjuster(salt: Salt) -> Salt {
+    Salt { enabled: false, ..salt }
+}
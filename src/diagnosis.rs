@@ -0,0 +1,275 @@
+//! Explains why an optimization problem has no solution
+//!
+//! A solve whose bounds cannot all be met only returns [`Unsolvable`]. The
+//! explanation costs one solve per bound and therefore runs on request, in
+//! [`diagnose`], with two passes: a deletion filter that shrinks the bound list to an irreducible
+//! infeasible subset, and an elastic LP with one slack variable per bound
+//! that finds the smallest relaxation making the problem solvable.
+//! Dose ranges, salt limits, solubility limits and tank assignment rules stay
+//! hard in both passes; dose and salt limits the conflict depends on are
+//! reported separately.
+
+use std::fmt;
+
+use good_lp::*;
+use crate::optimizer::{format_number, Bound, DisplayUnits, ElasticBound, OptimizationProblem, SaltModel};
+use crate::models::Salt;
+
+/// Tank load above which a tank counts as saturated
+const SATURATION: f64 = 1.0 - 1e-6;
+
+/// Error of a solve whose bounds cannot all be met; [`diagnose`] tells why
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unsolvable;
+
+impl fmt::Display for Unsolvable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Die Zielwerte lassen sich mit den verfügbaren Salzen nicht alle einhalten")
+    }
+}
+
+impl std::error::Error for Unsolvable {}
+
+/// Structured explanation of an infeasible problem
+#[derive(Debug, Clone, PartialEq)]
+pub struct Infeasibility {
+    /// Minimal set of bounds that cannot be met together; removing any one of
+    /// them makes the rest solvable
+    pub conflicts: Vec<Bound>,
+    /// Smallest changes to the bounds that make the problem solvable
+    pub relaxations: Vec<Relaxation>,
+    /// Tanks at their solubility limit in the relaxed recipe
+    pub saturated_tanks: Vec<String>,
+    /// Salts whose minimum or fixed dose is part of the conflict
+    pub dosed_salts: Vec<String>,
+    /// Largest number of salts, if that limit is part of the conflict
+    pub salt_limit: Option<usize>,
+    /// Minimum weighing amount (g), if it is part of the conflict
+    pub min_weighing: Option<f64>,
+}
+
+/// Suggested new value for a bound
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Relaxation {
+    pub bound: Bound,
+    /// Relaxed limit in g/L, or as a share for the NH₄⁺ ratio
+    pub value: f64,
+}
+
+impl Relaxation {
+    /// e.g. "K⁺ max 25 g l⁻¹ → 31 g l⁻¹"
+    pub fn describe(&self, units: &DisplayUnits) -> String {
+        format!("{} → {}", self.bound.describe(units), self.bound.subject.format_value(self.value, units))
+    }
+}
+
+impl Infeasibility {
+    /// Explanation in the basis and units the targets are entered in
+    pub fn describe(&self, units: &DisplayUnits) -> String {
+        // Lead with a bound the elastic pass had to relax, if it is part of the conflict
+        let lead = self.conflicts.iter()
+            .position(|b| self.relaxations.iter().any(|r| r.bound == *b))
+            .unwrap_or(0);
+        let reasons = self.salt_reasons();
+        let Some(first) = self.conflicts.get(lead) else {
+            if reasons.is_empty() {
+                return "Keine widersprüchlichen Grenzen gefunden".to_string();
+            }
+            return format!("Wegen {} ist keine Rezeptur möglich", join_german(&reasons));
+        };
+        let others: Vec<Bound> = self.conflicts.iter()
+            .enumerate()
+            .filter(|&(i, b)| i != lead && !(b.subject == first.subject && b.value == first.value))
+            .map(|(_, b)| *b)
+            .collect();
+
+        let first_text = describe_bounds(&[*first], &self.conflicts, units).remove(0);
+        let other_texts = describe_bounds(&others, &self.conflicts, units);
+        let mut text = match other_texts.len() {
+            0 => format!("{} kann mit den verfügbaren Salzen nicht eingehalten werden", first_text),
+            1 => format!("{} kann nicht eingehalten werden, solange {} gilt", first_text, other_texts[0]),
+            _ => format!("{} kann nicht eingehalten werden, solange {} gelten", first_text, join_german(&other_texts)),
+        };
+        if !reasons.is_empty() {
+            text += &format!(" (wegen {})", join_german(&reasons));
+        }
+        if !self.saturated_tanks.is_empty() {
+            let tanks: Vec<String> = self.saturated_tanks.iter().map(|t| format!("Stammlösung {}", t)).collect();
+            text += &format!(" (Löslichkeitsgrenze erreicht: {})", join_german(&tanks));
+        }
+        text
+    }
+
+    /// Restrictions on the salts the conflict depends on, as phrases after "wegen"
+    fn salt_reasons(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if !self.dosed_salts.is_empty() {
+            reasons.push(format!("der Mindest- bzw. Festmenge von {}", join_german(&self.dosed_salts)));
+        }
+        if let Some(limit) = self.salt_limit {
+            reasons.push(format!("der Beschränkung auf {} {}", limit, if limit == 1 { "Salz" } else { "Salze" }));
+        }
+        if let Some(grams) = self.min_weighing {
+            reasons.push(format!("der Mindesteinwaage von {} g", format_number(grams)));
+        }
+        reasons
+    }
+}
+
+/// Describes bounds, merging a min/max pair with equal values into "fest bei"
+fn describe_bounds(bounds: &[Bound], all: &[Bound], units: &DisplayUnits) -> Vec<String> {
+    let mut texts = Vec::new();
+    let mut seen = Vec::new();
+    for bound in bounds {
+        let counterpart = all.iter().any(|b| {
+            b.subject == bound.subject && b.side != bound.side && b.value == bound.value
+        });
+        if counterpart {
+            if seen.contains(&bound.subject) {
+                continue;
+            }
+            seen.push(bound.subject);
+            texts.push(format!(
+                "{} fest bei {}",
+                bound.subject.label(),
+                bound.subject.format_value(bound.value, units)
+            ));
+        } else {
+            texts.push(bound.describe(units));
+        }
+    }
+    texts
+}
+
+fn join_german(parts: &[String]) -> String {
+    match parts {
+        [] => String::new(),
+        [only] => only.clone(),
+        [init @ .., last] => format!("{} und {}", init.join(", "), last),
+    }
+}
+
+/// Finds the conflicting bounds and the smallest relaxation of an infeasible problem
+pub fn diagnose(problem: &OptimizationProblem) -> Infeasibility {
+    let salts = problem.enabled_salts();
+    let bounds = problem.bounds();
+
+    // Deletion filter: drop every bound whose removal keeps the rest infeasible
+    let mut conflicts = bounds.clone();
+    let mut i = 0;
+    while i < conflicts.len() {
+        let mut candidate = conflicts.clone();
+        candidate.remove(i);
+        if is_feasible(problem, &salts, &candidate) {
+            i += 1;
+        } else {
+            conflicts = candidate;
+        }
+    }
+
+    // Minimum doses the conflict depends on: lifting one makes it solvable
+    let dosed_salts = salts.iter()
+        .enumerate()
+        .filter(|(_, salt)| salt.dose.min > 0.0)
+        .filter(|&(i, _)| {
+            let mut lifted: Vec<Salt> = salts.iter().map(|s| (*s).clone()).collect();
+            lifted[i].dose.min = 0.0;
+            let lifted: Vec<&Salt> = lifted.iter().collect();
+            is_feasible(problem, &lifted, &conflicts)
+        })
+        .map(|(_, salt)| salt.name.clone())
+        .collect();
+
+    // Practical limits on the salts the conflict depends on
+    let lifts = |lift: fn(&mut OptimizationProblem)| {
+        let mut lifted = problem.clone();
+        lift(&mut lifted);
+        is_feasible(&lifted, &salts, &conflicts)
+    };
+    let salt_limit = problem.options.max_salts
+        .filter(|_| lifts(|p| p.options.max_salts = None));
+    let min_weighing = problem.options.min_weighing
+        .filter(|_| lifts(|p| p.options.min_weighing = None));
+
+    let (relaxations, saturated_tanks) = elastic_relaxations(problem, &salts, &bounds);
+    Infeasibility { conflicts, relaxations, saturated_tanks, dosed_salts, salt_limit, min_weighing }
+}
+
+fn is_feasible(problem: &OptimizationProblem, salts: &[&Salt], bounds: &[Bound]) -> bool {
+    let mut salt_model = SaltModel::new(problem, salts);
+    let salt_constraints = salt_model.salt_constraints(problem, salts);
+    let SaltModel { vars, exprs, .. } = salt_model;
+    let mut model = vars.minimise(Expression::from(0.0)).using(microlp);
+    for constraint in salt_constraints {
+        model.add_constraint(constraint);
+    }
+    for bound in bounds {
+        model.add_constraint(bound.constraint(&exprs));
+    }
+    model.solve().is_ok()
+}
+
+/// Solves with one slack per bound, minimising the relative total violation;
+/// also returns the tanks whose solubility limit the relaxed recipe reaches
+fn elastic_relaxations(problem: &OptimizationProblem, salts: &[&Salt], bounds: &[Bound]) -> (Vec<Relaxation>, Vec<String>) {
+    let mut salt_model = SaltModel::new(problem, salts);
+    let salt_constraints = salt_model.salt_constraints(problem, salts);
+    let tank_loads = salt_model.tank_loads(salts, problem.options.temperature);
+    let SaltModel { mut vars, exprs, .. } = salt_model;
+    let elastic = ElasticBound::for_bounds(&mut vars, bounds);
+    let objective = elastic.iter()
+        .fold(Expression::from(0.0), |acc, e| acc + e.relative_slack());
+
+    let mut model = vars.minimise(objective).using(microlp);
+    for e in &elastic {
+        model.add_constraint(e.constraint(&exprs));
+    }
+    for constraint in salt_constraints {
+        model.add_constraint(constraint);
+    }
+
+    let Ok(solution) = model.solve() else {
+        return (Vec::new(), Vec::new());
+    };
+
+    let relaxations = elastic.iter()
+        .filter(|e| e.is_violated(&solution))
+        .map(|e| Relaxation {
+            bound: e.bound,
+            value: e.bound.subject.evaluate(&solution, &exprs),
+        })
+        .collect();
+    let saturated_tanks = tank_loads.into_iter()
+        .filter(|(_, load)| solution.eval(load) >= SATURATION)
+        .map(|(tank, _)| tank)
+        .collect();
+    (relaxations, saturated_tanks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula;
+    use crate::models::{Nutrient, NutrientTargets};
+    use crate::optimizer::BoundSubject;
+
+    #[test]
+    fn solve_reports_unsolvable_and_diagnosis_explains_it() {
+        // KNO₃ cannot bring 50 g/L NO₃⁻ with at most 10 g/L K⁺
+        let mut salt = Salt::custom("KNO₃".to_string(), "KNO3".to_string(), formula::composition("KNO3", 1.0).unwrap());
+        salt.stock_solution = "A".to_string();
+        let targets = NutrientTargets::unconstrained()
+            .with_range(Nutrient::K, 0.0, 10.0)
+            .with_range(Nutrient::No3, 50.0, f64::INFINITY);
+        let problem = OptimizationProblem::new(targets).salts([salt]).tanks(["A".to_string()]);
+
+        let error = problem.solve().unwrap_err();
+        assert!(error.is::<Unsolvable>());
+
+        let diagnosis = diagnose(&problem);
+        let subjects: Vec<BoundSubject> = diagnosis.conflicts.iter().map(|b| b.subject).collect();
+        assert!(subjects.contains(&BoundSubject::Nutrient(Nutrient::K)));
+        assert!(subjects.contains(&BoundSubject::Nutrient(Nutrient::No3)));
+        assert!(!diagnosis.relaxations.is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+//! Electrical conductivity (EC) estimate of the final nutrient solution
+//!
+//! Sums the limiting molar conductivities of the ions (25 °C), each reduced
+//! by its Davies activity coefficient at the solution's ionic strength.
+//! Phosphorus counts as H₂PO₄⁻, the dominant form at pH 5–6; micronutrients
+//! and silicic acid are too dilute or uncharged to matter.
+
+use crate::models::{Nutrient, NutrientVector};
+
+/// Davies constant A for water at 25 °C
+const DAVIES_A: f64 = 0.509;
+
+/// Ionic strength (mol/L) per mS/cm, used where the composition is not known yet
+const IONIC_STRENGTH_PER_EC: f64 = 0.013;
+
+/// Charge and limiting molar conductivity (S cm² mol⁻¹) of the ion a nutrient forms
+fn ion(nutrient: Nutrient) -> Option<(f64, f64)> {
+    let conductivity = match nutrient {
+        Nutrient::Nh4 => 73.5,
+        Nutrient::No3 => 71.4,
+        Nutrient::K => 73.5,
+        Nutrient::P => 36.0,
+        Nutrient::Ca => 119.0,
+        Nutrient::Mg => 106.0,
+        Nutrient::S => 160.0,
+        Nutrient::Cl => 76.3,
+        Nutrient::Na => 50.1,
+        Nutrient::Hco3 => 44.5,
+        _ => return None,
+    };
+    nutrient.charge().map(|charge| (charge, conductivity))
+}
+
+/// Ionic strength (mol/L) of a solution given in g/L
+pub fn ionic_strength(concentrations: &NutrientVector) -> f64 {
+    concentrations.iter()
+        .filter_map(|(nutrient, g_per_l)| {
+            ion(nutrient).map(|(charge, _)| g_per_l / nutrient.molar_mass() * charge * charge)
+        })
+        .sum::<f64>()
+        / 2.0
+}
+
+/// Davies activity coefficient of an ion with `charge` at `ionic_strength`
+fn activity_coefficient(charge: f64, ionic_strength: f64) -> f64 {
+    let root = ionic_strength.sqrt();
+    10f64.powf(-DAVIES_A * charge * charge * (root / (1.0 + root) - 0.3 * ionic_strength))
+}
+
+/// mS/cm contributed by 1 g/L of each nutrient at a given ionic strength;
+/// linear in the concentrations, so usable as an LP constraint
+pub fn coefficients(ionic_strength: f64) -> NutrientVector {
+    let mut coefficients = NutrientVector::zero();
+    for nutrient in Nutrient::ALL {
+        if let Some((charge, conductivity)) = ion(nutrient) {
+            // g/L ÷ g/mol = mol/L, and mol/L · S cm² mol⁻¹ = mS/cm
+            coefficients[nutrient] = conductivity * activity_coefficient(charge, ionic_strength) / nutrient.molar_mass();
+        }
+    }
+    coefficients
+}
+
+/// Coefficients for a solution near the given EC, for when its composition is not known yet
+pub fn coefficients_near(ec: f64) -> NutrientVector {
+    coefficients(ec * IONIC_STRENGTH_PER_EC)
+}
+
+/// Estimated EC (mS/cm) of a solution given in g/L
+pub fn estimate_ec(concentrations: &NutrientVector) -> f64 {
+    let coefficients = coefficients(ionic_strength(concentrations));
+    concentrations.iter()
+        .map(|(nutrient, g_per_l)| g_per_l * coefficients[nutrient])
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 10 mmol/L of each ion of a salt, in g/L
+    fn millimolar(ions: &[Nutrient]) -> NutrientVector {
+        let mut concentrations = NutrientVector::zero();
+        for &nutrient in ions {
+            concentrations[nutrient] = 0.01 * nutrient.molar_mass();
+        }
+        concentrations
+    }
+
+    #[test]
+    fn potassium_chloride_standard() {
+        // 10 mmol/L KCl, the usual calibration standard, reads 1.413 mS/cm at 25 °C
+        let kcl = millimolar(&[Nutrient::K, Nutrient::Cl]);
+        assert!((ionic_strength(&kcl) - 0.01).abs() < 1e-9);
+        let ec = estimate_ec(&kcl);
+        assert!((ec - 1.413).abs() < 0.05 * 1.413, "{}", ec);
+    }
+
+    #[test]
+    fn divalent_ions_count_fourfold_in_the_ionic_strength() {
+        let mut cacl2 = millimolar(&[Nutrient::Ca, Nutrient::Cl]);
+        cacl2[Nutrient::Cl] *= 2.0;
+        assert!((ionic_strength(&cacl2) - 0.03).abs() < 1e-9);
+    }
+}
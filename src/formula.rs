@@ -0,0 +1,335 @@
+//! Chemical formula parser for salt compositions
+//!
+//! Understands the notation used in the salt database: Unicode or ASCII
+//! digits, parentheses and hydrate water after a middle dot, e.g.
+//! "Ca(NO₃)₂·4H₂O", "CaSO₄·0.5H₂O" or "(NH₄)₂SO₄". Nitrogen counts as NH₄⁺
+//! or NO₃⁻ when it appears as NH₄ or NO₃, carbon as HCO₃⁻ when it appears as
+//! HCO₃; all other nutrients are taken elementally. Carbonates (CO₃²⁻) are
+//! rejected, as the model only knows HCO₃⁻. Other nitrogen (e.g. urea) is
+//! not a nutrient in this model and only adds to the molar mass.
+
+use anyhow::{bail, Result};
+
+use crate::models::{Nutrient, NutrientVector};
+
+/// Standard atomic weights (g/mol) of the elements found in fertilizers
+const ATOMIC_WEIGHTS: [(&str, f64); 24] = [
+    ("H", 1.008), ("B", 10.81), ("C", 12.011), ("N", 14.007), ("O", 15.999),
+    ("F", 18.998), ("Na", 22.990), ("Mg", 24.305), ("Al", 26.982), ("Si", 28.085),
+    ("P", 30.974), ("S", 32.06), ("Cl", 35.45), ("K", 39.098), ("Ca", 40.078),
+    ("Mn", 54.938), ("Fe", 55.845), ("Co", 58.933), ("Ni", 58.693), ("Cu", 63.546),
+    ("Zn", 65.38), ("Se", 78.971), ("Mo", 95.95), ("I", 126.904),
+];
+
+/// Nutrients supplied by single atoms of an element
+const ELEMENT_NUTRIENTS: [(&str, Nutrient); 13] = [
+    ("K", Nutrient::K), ("P", Nutrient::P), ("Ca", Nutrient::Ca), ("Mg", Nutrient::Mg),
+    ("S", Nutrient::S), ("Cl", Nutrient::Cl), ("Fe", Nutrient::Fe), ("Mn", Nutrient::Mn),
+    ("Zn", Nutrient::Zn), ("Cu", Nutrient::Cu), ("B", Nutrient::B), ("Mo", Nutrient::Mo),
+    ("Na", Nutrient::Na),
+];
+
+/// Part of a formula that is counted as a whole
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Part {
+    Element(&'static str),
+    Ammonium,
+    Nitrate,
+    Bicarbonate,
+}
+
+impl Part {
+    fn molar_mass(self) -> f64 {
+        match self {
+            Part::Element(symbol) => atomic_weight(symbol).unwrap_or(0.0),
+            Part::Ammonium => atomic_weight("N").unwrap_or(0.0) + 4.0 * atomic_weight("H").unwrap_or(0.0),
+            Part::Nitrate => atomic_weight("N").unwrap_or(0.0) + 3.0 * atomic_weight("O").unwrap_or(0.0),
+            Part::Bicarbonate => {
+                atomic_weight("H").unwrap_or(0.0) + atomic_weight("C").unwrap_or(0.0) + 3.0 * atomic_weight("O").unwrap_or(0.0)
+            }
+        }
+    }
+
+    fn nutrient(self) -> Option<Nutrient> {
+        match self {
+            Part::Element(symbol) => ELEMENT_NUTRIENTS.iter().find(|(s, _)| *s == symbol).map(|(_, n)| *n),
+            Part::Ammonium => Some(Nutrient::Nh4),
+            Part::Nitrate => Some(Nutrient::No3),
+            Part::Bicarbonate => Some(Nutrient::Hco3),
+        }
+    }
+}
+
+fn atomic_weight(symbol: &str) -> Option<f64> {
+    ATOMIC_WEIGHTS.iter().find(|(s, _)| *s == symbol).map(|(_, w)| *w)
+}
+
+/// A parsed formula: the amount of each part per formula unit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    parts: Vec<(Part, f64)>,
+}
+
+impl Formula {
+    /// Molar mass of one formula unit including hydrate water (g/mol)
+    pub fn molar_mass(&self) -> f64 {
+        self.parts.iter().map(|(part, count)| part.molar_mass() * count).sum()
+    }
+
+    /// Nutrient mass fractions (g per g) of the pure substance
+    pub fn composition(&self) -> NutrientVector {
+        let molar_mass = self.molar_mass();
+        let mut composition = NutrientVector::zero();
+        for (part, count) in &self.parts {
+            if let Some(nutrient) = part.nutrient() {
+                composition[nutrient] += count * nutrient.molar_mass() / molar_mass;
+            }
+        }
+        composition
+    }
+}
+
+/// Parses a formula such as "Ca(NO₃)₂·4H₂O"
+pub fn parse(formula: &str) -> Result<Formula> {
+    let chars: Vec<char> = formula.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '₀'..='₉' => char::from_digit(c as u32 - '₀' as u32, 10).unwrap_or(c),
+            '[' | '{' => '(',
+            ']' | '}' => ')',
+            '*' | '•' => '·',
+            _ => c,
+        })
+        .collect();
+    // A dot is the hydrate separator, except inside a leading coefficient
+    // such as the "0.5" of "CaSO4.0.5H2O"
+    let mut normalized = String::new();
+    let mut segment_start = 0;
+    for (i, &c) in chars.iter().enumerate() {
+        let coefficient = i > segment_start && chars[segment_start..i].iter().all(|c| c.is_ascii_digit());
+        match c {
+            '.' if coefficient && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()) => normalized.push('.'),
+            '.' | '·' => {
+                normalized.push('·');
+                segment_start = i + 1;
+            }
+            c => normalized.push(c),
+        }
+    }
+    if normalized.is_empty() {
+        bail!("Leere Formel");
+    }
+
+    let mut parts = Vec::new();
+    for hydrate_part in normalized.split('·') {
+        let chars: Vec<char> = hydrate_part.chars().collect();
+        let mut parser = Parser { chars: &chars, pos: 0 };
+        let coefficient = parser.number().unwrap_or(1.0);
+        let group = parser.sequence()?;
+        if parser.pos < chars.len() {
+            bail!("Unerwartetes Zeichen „{}“ in der Formel", chars[parser.pos]);
+        }
+        if group.is_empty() {
+            bail!("Leerer Formelteil in „{}“", formula);
+        }
+        parts.extend(group.into_iter().map(|(part, count)| (part, count * coefficient)));
+    }
+    Ok(Formula { parts })
+}
+
+/// Nutrient mass fractions of a product with the given formula and purity (0.0 to 1.0)
+pub fn composition(formula: &str, purity: f64) -> Result<NutrientVector> {
+    Ok(parse(formula)?.composition().scaled(purity))
+}
+
+struct Parser<'a> {
+    chars: &'a [char],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn number(&mut self) -> Option<f64> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let fraction_follows = self.chars.get(self.pos + 1).is_some_and(|c| c.is_ascii_digit());
+        if self.pos > start && self.peek() == Some('.') && fraction_follows {
+            self.pos += 1;
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.pos += 1;
+            }
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().ok()
+    }
+
+    fn element(&mut self) -> Result<&'static str> {
+        let start = self.pos;
+        self.pos += 1;
+        while self.peek().is_some_and(|c| c.is_ascii_lowercase()) {
+            self.pos += 1;
+        }
+        let symbol: String = self.chars[start..self.pos].iter().collect();
+        match ATOMIC_WEIGHTS.iter().find(|(s, _)| *s == symbol) {
+            Some((s, _)) => Ok(s),
+            None => bail!("Unbekanntes Element „{}“ in der Formel", symbol),
+        }
+    }
+
+    /// Elements and groups up to the end or a closing parenthesis
+    fn sequence(&mut self) -> Result<Vec<(Part, f64)>> {
+        let mut atoms: Vec<(Part, f64)> = Vec::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '(' => {
+                    self.pos += 1;
+                    let group = self.sequence()?;
+                    if self.peek() != Some(')') {
+                        bail!("Klammer in der Formel nicht geschlossen");
+                    }
+                    self.pos += 1;
+                    let count = self.number().unwrap_or(1.0);
+                    atoms.extend(group.into_iter().map(|(part, n)| (part, n * count)));
+                }
+                ')' => break,
+                c if c.is_ascii_uppercase() => {
+                    let symbol = self.element()?;
+                    let count = self.number().unwrap_or(1.0);
+                    atoms.push((Part::Element(symbol), count));
+                }
+                c => bail!("Unerwartetes Zeichen „{}“ in der Formel", c),
+            }
+        }
+        combine_ions(atoms)
+    }
+}
+
+/// Merges N followed by H₄ or O₃ into NH₄ / NO₃ and H followed by C and O₃
+/// into HCO₃; fails on CO₃ without the H
+fn combine_ions(atoms: Vec<(Part, f64)>) -> Result<Vec<(Part, f64)>> {
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < atoms.len() {
+        let ion = match (atoms[i], atoms.get(i + 1), atoms.get(i + 2)) {
+            ((Part::Element("N"), 1.0), Some((Part::Element("H"), 4.0)), _) => Some((Part::Ammonium, 2)),
+            ((Part::Element("N"), 1.0), Some((Part::Element("O"), 3.0)), _) => Some((Part::Nitrate, 2)),
+            ((Part::Element("H"), 1.0), Some((Part::Element("C"), 1.0)), Some((Part::Element("O"), 3.0))) => {
+                Some((Part::Bicarbonate, 3))
+            }
+            ((Part::Element("C"), 1.0), Some((Part::Element("O"), 3.0)), _) => {
+                bail!("Carbonat (CO₃²⁻) wird nicht unterstützt; bitte als Hydrogencarbonat (HCO₃⁻) eingeben")
+            }
+            _ => None,
+        };
+        match ion {
+            Some((ion, len)) => {
+                parts.push((ion, 1.0));
+                i += len;
+            }
+            None => {
+                parts.push(atoms[i]);
+                i += 1;
+            }
+        }
+    }
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::get_predefined_salts;
+
+    /// Nitrogen fraction from the NH₄⁺ and NO₃⁻ fractions
+    fn nitrogen(composition: &NutrientVector) -> f64 {
+        [Nutrient::Nh4, Nutrient::No3].iter()
+            .map(|&n| composition[n] * 14.007 / n.molar_mass())
+            .sum()
+    }
+
+    /// Textbook mass fractions of the pure salts: (formula, N, K, P, Ca, S)
+    const EXPECTED: [(&str, f64, f64, f64, f64, f64); 10] = [
+        ("Ca(NO₃)₂·4H₂O", 0.1186, 0.0, 0.0, 0.1697, 0.0),
+        ("Mg(NO₃)₂·6H₂O", 0.1093, 0.0, 0.0, 0.0, 0.0),
+        ("CaCl₂·2H₂O", 0.0, 0.0, 0.0, 0.2726, 0.0),
+        ("KNO₃", 0.1385, 0.3867, 0.0, 0.0, 0.0),
+        ("(NH₄)₂SO₄", 0.2120, 0.0, 0.0, 0.0, 0.2426),
+        ("NH₄H₂PO₄", 0.1218, 0.0, 0.2693, 0.0, 0.0),
+        ("NH₄Cl", 0.2619, 0.0, 0.0, 0.0, 0.0),
+        ("KH₂PO₄", 0.0, 0.2873, 0.2276, 0.0, 0.0),
+        ("K₂SO₄", 0.0, 0.4487, 0.0, 0.0, 0.1840),
+        ("MgSO₄·7H₂O", 0.0, 0.0, 0.0, 0.0, 0.1301),
+    ];
+
+    #[test]
+    fn formulas_give_textbook_fractions() {
+        for (formula, n, k, p, ca, s) in EXPECTED {
+            let c = &composition(formula, 1.0).unwrap();
+            for (label, actual, expected) in [
+                ("N", nitrogen(c), n),
+                ("K", c[Nutrient::K], k),
+                ("P", c[Nutrient::P], p),
+                ("Ca", c[Nutrient::Ca], ca),
+                ("S", c[Nutrient::S], s),
+            ] {
+                assert!((actual - expected).abs() < 5e-4, "{} {}: {} statt {}", formula, label, actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn technical_calcium_nitrate_keeps_its_label_nitrogen() {
+        let salts = get_predefined_salts();
+        let salt = salts.iter().find(|salt| salt.name == "Ca(NO₃)₂·4H₂O").unwrap();
+        let c = &salt.composition;
+        assert!((nitrogen(c) - 0.155).abs() < 5e-4);
+        assert!((c[Nutrient::Nh4] * 14.007 / Nutrient::Nh4.molar_mass() - 0.011).abs() < 5e-4);
+    }
+
+    #[test]
+    fn technical_grades_scale_with_purity() {
+        let nitric = composition("HNO₃", 0.65).unwrap();
+        assert!((nitrogen(&nitric) - 0.65 * 0.2223).abs() < 5e-4);
+        let phosphoric = composition("H₃PO₄", 0.75).unwrap();
+        assert!((phosphoric[Nutrient::P] - 0.75 * 0.3161).abs() < 5e-4);
+    }
+
+    #[test]
+    fn bicarbonate_is_one_hco3() {
+        let c = composition("KHCO₃", 1.0).unwrap();
+        assert!((c[Nutrient::K] - 0.3905).abs() < 5e-4);
+        assert!((c[Nutrient::Hco3] - 0.6095).abs() < 5e-4);
+    }
+
+    #[test]
+    fn carbonates_are_rejected() {
+        for formula in ["K₂CO₃", "CaCO3", "MgCO₃"] {
+            assert!(parse(formula).is_err(), "{}", formula);
+        }
+    }
+
+    #[test]
+    fn ascii_and_decimal_hydrates() {
+        let unicode = parse("MgSO₄·7H₂O").unwrap().molar_mass();
+        for formula in ["MgSO4.7H2O", "MgSO4*7H2O", "MgSO4 · 7 H2O"] {
+            assert!((parse(formula).unwrap().molar_mass() - unicode).abs() < 1e-9, "{}", formula);
+        }
+        for formula in ["CaSO₄·0.5H₂O", "CaSO4.0.5H2O"] {
+            assert!((parse(formula).unwrap().molar_mass() - 145.15).abs() < 0.01, "{}", formula);
+        }
+        let mass = parse("2.5H₂O").unwrap().molar_mass();
+        assert!((mass - 2.5 * 18.015).abs() < 0.01);
+    }
+
+    #[test]
+    fn invalid_formulas_are_rejected() {
+        for formula in ["", "  ", "Xy2", "Ca(NO3", "KNO3)", "K-NO3", "CaSO4··2H2O", "kno3"] {
+            assert!(parse(formula).is_err(), "„{}“ sollte nicht lesbar sein", formula);
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! Nutrient solution recipe optimizer
+//!
+//! The data model, salt database and solver are usable without the UI, e.g.
+//! from scripts that build many [`optimizer::OptimizationProblem`]s.
+
+pub mod models;
+pub mod data;
+pub mod optimizer;
+pub mod diagnosis;
+pub mod sensitivity;
+pub mod sweep;
+pub mod pareto;
+pub mod compatibility;
+pub mod ec;
+pub mod charge;
+pub mod ph;
+pub mod formula;
+pub mod salt_csv;
+pub mod persistence;
+pub mod share;
+pub mod components;
@@ -1,23 +1,36 @@
 use dioxus::prelude::*;
 
-mod models;
-mod data;
-mod optimizer;
-mod components;
-
-use components::FertilizerOptimizer;
+use nh4no3::components::FertilizerOptimizer;
+use nh4no3::share;
 
 const MAIN_CSS: Asset = asset!("/assets/styling/main.css");
 
+/// Reports every change of the URL fragment, e.g. a share link pasted into an open tab
+const HASH_CHANGE_SCRIPT: &str = r#"
+    window.addEventListener("hashchange", () => dioxus.send(null));
+    await new Promise(() => {});
+"#;
+
 fn main() {
     dioxus::launch(App);
 }
 
 #[component]
 fn App() -> Element {
+    // Code of a share link the page was opened with, or that arrives later
+    let mut opened_link = use_signal(share::take_link_code);
+    use_future(move || async move {
+        let mut hash_changes = document::eval(HASH_CHANGE_SCRIPT);
+        while hash_changes.recv::<serde_json::Value>().await.is_ok() {
+            if let Some(code) = share::take_link_code() {
+                opened_link.set(Some(code));
+            }
+        }
+    });
+
     rsx! {
         document::Link { rel: "stylesheet", href: MAIN_CSS }
-        FertilizerOptimizer {}
+        FertilizerOptimizer { opened_link }
         ImpressumView {}
     }
 }
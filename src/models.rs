@@ -1,91 +1,953 @@
-/// Data structures for the fertilizer optimizer application
+//! Data structures for the fertilizer optimizer application
 
-#[derive(Debug, Clone, PartialEq)]
+use std::collections::BTreeMap;
+use std::ops::{Index, IndexMut};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::charge::ChargeBalance;
+use crate::compatibility::{self, Conflict};
+use crate::optimizer::Deviation;
+
+/// Nutrients tracked by the optimizer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Nutrient {
+    Nh4,
+    No3,
+    K,
+    P,
+    Ca,
+    Mg,
+    S,
+    Cl,
+    Fe,
+    Mn,
+    Zn,
+    Cu,
+    B,
+    Mo,
+    Na,
+    Si,
+    Hco3,
+}
+
+impl Nutrient {
+    pub const COUNT: usize = 17;
+
+    /// All nutrients in display order
+    pub const ALL: [Nutrient; Nutrient::COUNT] = [
+        Nutrient::Nh4, Nutrient::No3, Nutrient::K, Nutrient::P,
+        Nutrient::Ca, Nutrient::Mg, Nutrient::S, Nutrient::Cl,
+        Nutrient::Fe, Nutrient::Mn, Nutrient::Zn, Nutrient::Cu,
+        Nutrient::B, Nutrient::Mo, Nutrient::Na, Nutrient::Si,
+        Nutrient::Hco3,
+    ];
+
+    /// Short lowercase identifier, used for CSS classes
+    pub fn key(self) -> &'static str {
+        match self {
+            Nutrient::Nh4 => "nh4",
+            Nutrient::No3 => "no3",
+            Nutrient::K => "k",
+            Nutrient::P => "p",
+            Nutrient::Ca => "ca",
+            Nutrient::Mg => "mg",
+            Nutrient::S => "s",
+            Nutrient::Cl => "cl",
+            Nutrient::Fe => "fe",
+            Nutrient::Mn => "mn",
+            Nutrient::Zn => "zn",
+            Nutrient::Cu => "cu",
+            Nutrient::B => "b",
+            Nutrient::Mo => "mo",
+            Nutrient::Na => "na",
+            Nutrient::Si => "si",
+            Nutrient::Hco3 => "hco3",
+        }
+    }
+
+    /// Chemical symbol as shown in the UI
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Nutrient::Nh4 => "NH₄⁺",
+            Nutrient::No3 => "NO₃⁻",
+            Nutrient::K => "K⁺",
+            Nutrient::P => "P",
+            Nutrient::Ca => "Ca²⁺",
+            Nutrient::Mg => "Mg²⁺",
+            Nutrient::S => "S",
+            Nutrient::Cl => "Cl⁻",
+            Nutrient::Fe => "Fe",
+            Nutrient::Mn => "Mn",
+            Nutrient::Zn => "Zn",
+            Nutrient::Cu => "Cu",
+            Nutrient::B => "B",
+            Nutrient::Mo => "Mo",
+            Nutrient::Na => "Na⁺",
+            Nutrient::Si => "Si",
+            Nutrient::Hco3 => "HCO₃⁻",
+        }
+    }
+
+    /// German display name
+    pub fn name(self) -> &'static str {
+        match self {
+            Nutrient::Nh4 => "Ammonium",
+            Nutrient::No3 => "Nitrat",
+            Nutrient::K => "Kalium",
+            Nutrient::P => "Phosphor",
+            Nutrient::Ca => "Kalzium",
+            Nutrient::Mg => "Magnesium",
+            Nutrient::S => "Schwefel",
+            Nutrient::Cl => "Chlorid",
+            Nutrient::Fe => "Eisen",
+            Nutrient::Mn => "Mangan",
+            Nutrient::Zn => "Zink",
+            Nutrient::Cu => "Kupfer",
+            Nutrient::B => "Bor",
+            Nutrient::Mo => "Molybdän",
+            Nutrient::Na => "Natrium",
+            Nutrient::Si => "Silizium",
+            Nutrient::Hco3 => "Hydrogencarbonat",
+        }
+    }
+
+    /// NH₄⁺ and NO₃⁻ are constrained through total nitrogen and the NH₄⁺ ratio
+    pub fn is_nitrogen(self) -> bool {
+        matches!(self, Nutrient::Nh4 | Nutrient::No3)
+    }
+
+    /// Micronutrients are entered and displayed in mg/L instead of g/L
+    pub fn is_micronutrient(self) -> bool {
+        matches!(
+            self,
+            Nutrient::Fe | Nutrient::Mn | Nutrient::Zn | Nutrient::Cu | Nutrient::B | Nutrient::Mo
+        )
+    }
+
+    pub fn is_macronutrient(self) -> bool {
+        !self.is_micronutrient() && !self.is_other_element()
+    }
+
+    /// Beneficial or undesired constituents that are tracked but not essential
+    pub fn is_other_element(self) -> bool {
+        matches!(self, Nutrient::Na | Nutrient::Si | Nutrient::Hco3)
+    }
+
+    /// Molar mass of the ion or element in g/mol
+    pub fn molar_mass(self) -> f64 {
+        match self {
+            Nutrient::Nh4 => 18.038,
+            Nutrient::No3 => 62.004,
+            Nutrient::K => 39.098,
+            Nutrient::P => 30.974,
+            Nutrient::Ca => 40.078,
+            Nutrient::Mg => 24.305,
+            Nutrient::S => 32.06,
+            Nutrient::Cl => 35.45,
+            Nutrient::Fe => 55.845,
+            Nutrient::Mn => 54.938,
+            Nutrient::Zn => 65.38,
+            Nutrient::Cu => 63.546,
+            Nutrient::B => 10.81,
+            Nutrient::Mo => 95.95,
+            Nutrient::Na => 22.990,
+            Nutrient::Si => 28.086,
+            Nutrient::Hco3 => 61.017,
+        }
+    }
+
+    /// Fertilizer-label oxide and its mass per mass of the nutrient
+    pub fn oxide(self) -> Option<(&'static str, f64)> {
+        match self {
+            Nutrient::P => Some(("P₂O₅", 2.2914)),
+            Nutrient::K => Some(("K₂O", 1.2046)),
+            Nutrient::Ca => Some(("CaO", 1.3992)),
+            Nutrient::Mg => Some(("MgO", 1.6583)),
+            Nutrient::S => Some(("SO₃", 2.4972)),
+            _ => None,
+        }
+    }
+
+    /// Charge of the ion the nutrient is dissolved as; phosphorus counts as
+    /// H₂PO₄⁻ (dominant at pH 5–6) and sulfur as SO₄²⁻. `None` for micronutrients
+    /// and uncharged silicic acid.
+    pub fn charge(self) -> Option<f64> {
+        match self {
+            Nutrient::Nh4 | Nutrient::K | Nutrient::Na => Some(1.0),
+            Nutrient::Ca | Nutrient::Mg => Some(2.0),
+            Nutrient::No3 | Nutrient::P | Nutrient::Cl | Nutrient::Hco3 => Some(-1.0),
+            Nutrient::S => Some(-2.0),
+            _ => None,
+        }
+    }
+
+    /// Unit used for targets and results unless specified otherwise
+    pub fn default_unit(self) -> Unit {
+        if self.is_micronutrient() {
+            Unit::MilligramsPerLiter
+        } else {
+            Unit::GramsPerLiter
+        }
+    }
+}
+
+/// Concentration units for targets and results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Unit {
+    GramsPerLiter,
+    MilligramsPerLiter,
+    /// Same as mg/L, as written on many water analyses
+    PartsPerMillion,
+    MillimolesPerLiter,
+    MicromolesPerLiter,
+    /// Charge equivalents; nutrients without an ionic charge count as mmol/L
+    MilliequivalentsPerLiter,
+    /// Mass of the fertilizer-label oxide (P₂O₅, K₂O, CaO, MgO, SO₃); other nutrients as g/L
+    OxideGramsPerLiter,
+    /// Mass of the fertilizer-label oxide; other nutrients as mg/L
+    OxideMilligramsPerLiter,
+}
+
+impl Unit {
+    pub fn label(self) -> &'static str {
+        match self {
+            Unit::GramsPerLiter | Unit::OxideGramsPerLiter => "g l⁻¹",
+            Unit::MilligramsPerLiter | Unit::OxideMilligramsPerLiter => "mg l⁻¹",
+            Unit::PartsPerMillion => "ppm",
+            Unit::MillimolesPerLiter => "mmol l⁻¹",
+            Unit::MicromolesPerLiter => "µmol l⁻¹",
+            Unit::MilliequivalentsPerLiter => "meq l⁻¹",
+        }
+    }
+
+    /// Symbol of what is measured: the oxide for oxide units, else the nutrient
+    pub fn symbol(self, nutrient: Nutrient) -> &'static str {
+        match (self, nutrient.oxide()) {
+            (Unit::OxideGramsPerLiter | Unit::OxideMilligramsPerLiter, Some((oxide, _))) => oxide,
+            _ => nutrient.symbol(),
+        }
+    }
+
+    /// Unit label naming the oxide where one is used, e.g. "P₂O₅ mg l⁻¹"
+    pub fn label_for(self, nutrient: Nutrient) -> String {
+        if self.symbol(nutrient) == nutrient.symbol() {
+            self.label().to_string()
+        } else {
+            format!("{} {}", self.symbol(nutrient), self.label())
+        }
+    }
+
+    /// g/L of the nutrient per unit value
+    fn grams_per_liter_per_unit(self, nutrient: Nutrient) -> f64 {
+        let per_oxide = nutrient.oxide().map_or(1.0, |(_, factor)| 1.0 / factor);
+        match self {
+            Unit::GramsPerLiter => 1.0,
+            Unit::MilligramsPerLiter | Unit::PartsPerMillion => 1e-3,
+            Unit::MillimolesPerLiter => nutrient.molar_mass() / 1e3,
+            Unit::MicromolesPerLiter => nutrient.molar_mass() / 1e6,
+            Unit::MilliequivalentsPerLiter => {
+                let charge = nutrient.charge().map_or(1.0, f64::abs);
+                nutrient.molar_mass() / charge / 1e3
+            }
+            Unit::OxideGramsPerLiter => per_oxide,
+            Unit::OxideMilligramsPerLiter => per_oxide / 1e3,
+        }
+    }
+
+    /// Converts a value of `nutrient` in this unit to g/L, the unit used inside the LP
+    pub fn to_grams_per_liter(self, nutrient: Nutrient, value: f64) -> f64 {
+        value * self.grams_per_liter_per_unit(nutrient)
+    }
+
+    /// Converts a value of `nutrient` in g/L to this unit
+    pub fn from_grams_per_liter(self, nutrient: Nutrient, value: f64) -> f64 {
+        value / self.grams_per_liter_per_unit(nutrient)
+    }
+}
+
+/// Consistent choice of units for all nutrients, switchable per view
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnitSystem {
+    /// g/L in the stock solution, mg/L in the final solution; micronutrients always mg/L
+    #[default]
+    Mass,
+    Ppm,
+    /// mmol/L, micronutrients µmol/L (Sonneveld style)
+    Molar,
+    /// meq/L for ions, otherwise as [`UnitSystem::Molar`]
+    Equivalent,
+    /// Fertilizer-label oxides P₂O₅, K₂O, CaO, MgO and SO₃, otherwise as [`UnitSystem::Mass`]
+    Oxide,
+}
+
+impl UnitSystem {
+    pub const ALL: [UnitSystem; 5] = [UnitSystem::Mass, UnitSystem::Ppm, UnitSystem::Molar, UnitSystem::Equivalent, UnitSystem::Oxide];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            UnitSystem::Mass => "g l⁻¹ / mg l⁻¹",
+            UnitSystem::Ppm => "ppm",
+            UnitSystem::Molar => "mmol l⁻¹",
+            UnitSystem::Equivalent => "meq l⁻¹",
+            UnitSystem::Oxide => "Oxide (P₂O₅, K₂O, …)",
+        }
+    }
+
+    /// Unit of a nutrient on the given basis
+    pub fn unit_for(self, nutrient: Nutrient, basis: TargetBasis) -> Unit {
+        let mass = match basis {
+            TargetBasis::Stock => nutrient.default_unit(),
+            TargetBasis::Final => Unit::MilligramsPerLiter,
+        };
+        match self {
+            UnitSystem::Mass => mass,
+            UnitSystem::Ppm => Unit::PartsPerMillion,
+            UnitSystem::Molar | UnitSystem::Equivalent if nutrient.is_micronutrient() => Unit::MicromolesPerLiter,
+            UnitSystem::Molar => Unit::MillimolesPerLiter,
+            UnitSystem::Equivalent if nutrient.charge().is_some() => Unit::MilliequivalentsPerLiter,
+            UnitSystem::Equivalent => Unit::MillimolesPerLiter,
+            UnitSystem::Oxide => match mass {
+                _ if nutrient.oxide().is_none() => mass,
+                Unit::GramsPerLiter => Unit::OxideGramsPerLiter,
+                _ => Unit::OxideMilligramsPerLiter,
+            },
+        }
+    }
+
+    /// System the targets are entered in, judged by a macronutrient
+    pub fn of(targets: &NutrientTargets) -> Self {
+        UnitSystem::ALL.into_iter()
+            .find(|system| system.unit_for(Nutrient::K, targets.basis) == targets.get(Nutrient::K).unit)
+            .unwrap_or_default()
+    }
+}
+
+/// Whether targets describe the stock solution or the diluted nutrient solution
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TargetBasis {
+    /// Concentrations in the stock solution
+    #[default]
+    Stock,
+    /// Concentrations in the final solution after injector dilution
+    Final,
+}
+
+impl TargetBasis {
+    pub fn label(self) -> &'static str {
+        match self {
+            TargetBasis::Stock => "Stammlösung",
+            TargetBasis::Final => "Nährlösung",
+        }
+    }
+
+    /// Converts a concentration on this basis to the stock solution
+    pub fn to_stock(self, value: f64, dilution_ratio: f64) -> f64 {
+        match self {
+            TargetBasis::Stock => value,
+            TargetBasis::Final => value * dilution_ratio,
+        }
+    }
+
+    /// Converts a stock concentration to this basis
+    pub fn from_stock(self, value: f64, dilution_ratio: f64) -> f64 {
+        match self {
+            TargetBasis::Stock => value,
+            TargetBasis::Final => value / dilution_ratio,
+        }
+    }
+}
+
+/// Inclusive concentration range; `max` may be infinite
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Range {
+    pub min: f64,
+    #[serde(with = "infinite_as_null", default = "infinite_as_null::open")]
+    pub max: f64,
+}
+
+/// JSON has no infinity, so an open upper limit is stored as `null` (or left out)
+mod infinite_as_null {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        match value.is_finite() {
+            true => serializer.serialize_some(value),
+            false => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::INFINITY))
+    }
+
+    pub fn open() -> f64 {
+        f64::INFINITY
+    }
+}
+
+impl Range {
+    pub fn new(min: f64, max: f64) -> Self {
+        Range { min, max }
+    }
+
+    pub fn exact(value: f64) -> Self {
+        Range { min: value, max: value }
+    }
+
+    pub fn unbounded() -> Self {
+        Range { min: 0.0, max: f64::INFINITY }
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        (self.min..=self.max).contains(&value)
+    }
+
+    pub fn map(self, f: impl Fn(f64) -> f64) -> Self {
+        Range { min: f(self.min), max: f(self.max) }
+    }
+}
+
+/// One value per nutrient, indexed by [`Nutrient`]
+///
+/// Holds mass fractions (g per g salt) for salt compositions and g/L for
+/// concentrations; [`Nutrient::default_unit`] gives the unit for display.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NutrientVector([f64; Nutrient::COUNT]);
+
+impl NutrientVector {
+    pub fn zero() -> Self {
+        NutrientVector::default()
+    }
+
+    /// Vector with the given entries set and all others zero
+    pub fn from_pairs(pairs: &[(Nutrient, f64)]) -> Self {
+        let mut vector = NutrientVector::zero();
+        for &(nutrient, value) in pairs {
+            vector[nutrient] = value;
+        }
+        vector
+    }
+
+    /// Value converted from g/L to the nutrient's display unit
+    pub fn display_value(&self, nutrient: Nutrient) -> f64 {
+        nutrient.default_unit().from_grams_per_liter(nutrient, self[nutrient])
+    }
+
+    /// Every entry multiplied by `factor`
+    pub fn scaled(&self, factor: f64) -> NutrientVector {
+        NutrientVector(self.0.map(|value| value * factor))
+    }
+
+    /// Non-zero entries in display order
+    pub fn non_zero(&self) -> impl Iterator<Item = (Nutrient, f64)> + '_ {
+        self.iter().filter(|&(_, value)| value != 0.0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Nutrient, f64)> + '_ {
+        Nutrient::ALL.iter().map(|&n| (n, self[n]))
+    }
+}
+
+impl Index<Nutrient> for NutrientVector {
+    type Output = f64;
+
+    fn index(&self, nutrient: Nutrient) -> &f64 {
+        &self.0[nutrient as usize]
+    }
+}
+
+impl IndexMut<Nutrient> for NutrientVector {
+    fn index_mut(&mut self, nutrient: Nutrient) -> &mut f64 {
+        &mut self.0[nutrient as usize]
+    }
+}
+
+/// Stored as a map of the non-zero entries, so that adding a nutrient keeps old data readable
+impl Serialize for NutrientVector {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.non_zero())
+    }
+}
+
+impl<'de> Deserialize<'de> for NutrientVector {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = BTreeMap::<Nutrient, f64>::deserialize(deserializer)?;
+        Ok(NutrientVector::from_pairs(&entries.into_iter().collect::<Vec<_>>()))
+    }
+}
+
+/// Target for a single nutrient
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NutrientTarget {
+    pub range: Range,
+    pub unit: Unit,
+    /// Relative importance of this nutrient, `None` means 1.0
+    pub weight: Option<f64>,
+    /// Preferred value within the range for the closest-recipe mode;
+    /// `None` accepts any value inside the range
+    pub target: Option<f64>,
+}
+
+impl NutrientTarget {
+    /// Unconstrained target in the nutrient's default unit
+    pub fn unbounded(nutrient: Nutrient) -> Self {
+        NutrientTarget {
+            range: Range::unbounded(),
+            unit: nutrient.default_unit(),
+            weight: None,
+            target: None,
+        }
+    }
+
+    /// Range converted to g/L
+    pub fn range_g_per_l(&self, nutrient: Nutrient) -> Range {
+        self.range.map(|v| self.unit.to_grams_per_liter(nutrient, v))
+    }
+
+    /// Preferred value converted to g/L
+    pub fn target_g_per_l(&self, nutrient: Nutrient) -> Option<f64> {
+        self.target.map(|v| self.unit.to_grams_per_liter(nutrient, v))
+    }
+
+    /// Same target expressed in another unit and basis
+    fn convert(self, nutrient: Nutrient, from: TargetBasis, to: TargetBasis, unit: Unit, dilution_ratio: f64) -> Self {
+        let convert = |v: f64| {
+            let stock = from.to_stock(self.unit.to_grams_per_liter(nutrient, v), dilution_ratio);
+            unit.from_grams_per_liter(nutrient, to.from_stock(stock, dilution_ratio))
+        };
+        NutrientTarget {
+            range: self.range.map(convert),
+            unit,
+            target: self.target.map(convert),
+            ..self
+        }
+    }
+
+    pub fn weight_or_default(&self) -> f64 {
+        self.weight.unwrap_or(1.0)
+    }
+}
+
+/// Target ranges for all nutrients of a stock solution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NutrientTargets {
+    /// What the ranges refer to; the optimizer scales final-solution targets
+    /// by the reference dilution ratio
+    pub basis: TargetBasis,
+    /// Total nitrogen (NH₄⁺ + NO₃⁻) in [`nitrogen_unit`](Self::nitrogen_unit)
+    pub nitrogen: Range,
+    /// Share of NH₄⁺ in total nitrogen (0.0 to 1.0), `None` leaves it free
+    pub nh4_ratio: Option<f64>,
+    /// Upper limit for the EC of the final solution (mS/cm)
+    pub ec_max: Option<f64>,
+    /// pH range of the final solution; a minimum of 0 and an infinite maximum leave it free
+    pub ph: Range,
+    #[serde(rename = "nutrients")]
+    targets: BTreeMap<Nutrient, NutrientTarget>,
+}
+
+impl NutrientTargets {
+    /// Targets without any constraint
+    pub fn unconstrained() -> Self {
+        NutrientTargets {
+            basis: TargetBasis::Stock,
+            nitrogen: Range::unbounded(),
+            nh4_ratio: None,
+            ec_max: None,
+            ph: Range::unbounded(),
+            targets: BTreeMap::new(),
+        }
+    }
+
+    /// Target of a nutrient, unbounded if none was set
+    pub fn get(&self, nutrient: Nutrient) -> NutrientTarget {
+        self.targets
+            .get(&nutrient)
+            .copied()
+            .unwrap_or_else(|| NutrientTarget::unbounded(nutrient))
+    }
+
+    pub fn set(&mut self, nutrient: Nutrient, target: NutrientTarget) {
+        self.targets.insert(nutrient, target);
+    }
+
+    /// Replaces the range of a nutrient, keeping its unit and weight
+    pub fn set_range(&mut self, nutrient: Nutrient, range: Range) {
+        let mut target = self.get(nutrient);
+        target.range = range;
+        self.set(nutrient, target);
+    }
+
+    /// Builder variant of [`set_range`](Self::set_range), range in the nutrient's current unit
+    pub fn with_range(mut self, nutrient: Nutrient, min: f64, max: f64) -> Self {
+        self.set_range(nutrient, Range::new(min, max));
+        self
+    }
+
+    pub fn with_weight(mut self, nutrient: Nutrient, weight: f64) -> Self {
+        let mut target = self.get(nutrient);
+        target.weight = Some(weight);
+        self.set(nutrient, target);
+        self
+    }
+
+    /// Preferred value in the nutrient's current unit, see [`NutrientTarget::target`]
+    pub fn with_target(mut self, nutrient: Nutrient, value: f64) -> Self {
+        let mut target = self.get(nutrient);
+        target.target = Some(value);
+        self.set(nutrient, target);
+        self
+    }
+
+    pub fn with_nitrogen(mut self, min: f64, max: f64, nh4_ratio: Option<f64>) -> Self {
+        self.nitrogen = Range::new(min, max);
+        self.nh4_ratio = nh4_ratio;
+        self
+    }
+
+    /// All nutrient targets in display order
+    pub fn iter(&self) -> impl Iterator<Item = (Nutrient, NutrientTarget)> + '_ {
+        Nutrient::ALL.iter().map(|&n| (n, self.get(n)))
+    }
+
+    /// Total nitrogen is entered in g/L for stock and mg/L for final-solution targets
+    pub fn nitrogen_unit(&self) -> Unit {
+        match self.basis {
+            TargetBasis::Stock => Unit::GramsPerLiter,
+            TargetBasis::Final => Unit::MilligramsPerLiter,
+        }
+    }
+
+    /// Total nitrogen range in g/L on the targets' basis
+    pub fn nitrogen_g_per_l(&self) -> Range {
+        let unit = self.nitrogen_unit();
+        // Nitrogen is a sum of ion masses; mass units ignore the nutrient
+        self.nitrogen.map(|v| unit.to_grams_per_liter(Nutrient::No3, v))
+    }
+
+    /// Equivalent targets on another basis, with every nutrient in `unit_for(nutrient)`
+    pub fn rebase(&self, basis: TargetBasis, unit_for: impl Fn(Nutrient) -> Unit, dilution_ratio: f64) -> Self {
+        let mut rebased = NutrientTargets { basis, ..self.clone() };
+        let nitrogen_unit = rebased.nitrogen_unit();
+        rebased.nitrogen = self.nitrogen_g_per_l().map(|v| {
+            let g_per_l = basis.from_stock(self.basis.to_stock(v, dilution_ratio), dilution_ratio);
+            nitrogen_unit.from_grams_per_liter(Nutrient::No3, g_per_l)
+        });
+        for (nutrient, target) in self.iter() {
+            rebased.set(nutrient, target.convert(nutrient, self.basis, basis, unit_for(nutrient), dilution_ratio));
+        }
+        rebased
+    }
+}
+
+impl Default for NutrientTargets {
+    /// Default recipe shown on first load
+    fn default() -> Self {
+        NutrientTargets::unconstrained()
+            .with_nitrogen(40.0, 40.0, Some(0.5))
+            .with_range(Nutrient::K, 15.0, 25.0)
+            .with_range(Nutrient::P, 4.0, 8.0)
+            .with_range(Nutrient::Ca, 10.0, 15.0)
+            .with_range(Nutrient::Mg, 4.0, 5.0)
+            .with_range(Nutrient::S, 20.0, 25.0)
+            .with_range(Nutrient::Cl, 0.0, 75.0)
+            // Room for the fixed Ferty 72 and Ferty 10 doses; no minimum, so
+            // the recipe stays feasible without them
+            .with_range(Nutrient::Fe, 0.0, 35.0)
+            .with_range(Nutrient::Mn, 0.0, 25.0)
+            .with_range(Nutrient::Zn, 0.0, 5.0)
+            .with_range(Nutrient::Cu, 0.0, 2.0)
+            .with_range(Nutrient::B, 0.0, 5.0)
+            .with_range(Nutrient::Mo, 0.0, 1.5)
+    }
+}
+
+/// Nutrients already present in the irrigation water
+///
+/// Counted toward the final solution, so the salts only supply the remainder.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct WaterAnalysis {
+    /// Concentrations in mg/L of water; S is elemental, see [`set_sulfate`](Self::set_sulfate)
+    pub concentrations: NutrientVector,
+}
+
+impl WaterAnalysis {
+    /// Mass of S in one unit of mass of SO₄²⁻
+    pub const SULFUR_PER_SULFATE: f64 = 32.06 / 96.06;
+
+    /// Water without any dissolved nutrients (e.g. reverse osmosis)
+    pub fn pure() -> Self {
+        WaterAnalysis::default()
+    }
+
+    /// Builder variant, concentration in mg/L
+    pub fn with(mut self, nutrient: Nutrient, mg_per_l: f64) -> Self {
+        self.concentrations[nutrient] = mg_per_l;
+        self
+    }
+
+    pub fn is_pure(&self) -> bool {
+        self.concentrations.non_zero().next().is_none()
+    }
+
+    /// Sulfate in mg/L, as reported by most water analyses
+    pub fn sulfate(&self) -> f64 {
+        self.concentrations[Nutrient::S] / Self::SULFUR_PER_SULFATE
+    }
+
+    pub fn set_sulfate(&mut self, mg_per_l: f64) {
+        self.concentrations[Nutrient::S] = mg_per_l * Self::SULFUR_PER_SULFATE;
+    }
+
+    /// Contribution expressed like a stock solution (g/L) diluted 1:`dilution_ratio`
+    pub fn stock_equivalent(&self, dilution_ratio: f64) -> NutrientVector {
+        let mut vector = NutrientVector::zero();
+        for (nutrient, mg_per_l) in self.concentrations.iter() {
+            vector[nutrient] = mg_per_l / 1000.0 * dilution_ratio;
+        }
+        vector
+    }
+}
+
+/// Purchase price of a salt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Price {
+    pub per_kg: f64,
+    /// Currency code, e.g. "EUR"; prices in different currencies are not converted
+    pub currency: String,
+}
+
+impl Price {
+    pub fn new(per_kg: f64, currency: &str) -> Self {
+        Price { per_kg, currency: currency.to_string() }
+    }
+}
+
+/// Solubility of a salt in g per litre of stock solution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Solubility {
+    /// (°C, g/L) sorted by temperature; a single point applies at every temperature
+    pub points: Vec<(f64, f64)>,
+}
+
+impl Solubility {
+    /// Temperature of single-value solubilities and of the default tank
+    pub const REFERENCE_TEMPERATURE: f64 = 20.0;
+
+    pub fn at_reference(g_per_l: f64) -> Self {
+        Solubility { points: vec![(Self::REFERENCE_TEMPERATURE, g_per_l)] }
+    }
+
+    pub fn curve(points: &[(f64, f64)]) -> Self {
+        let mut points = points.to_vec();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Solubility { points }
+    }
+
+    /// Solubility at `temperature` (°C), interpolated linearly and held
+    /// constant beyond the ends of the curve
+    pub fn at(&self, temperature: f64) -> f64 {
+        let (Some(&first), Some(&last)) = (self.points.first(), self.points.last()) else {
+            return f64::INFINITY;
+        };
+        if temperature <= first.0 {
+            return first.1;
+        }
+        if temperature >= last.0 {
+            return last.1;
+        }
+        self.points.windows(2)
+            .find(|w| temperature <= w[1].0)
+            .map(|w| {
+                let ((t0, s0), (t1, s1)) = (w[0], w[1]);
+                s0 + (s1 - s0) * (temperature - t0) / (t1 - t0)
+            })
+            .unwrap_or(last.1)
+    }
+}
+
+/// A fertilizer salt or liquid product
+///
+/// Fields added after the first stored workspace need a serde default, so
+/// that salts saved by older versions still load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Salt {
     pub name: String,
     pub formula: String,
+    #[serde(default)]
     pub is_custom: bool,
+    #[serde(default = "enabled_by_default")]
     pub enabled: bool,
     pub stock_solution: String,  // "A", "B", "C", etc. or "Unassigned"
-    // macronutrients / major ions (mass fraction, g per g salt)
-    pub nh4: f64,
-    pub no3: f64,
-    pub p: f64,   // elemental P (not PO₄³⁻)
-    pub k: f64,
-    pub ca: f64,
-    pub mg: f64,
-    pub s: f64,   // elemental S (not SO₄²⁻)
-    pub cl: f64,
-    // micronutrients (mass fraction, g per g salt)
-    pub fe: f64,  // Iron
-    pub mn: f64,  // Manganese
-    pub zn: f64,  // Zinc
-    pub cu: f64,  // Copper
-    pub b: f64,   // Boron
-    pub mo: f64,  // Molybdenum
+    /// Mass fractions (g per g salt); P and S are elemental, not PO₄³⁻ / SO₄²⁻
+    pub composition: NutrientVector,
+    #[serde(default)]
+    pub kind: SaltKind,
+    /// Density (g/mL) of liquid products, which are dosed by volume
+    #[serde(default)]
+    pub density: Option<f64>,
+    /// Allowed amount in g/L of stock solution, summed over all tanks;
+    /// equal bounds fix the amount
+    #[serde(default = "Range::unbounded")]
+    pub dose: Range,
+    /// `None` if unknown; such salts count as free for the cost objective
+    #[serde(default)]
+    pub price: Option<Price>,
+    /// `None` if unknown; such salts do not count toward the tank's solubility limit
+    #[serde(default)]
+    pub solubility: Option<Solubility>,
+}
+
+fn enabled_by_default() -> bool {
+    true
 }
 
 impl Salt {
     /// Create a new predefined salt with default stock solution assignment
-    pub fn predefined(
-        name: &str,
-        formula: &str,
-        stock_solution: &str,
-        nh4: f64, no3: f64, p: f64, k: f64, ca: f64, mg: f64, s: f64, cl: f64,
-        fe: f64, mn: f64, zn: f64, cu: f64, b: f64, mo: f64,
-    ) -> Self {
+    pub fn predefined(name: &str, formula: &str, stock_solution: &str, composition: NutrientVector) -> Self {
         Salt {
             name: name.to_string(),
             formula: formula.to_string(),
             is_custom: false,
             enabled: true,
             stock_solution: stock_solution.to_string(),
-            nh4, no3, p, k, ca, mg, s, cl,
-            fe, mn, zn, cu, b, mo,
+            composition,
+            kind: SaltKind::Salt,
+            density: None,
+            dose: Range::unbounded(),
+            price: None,
+            solubility: None,
         }
     }
 
     /// Create a new custom salt
-    pub fn custom(
-        name: String,
-        formula: String,
-        nh4: f64, no3: f64, p: f64, k: f64, ca: f64, mg: f64, s: f64, cl: f64,
-        fe: f64, mn: f64, zn: f64, cu: f64, b: f64, mo: f64,
-    ) -> Self {
+    pub fn custom(name: String, formula: String, composition: NutrientVector) -> Self {
         Salt {
             name,
             formula,
             is_custom: true,
             enabled: true,
             stock_solution: "Unassigned".to_string(),
-            nh4, no3, p, k, ca, mg, s, cl,
-            fe, mn, zn, cu, b, mo,
+            composition,
+            kind: SaltKind::Salt,
+            density: None,
+            dose: Range::unbounded(),
+            price: None,
+            solubility: None,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: SaltKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_dose(mut self, dose: Range) -> Self {
+        self.dose = dose;
+        self
+    }
+
+    /// Whether the dose is fixed to a single amount
+    pub fn has_fixed_dose(&self) -> bool {
+        self.dose.min == self.dose.max
+    }
+
+    /// Marks the product as a liquid of the given density (g/mL)
+    pub fn liquid(mut self, density: f64) -> Self {
+        self.density = Some(density);
+        self
+    }
+
+    pub fn with_price(mut self, price: Price) -> Self {
+        self.price = Some(price);
+        self
+    }
+
+    pub fn with_solubility(mut self, solubility: Solubility) -> Self {
+        self.solubility = Some(solubility);
+        self
+    }
+}
+
+/// Whether a product is a neutral salt or adds free H⁺ or OH⁻
+///
+/// Acids and bases lack the counter-ion of their nutrient ion, so their
+/// compositions are deliberately unbalanced; they move the pH.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SaltKind {
+    #[default]
+    Salt,
+    Acid,
+    Base,
+}
+
+impl SaltKind {
+    pub const ALL: [SaltKind; 3] = [SaltKind::Salt, SaltKind::Acid, SaltKind::Base];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SaltKind::Salt => "Salz",
+            SaltKind::Acid => "Säure",
+            SaltKind::Base => "Lauge",
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Amount of one salt in one stock solution tank
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecipeEntry {
+    pub salt: String,
+    pub tank: String,
+    /// g per litre of the tank's stock solution
+    pub amount: f64,
+}
+
+/// Stored with the comparison, so every field has a serde default and
+/// conflicts of rules that no longer exist are left out
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct OptimizationResult {
-    pub recipe: Vec<(String, f64)>,
-    pub nh4_actual: f64,
-    pub no3_actual: f64,
-    pub k_actual: f64,
-    pub p_actual: f64,
-    pub ca_actual: f64,
-    pub mg_actual: f64,
-    pub s_actual: f64,
-    pub cl_actual: f64,
-    pub fe_actual: f64,
-    pub mn_actual: f64,
-    pub zn_actual: f64,
-    pub cu_actual: f64,
-    pub b_actual: f64,
-    pub mo_actual: f64,
-}
-
-#[derive(Debug, Clone)]
+    pub recipe: Vec<RecipeEntry>,
+    /// Nutrient concentrations of the recipe (g/L), including the source water,
+    /// as one stock solution at the reference dilution ratio
+    pub totals: NutrientVector,
+    /// Nutrient concentrations in the final solution after dilution (g/L)
+    pub final_totals: NutrientVector,
+    /// Bounds and targets the recipe misses; filled by the closest-recipe
+    /// objective, and with an EC limit the linearised bound could not keep
+    pub deviations: Vec<Deviation>,
+    pub cost: RecipeCost,
+    /// Share of each tank's solubility capacity used by the recipe (1.0 = saturated)
+    pub tank_loads: BTreeMap<String, f64>,
+    /// Precipitation rules the recipe breaks; empty if every tank can be mixed
+    #[serde(deserialize_with = "compatibility::known_conflicts")]
+    pub conflicts: Vec<Conflict>,
+    /// Estimated EC of the final solution (mS/cm)
+    pub ec: f64,
+    /// Cations and anions of the final solution (meq/L)
+    pub charge_balance: ChargeBalance,
+    /// Estimated pH of the final solution
+    pub ph: f64,
+}
+
+/// Purchase cost of a recipe
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecipeCost {
+    /// Cost of one litre of every stock solution, per currency
+    pub per_liter_stock: BTreeMap<String, f64>,
+    /// Cost for 1000 L of final nutrient solution, per currency
+    pub per_1000_l_final: BTreeMap<String, f64>,
+    /// Salts in the recipe without a price
+    pub unpriced_salts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComparisonEntry {
     pub result: OptimizationResult,
     pub timestamp: String,
+    /// Why the targets of this entry cannot be met; the result is then the closest recipe
+    #[serde(default)]
+    pub infeasible: Option<String>,
 }
-
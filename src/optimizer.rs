@@ -1,210 +1,1221 @@
-/// Linear programming optimizer for fertilizer recipes
+//! Linear programming optimizer for fertilizer recipes
 
-use anyhow::Result;
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
 use good_lp::*;
-use crate::models::{Salt, OptimizationResult};
+use serde::{Deserialize, Serialize};
+use crate::compatibility::{self, RULES};
+use crate::diagnosis::Unsolvable;
+use crate::charge::{self, ChargeBalance};
+use crate::ec;
+use crate::ph;
+use crate::models::{Nutrient, NutrientTargets, NutrientVector, Range, RecipeCost, RecipeEntry, Salt, Solubility, OptimizationResult, TargetBasis, Unit, WaterAnalysis};
 
-/// Optimizes fertilizer recipe using linear programming
-/// 
-/// # Arguments
-/// * `min_n`, `max_n` - Nitrogen range (g/L)
-/// * `nh4_ratio` - Ratio of NH4+ to total nitrogen (0.0 to 1.0)
-/// * `min_k`, `max_k` - Potassium range (g/L)
-/// * `min_p`, `max_p` - Phosphorus range (g/L)
-/// * `min_ca`, `max_ca` - Calcium range (g/L)
-/// * `min_mg`, `max_mg` - Magnesium range (g/L)
-/// * `min_s`, `max_s` - Sulfur range (g/L)
-/// * `min_cl`, `max_cl` - Chloride range (g/L)
-/// * Micronutrient ranges (mg/L): Fe, Mn, Zn, Cu, B, Mo
-/// * `is_fine_tuning` - If true, applies stricter chloride constraints
-/// * `salts` - Available salts with their nutrient compositions
-#[allow(clippy::too_many_arguments)]
-pub fn optimize_recipe(
-    min_n: f64, 
-    max_n: f64, 
-    nh4_ratio: f64, 
-    min_k: f64, 
-    max_k: f64, 
-    min_p: f64, 
-    max_p: f64, 
-    min_ca: f64, 
-    max_ca: f64, 
-    min_mg: f64, 
-    max_mg: f64, 
-    min_s: f64, 
-    max_s: f64, 
-    min_cl: f64, 
-    max_cl: f64,
-    min_fe: f64,
-    max_fe: f64,
-    min_mn: f64,
-    max_mn: f64,
-    min_zn: f64,
-    max_zn: f64,
-    min_cu: f64,
-    max_cu: f64,
-    min_b: f64,
-    max_b: f64,
-    min_mo: f64,
-    max_mo: f64,
-    is_fine_tuning: bool, 
-    salts: &[Salt]
-) -> Result<OptimizationResult> {
-    // Create variables using the good_lp API
-    let mut vars = variables!();
-    
-    // Add one variable for each salt (amount in g/L)
-    let salt_vars: Vec<Variable> = salts.iter()
-        .map(|_| vars.add(variable().min(0.0)))
-        .collect();
+/// Bound values below this are treated as zero when scaling deviations (g/L)
+const MIN_SCALE: f64 = 1e-3;
+
+/// Relative deviations below this are solver noise
+const DEVIATION_TOLERANCE: f64 = 1e-7;
+
+/// Weight of the total salt mass (per g/L) in objectives it does not drive, only breaks ties
+const TIE_BREAK_MASS_WEIGHT: f64 = 1e-6;
+
+/// No salt dissolves beyond this (g/L); caps amounts that no nutrient bound limits
+const MAX_SALT_AMOUNT: f64 = 2000.0;
+
+/// Weight of each used salt in objectives other than the salt count, only breaks ties
+const TIE_BREAK_SALT_WEIGHT: f64 = 1e-4;
+
+/// Ion balance and pH slacks are in meq/L; a typical solution carries this many per side
+const CHARGE_SCALE: f64 = 20.0;
+
+/// Relative EC overshoot accepted before the EC bound is re-linearised
+const EC_TOLERANCE: f64 = 0.005;
+
+/// Re-solves at most spent on bringing the estimated EC under its limit
+const EC_REFINEMENTS: usize = 3;
+
+/// What the optimizer minimises
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Objective {
+    /// Total salt mass; all nutrient bounds are hard constraints
+    #[default]
+    Mass,
+    /// Purchase cost from the salt prices; salts without price count as free
+    Cost,
+    /// Number of different salts in the recipe (mixed-integer)
+    SaltCount,
+    /// Weighted relative deviation from the targets; bounds become soft, so
+    /// the nearest achievable recipe is always returned
+    Deviation,
+}
+
+impl Objective {
+    pub const ALL: [Objective; 4] = [Objective::Mass, Objective::Cost, Objective::SaltCount, Objective::Deviation];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Objective::Mass => "Minimale Salzmasse",
+            Objective::Cost => "Minimale Kosten",
+            Objective::SaltCount => "Wenigste Salze",
+            Objective::Deviation => "Bestmögliche Annäherung",
+        }
+    }
+}
+
+/// Quantity a Pareto front trades off; each can be minimised or capped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Criterion {
+    /// Total salt mass (g per litre of stock solution)
+    Mass,
+    /// Purchase cost per litre of stock solution; salts without price count as free
+    Cost,
+    /// Concentration of one nutrient (g/L at the reference dilution), e.g. chloride
+    Nutrient(Nutrient),
+    /// Weighted relative deviation from the targets; makes the bounds soft
+    Deviation,
+}
+
+impl Criterion {
+    /// Every criterion in display order
+    pub fn all() -> Vec<Criterion> {
+        let mut criteria = vec![Criterion::Mass, Criterion::Cost, Criterion::Deviation];
+        criteria.extend(Nutrient::ALL.into_iter().map(Criterion::Nutrient));
+        criteria
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Criterion::Mass => "Salzmasse",
+            Criterion::Cost => "Kosten",
+            Criterion::Nutrient(nutrient) => nutrient.symbol(),
+            Criterion::Deviation => "Abweichung",
+        }
+    }
+
+    /// Converts a value in the solver's unit to the display unit
+    pub fn display_value(self, value: f64) -> f64 {
+        match self {
+            Criterion::Nutrient(nutrient) => nutrient.default_unit().from_grams_per_liter(nutrient, value),
+            Criterion::Mass | Criterion::Cost | Criterion::Deviation => value,
+        }
+    }
+
+    /// Display unit; costs are per litre of stock solution in the salts' currency
+    pub fn unit_label(self) -> &'static str {
+        match self {
+            Criterion::Mass => "g l⁻¹",
+            Criterion::Cost => "pro l Stammlösung",
+            Criterion::Nutrient(nutrient) => nutrient.default_unit().label(),
+            Criterion::Deviation => "",
+        }
+    }
+}
+
+/// Solver settings that are not nutrient targets
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OptimizationOptions {
+    /// If true, applies stricter chloride constraints
+    pub fine_tuning: bool,
+    pub objective: Objective,
+    /// Reference injector dilution of the stock solutions (1:x); final-solution
+    /// targets and the source water are scaled by it
+    pub dilution_ratio: f64,
+    /// Dilution ratios of single stock solutions that differ from the reference
+    pub tank_dilutions: BTreeMap<String, f64>,
+    /// Temperature of the stock solutions (°C), for the solubility limits
+    pub temperature: f64,
+    /// Allows dissolving a salt in other tanks than its own when one tank
+    /// cannot hold the full amount
+    pub split_salts: bool,
+    /// Lets the optimizer choose each salt's tank so that no precipitation
+    /// rule is broken (mixed-integer); the salts' own tanks are ignored
+    pub auto_assign_tanks: bool,
+    /// Largest accepted ion balance error of the final solution (share),
+    /// `None` leaves the balance unchecked
+    pub charge_balance_tolerance: Option<f64>,
+    /// Largest number of different salts in the recipe (mixed-integer),
+    /// `None` leaves it open
+    pub max_salts: Option<usize>,
+    /// Smallest amount of a salt (g) that can be weighed into one batch of a
+    /// stock solution; the optimizer uses at least this much or none (mixed-integer)
+    pub min_weighing: Option<f64>,
+    /// Litres of each stock solution mixed at once, for the minimum weighing amount
+    pub batch_volume: f64,
+}
+
+impl Default for OptimizationOptions {
+    fn default() -> Self {
+        OptimizationOptions {
+            fine_tuning: false,
+            objective: Objective::default(),
+            dilution_ratio: 100.0,
+            tank_dilutions: BTreeMap::new(),
+            temperature: Solubility::REFERENCE_TEMPERATURE,
+            split_salts: false,
+            auto_assign_tanks: false,
+            charge_balance_tolerance: None,
+            max_salts: None,
+            min_weighing: None,
+            batch_volume: 100.0,
+        }
+    }
+}
+
+impl OptimizationOptions {
+    /// Dilution ratio of a stock solution tank
+    pub fn dilution_for(&self, tank: &str) -> f64 {
+        self.tank_dilutions.get(tank).copied().unwrap_or(self.dilution_ratio)
+    }
+
+    /// Whether the model needs binary "salt used" indicators; recipes then
+    /// also prefer fewer salts among equally good ones
+    pub fn counts_salts(&self) -> bool {
+        self.objective == Objective::SaltCount || self.max_salts.is_some() || self.min_weighing.is_some()
+    }
+}
+
+/// A complete optimization problem: targets, available salts and options
+#[derive(Debug, Clone)]
+pub struct OptimizationProblem {
+    pub targets: NutrientTargets,
+    pub salts: Vec<Salt>,
+    pub options: OptimizationOptions,
+    /// Source water, diluted together with the stock solutions
+    pub water: WaterAnalysis,
+    /// Stock solution tanks salts may be split into besides their own
+    pub tanks: Vec<String>,
+    /// Ionic strength (mol/L) at which the EC bound is linearised; `None`
+    /// estimates it from the EC limit
+    pub(crate) ec_ionic_strength: Option<f64>,
+    /// Criterion minimised instead of the objective, for Pareto solves
+    pub(crate) criterion: Option<Criterion>,
+    /// Upper limits on criteria (the epsilon constraints of a Pareto front)
+    pub(crate) criterion_limits: Vec<(Criterion, f64)>,
+}
+
+impl OptimizationProblem {
+    pub fn new(targets: NutrientTargets) -> Self {
+        OptimizationProblem {
+            targets,
+            salts: Vec::new(),
+            options: OptimizationOptions::default(),
+            water: WaterAnalysis::pure(),
+            tanks: Vec::new(),
+            ec_ionic_strength: None,
+            criterion: None,
+            criterion_limits: Vec::new(),
+        }
+    }
+
+    /// Salts available to the optimizer; disabled salts are ignored when solving
+    pub fn salts(mut self, salts: impl IntoIterator<Item = Salt>) -> Self {
+        self.salts = salts.into_iter().collect();
+        self
+    }
+
+    pub fn options(mut self, options: OptimizationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn fine_tuning(mut self, fine_tuning: bool) -> Self {
+        self.options.fine_tuning = fine_tuning;
+        self
+    }
+
+    pub fn objective(mut self, objective: Objective) -> Self {
+        self.options.objective = objective;
+        self
+    }
+
+    pub fn water(mut self, water: WaterAnalysis) -> Self {
+        self.water = water;
+        self
+    }
+
+    pub fn tanks(mut self, tanks: impl IntoIterator<Item = String>) -> Self {
+        self.tanks = tanks.into_iter().collect();
+        self
+    }
+
+    pub fn solve(&self) -> Result<OptimizationResult> {
+        optimize_recipe(self)
+    }
+
+    pub(crate) fn enabled_salts(&self) -> Vec<&Salt> {
+        self.salts.iter().filter(|s| s.enabled).collect()
+    }
+
+    /// Units the problem's bounds are described in
+    pub fn display_units(&self) -> DisplayUnits {
+        DisplayUnits::of(&self.targets, self.options.dilution_ratio)
+    }
 
-    // Build nutrient expressions for all nutrients
-    let mut nh4_expr = Expression::from(0.0);
-    let mut no3_expr = Expression::from(0.0);
-    let mut k_expr = Expression::from(0.0);
-    let mut p_expr = Expression::from(0.0);
-    let mut ca_expr = Expression::from(0.0);
-    let mut mg_expr = Expression::from(0.0);
-    let mut s_expr = Expression::from(0.0);
-    let mut cl_expr = Expression::from(0.0);
-    let mut fe_expr = Expression::from(0.0);
-    let mut mn_expr = Expression::from(0.0);
-    let mut zn_expr = Expression::from(0.0);
-    let mut cu_expr = Expression::from(0.0);
-    let mut b_expr = Expression::from(0.0);
-    let mut mo_expr = Expression::from(0.0);
-    
-    for (i, salt) in salts.iter().enumerate() {
-        if salt.nh4 != 0.0 {
-            nh4_expr = nh4_expr + salt_vars[i] * salt.nh4;
+    /// The one currency of the enabled priced salts, `None` if none is priced;
+    /// prices in different currencies cannot be added up to a cost
+    pub fn currency(&self) -> Result<Option<String>> {
+        let mut currencies: Vec<&str> = self.enabled_salts().into_iter()
+            .filter_map(|salt| salt.price.as_ref())
+            .map(|price| price.currency.trim())
+            .collect();
+        currencies.sort_unstable();
+        currencies.dedup();
+        match currencies.as_slice() {
+            [] => Ok(None),
+            [currency] => Ok(Some(currency.to_string())),
+            _ => bail!(
+                "Die Preise sind in verschiedenen Währungen ({}) angegeben und lassen sich nicht zu Kosten zusammenzählen; bitte für alle aktiven Salze dieselbe Währung verwenden",
+                currencies.join(", ")
+            ),
         }
-        if salt.no3 != 0.0 {
-            no3_expr = no3_expr + salt_vars[i] * salt.no3;
+    }
+
+    /// Whether the objective, the criterion or a criterion limit adds up costs
+    pub(crate) fn uses_cost(&self) -> bool {
+        let minimised = match self.criterion {
+            Some(criterion) => criterion == Criterion::Cost,
+            None => self.options.objective == Objective::Cost,
+        };
+        minimised || self.criterion_limits.iter().any(|(c, _)| *c == Criterion::Cost)
+    }
+
+    /// Nutrients supplied by the source water, in g/L of stock solution
+    pub(crate) fn baseline(&self) -> NutrientVector {
+        self.water.stock_equivalent(self.options.dilution_ratio)
+    }
+
+    /// Factor turning g/L in a tank into g/L at the reference dilution
+    pub(crate) fn tank_scale(&self, tank: &str) -> f64 {
+        self.options.dilution_ratio / self.options.dilution_for(tank)
+    }
+
+    /// Tanks each salt may be dissolved in: its own, and with splitting every
+    /// other tank where it does not meet a precipitation partner; with
+    /// automatic assignment every tank
+    pub(crate) fn placements(&self, salts: &[&Salt]) -> Vec<Placement> {
+        let mut placements = Vec::new();
+        for (index, salt) in salts.iter().enumerate() {
+            if self.options.auto_assign_tanks && !self.tanks.is_empty() {
+                placements.extend(self.tanks.iter().map(|tank| Placement { salt: index, tank: tank.clone() }));
+                continue;
+            }
+            placements.push(Placement { salt: index, tank: salt.stock_solution.clone() });
+            if !self.options.split_salts {
+                continue;
+            }
+            for tank in self.tanks.iter().filter(|t| **t != salt.stock_solution) {
+                let precipitates = salts.iter()
+                    .filter(|other| other.stock_solution == *tank)
+                    .any(|other| !compatibility::compatible(salt, other));
+                if !precipitates {
+                    placements.push(Placement { salt: index, tank: tank.clone() });
+                }
+            }
         }
-        if salt.k != 0.0 {
-            k_expr = k_expr + salt_vars[i] * salt.k;
+        placements
+    }
+
+    /// All bounds the recipe has to satisfy
+    pub fn bounds(&self) -> Vec<Bound> {
+        self.collect_bounds(false)
+    }
+
+    /// Bounds used by the deviation objective: nutrients with a preferred
+    /// value are pinned to it, all others keep their range
+    pub fn deviation_bounds(&self) -> Vec<Bound> {
+        self.collect_bounds(true)
+    }
+
+    /// Relative importance of meeting a bound in the deviation objective
+    pub fn weight(&self, subject: BoundSubject) -> f64 {
+        match subject {
+            BoundSubject::Nutrient(nutrient) => self.targets.get(nutrient).weight_or_default(),
+            BoundSubject::Nitrogen | BoundSubject::Nh4Ratio | BoundSubject::Ec | BoundSubject::ChargeBalance | BoundSubject::Ph => 1.0,
+        }
+    }
+
+    fn collect_bounds(&self, pin_targets: bool) -> Vec<Bound> {
+        let targets = &self.targets;
+        let mut bounds = Vec::new();
+        let dilution_ratio = self.options.dilution_ratio;
+        let mut push_range = |subject: BoundSubject, range: Range| {
+            let Range { min, max } = range.map(|v| targets.basis.to_stock(v, dilution_ratio));
+            if min > 0.0 {
+                bounds.push(Bound { subject, side: BoundSide::Min, value: min });
+            }
+            if max.is_finite() {
+                bounds.push(Bound { subject, side: BoundSide::Max, value: max });
+            }
+        };
+
+        push_range(BoundSubject::Nitrogen, targets.nitrogen_g_per_l());
+        for (nutrient, target) in targets.iter() {
+            let mut range = target.range_g_per_l(nutrient);
+            // Apply stricter chloride constraint when fine-tuning
+            if nutrient == Nutrient::Cl && self.options.fine_tuning {
+                range.max *= 0.8;
+            }
+            if let Some(value) = target.target_g_per_l(nutrient).filter(|_| pin_targets) {
+                range = Range::exact(value);
+            }
+            push_range(BoundSubject::Nutrient(nutrient), range);
         }
-        if salt.p != 0.0 {
-            p_expr = p_expr + salt_vars[i] * salt.p;
+        if let Some(nh4_ratio) = targets.nh4_ratio {
+            bounds.push(Bound { subject: BoundSubject::Nh4Ratio, side: BoundSide::Min, value: nh4_ratio });
+            bounds.push(Bound { subject: BoundSubject::Nh4Ratio, side: BoundSide::Max, value: nh4_ratio });
         }
-        if salt.ca != 0.0 {
-            ca_expr = ca_expr + salt_vars[i] * salt.ca;
+        if let Some(ec_max) = targets.ec_max {
+            bounds.push(Bound { subject: BoundSubject::Ec, side: BoundSide::Max, value: ec_max });
         }
-        if salt.mg != 0.0 {
-            mg_expr = mg_expr + salt_vars[i] * salt.mg;
+        if targets.ph.min > 0.0 {
+            bounds.push(Bound { subject: BoundSubject::Ph, side: BoundSide::Min, value: targets.ph.min });
         }
-        if salt.s != 0.0 {
-            s_expr = s_expr + salt_vars[i] * salt.s;
+        if targets.ph.max.is_finite() {
+            bounds.push(Bound { subject: BoundSubject::Ph, side: BoundSide::Max, value: targets.ph.max });
         }
-        if salt.cl != 0.0 {
-            cl_expr = cl_expr + salt_vars[i] * salt.cl;
+        if let Some(tolerance) = self.options.charge_balance_tolerance {
+            bounds.push(Bound { subject: BoundSubject::ChargeBalance, side: BoundSide::Min, value: -tolerance });
+            bounds.push(Bound { subject: BoundSubject::ChargeBalance, side: BoundSide::Max, value: tolerance });
         }
-        if salt.fe != 0.0 {
-            fe_expr = fe_expr + salt_vars[i] * salt.fe;
+        bounds
+    }
+}
+
+/// Quantity constrained by a [`Bound`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoundSubject {
+    /// Total nitrogen (NH₄⁺ + NO₃⁻)
+    Nitrogen,
+    /// Share of NH₄⁺ in total nitrogen
+    Nh4Ratio,
+    Nutrient(Nutrient),
+    /// Electrical conductivity of the final solution
+    Ec,
+    /// Ion balance error (Σcations − Σanions) / (Σcations + Σanions)
+    ChargeBalance,
+    /// pH of the final solution
+    Ph,
+}
+
+impl BoundSubject {
+    pub fn label(self) -> &'static str {
+        match self {
+            BoundSubject::Nitrogen => "N",
+            BoundSubject::Nh4Ratio => "NH₄⁺-Anteil",
+            BoundSubject::Nutrient(nutrient) => nutrient.symbol(),
+            BoundSubject::Ec => "EC",
+            BoundSubject::ChargeBalance => "Ionenbilanz",
+            BoundSubject::Ph => "pH",
+        }
+    }
+
+    /// Converts a value in g/L of stock solution (share for the NH₄⁺ ratio and
+    /// ion balance, mS/cm for EC) to the basis and unit it is displayed in
+    pub fn display_value(self, value: f64, units: &DisplayUnits) -> f64 {
+        match self {
+            BoundSubject::Ec | BoundSubject::Ph => value,
+            BoundSubject::Nh4Ratio | BoundSubject::ChargeBalance => value * 100.0,
+            // Nitrogen is a sum of ion masses; mass units ignore the nutrient
+            BoundSubject::Nitrogen => units.nitrogen.from_grams_per_liter(Nutrient::No3, units.on_basis(value)),
+            BoundSubject::Nutrient(nutrient) => units.unit(nutrient).from_grams_per_liter(nutrient, units.on_basis(value)),
+        }
+    }
+
+    pub fn unit_label(self, units: &DisplayUnits) -> String {
+        match self {
+            BoundSubject::Nitrogen => units.nitrogen.label().to_string(),
+            BoundSubject::Nh4Ratio | BoundSubject::ChargeBalance => "%".to_string(),
+            BoundSubject::Nutrient(nutrient) => units.unit(nutrient).label_for(nutrient),
+            BoundSubject::Ec => "mS cm⁻¹".to_string(),
+            BoundSubject::Ph => String::new(),
         }
-        if salt.mn != 0.0 {
-            mn_expr = mn_expr + salt_vars[i] * salt.mn;
+    }
+
+    /// Display value with its unit, e.g. "15 g l⁻¹"
+    pub fn format_value(self, value: f64, units: &DisplayUnits) -> String {
+        let number = format_number(self.display_value(value, units));
+        match self.unit_label(units) {
+            unit if unit.is_empty() => number,
+            unit => format!("{} {}", number, unit),
         }
-        if salt.zn != 0.0 {
-            zn_expr = zn_expr + salt_vars[i] * salt.zn;
+    }
+
+    /// Value of the subject in a solution, in g/L (share for the NH₄⁺ ratio and ion balance, mS/cm for EC)
+    pub(crate) fn evaluate(self, solution: &impl Solution, exprs: &ModelExprs) -> f64 {
+        let nh4 = solution.eval(&exprs.nutrients[Nutrient::Nh4 as usize]);
+        let nitrogen = nh4 + solution.eval(&exprs.nutrients[Nutrient::No3 as usize]);
+        match self {
+            BoundSubject::Nitrogen => nitrogen,
+            BoundSubject::Nh4Ratio if nitrogen > 0.0 => nh4 / nitrogen,
+            BoundSubject::Nh4Ratio => 0.0,
+            BoundSubject::Nutrient(nutrient) => solution.eval(&exprs.nutrients[nutrient as usize]),
+            BoundSubject::Ec => solution.eval(&exprs.ec),
+            BoundSubject::ChargeBalance => {
+                let (cations, anions) = (solution.eval(&exprs.cations), solution.eval(&exprs.anions));
+                if cations + anions > 0.0 { (cations - anions) / (cations + anions) } else { 0.0 }
+            }
+            BoundSubject::Ph => {
+                let mut final_totals = NutrientVector::zero();
+                for nutrient in Nutrient::ALL {
+                    final_totals[nutrient] = solution.eval(&exprs.nutrients[nutrient as usize]) / exprs.dilution_ratio;
+                }
+                ph::estimate_ph(&final_totals)
+            }
         }
-        if salt.cu != 0.0 {
-            cu_expr = cu_expr + salt_vars[i] * salt.cu;
+    }
+}
+
+/// Basis and units bound values are shown in, those the targets are entered in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayUnits {
+    pub basis: TargetBasis,
+    /// Reference dilution of the stock solution, for final-solution values
+    pub dilution_ratio: f64,
+    /// Unit of total nitrogen
+    pub nitrogen: Unit,
+    /// Unit of each nutrient, indexed by `Nutrient as usize`
+    pub nutrients: [Unit; Nutrient::COUNT],
+}
+
+impl DisplayUnits {
+    pub fn of(targets: &NutrientTargets, dilution_ratio: f64) -> Self {
+        DisplayUnits {
+            basis: targets.basis,
+            dilution_ratio,
+            nitrogen: targets.nitrogen_unit(),
+            nutrients: Nutrient::ALL.map(|n| targets.get(n).unit),
         }
-        if salt.b != 0.0 {
-            b_expr = b_expr + salt_vars[i] * salt.b;
+    }
+
+    pub fn unit(&self, nutrient: Nutrient) -> Unit {
+        self.nutrients[nutrient as usize]
+    }
+
+    fn on_basis(&self, value: f64) -> f64 {
+        self.basis.from_stock(value, self.dilution_ratio)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoundSide {
+    Min,
+    Max,
+}
+
+/// A single lower or upper bound of the problem
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bound {
+    pub subject: BoundSubject,
+    pub side: BoundSide,
+    /// Limit in g/L, as a share (0.0 to 1.0) for the NH₄⁺ ratio, as a signed share
+    /// for the ion balance, or in mS/cm for EC
+    pub value: f64,
+}
+
+impl Bound {
+    /// Both sides of the linear constraint `lhs >= rhs` (min) or `lhs <= rhs` (max)
+    pub(crate) fn linear_form(&self, exprs: &ModelExprs) -> (Expression, f64) {
+        let nh4 = exprs.nutrients[Nutrient::Nh4 as usize].clone();
+        let nitrogen = nh4.clone() + exprs.nutrients[Nutrient::No3 as usize].clone();
+        match self.subject {
+            BoundSubject::Nitrogen => (nitrogen, self.value),
+            BoundSubject::Nh4Ratio => (nh4 - self.value * nitrogen, 0.0),
+            BoundSubject::Nutrient(nutrient) => (exprs.nutrients[nutrient as usize].clone(), self.value),
+            BoundSubject::Ec => (exprs.ec.clone(), self.value),
+            BoundSubject::ChargeBalance => {
+                let difference = exprs.cations.clone() - exprs.anions.clone();
+                let sum = exprs.cations.clone() + exprs.anions.clone();
+                (difference - self.value * sum, 0.0)
+            }
+            // The net charge at the bound's pH is positive below the actual pH
+            // and negative above it
+            BoundSubject::Ph => {
+                let coefficients = ph::charge_coefficients(self.value);
+                let charge = Nutrient::ALL.iter().fold(Expression::from(ph::free_charge(self.value)), |acc, &n| {
+                    acc + exprs.nutrients[n as usize].clone() * (coefficients[n] / exprs.dilution_ratio)
+                });
+                (charge, 0.0)
+            }
         }
-        if salt.mo != 0.0 {
-            mo_expr = mo_expr + salt_vars[i] * salt.mo;
+    }
+
+    pub(crate) fn constraint(&self, exprs: &ModelExprs) -> Constraint {
+        let (lhs, rhs) = self.linear_form(exprs);
+        match self.side {
+            BoundSide::Min => constraint!(lhs >= rhs),
+            BoundSide::Max => constraint!(lhs <= rhs),
         }
     }
 
-    // Objective: minimize total salt mass
-    let total_mass = salt_vars.iter()
-        .fold(Expression::from(0.0), |acc, &var| acc + var);
+    pub fn display_value(&self, units: &DisplayUnits) -> f64 {
+        self.subject.display_value(self.value, units)
+    }
 
-    // Apply stricter constraints when fine-tuning
-    let (adj_min_cl, adj_max_cl) = if is_fine_tuning {
-        (min_cl, max_cl * 0.8) // Reduce max chloride by 20% when fine-tuning
-    } else {
-        (min_cl, max_cl)
+    /// e.g. "K⁺ max 25 g l⁻¹"
+    pub fn describe(&self, units: &DisplayUnits) -> String {
+        let side = match self.side {
+            BoundSide::Min => "min",
+            BoundSide::Max => "max",
+        };
+        format!("{} {} {}", self.subject.label(), side, self.subject.format_value(self.value, units))
+    }
+}
+
+/// Formats a number with up to three decimals and without trailing zeros
+pub(crate) fn format_number(value: f64) -> String {
+    let text = format!("{:.3}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// A bound that may be violated at a cost, through a non-negative slack variable
+pub(crate) struct ElasticBound {
+    pub bound: Bound,
+    pub slack: Variable,
+    /// Slacks are divided by this so that g/L and mg/L bounds are comparable
+    pub scale: f64,
+}
+
+impl ElasticBound {
+    /// Adds one slack variable per bound
+    pub fn for_bounds(vars: &mut ProblemVariables, bounds: &[Bound]) -> Vec<ElasticBound> {
+        // The NH₄⁺ ratio constraint is expressed in g/L of NH₄⁺, so scale it by total nitrogen
+        let nitrogen_scale = bounds.iter()
+            .filter(|b| b.subject == BoundSubject::Nitrogen)
+            .map(|b| b.value)
+            .fold(MIN_SCALE, f64::max);
+        bounds.iter()
+            .map(|&bound| ElasticBound {
+                bound,
+                slack: vars.add(variable().min(0.0)),
+                scale: match bound.subject {
+                    BoundSubject::Nh4Ratio => nitrogen_scale,
+                    BoundSubject::ChargeBalance | BoundSubject::Ph => CHARGE_SCALE,
+                    _ => bound.value.abs().max(MIN_SCALE),
+                },
+            })
+            .collect()
+    }
+
+    /// Relative violation, the term minimised by elastic objectives
+    pub fn relative_slack(&self) -> Expression {
+        self.slack / self.scale
+    }
+
+    pub fn constraint(&self, exprs: &ModelExprs) -> Constraint {
+        let (lhs, rhs) = self.bound.linear_form(exprs);
+        let slack = self.slack;
+        match self.bound.side {
+            BoundSide::Min => constraint!(lhs + slack >= rhs),
+            BoundSide::Max => constraint!(lhs - slack <= rhs),
+        }
+    }
+
+    /// Whether the bound is violated in a solution beyond solver noise
+    pub fn is_violated(&self, solution: &impl Solution) -> bool {
+        solution.value(self.slack) / self.scale > DEVIATION_TOLERANCE
+    }
+}
+
+/// A bound or target the recipe misses
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Deviation {
+    pub bound: Bound,
+    /// Value reached by the recipe, in g/L (share for the NH₄⁺ ratio)
+    pub actual: f64,
+}
+
+impl Deviation {
+    /// Signed difference to the bound, in the subject's display unit
+    pub fn display_difference(&self, units: &DisplayUnits) -> f64 {
+        self.bound.subject.display_value(self.actual, units) - self.bound.display_value(units)
+    }
+
+    /// e.g. "K⁺ max 25 g l⁻¹: 27 g l⁻¹ (+2)"
+    pub fn describe(&self, units: &DisplayUnits) -> String {
+        let difference = self.display_difference(units);
+        let sign = if difference > 0.0 { "+" } else { "" };
+        format!(
+            "{}: {} ({}{})",
+            self.bound.describe(units),
+            self.bound.subject.format_value(self.actual, units),
+            sign,
+            format_number(difference)
+        )
+    }
+}
+
+/// LP expressions of the quantities bounds refer to
+pub(crate) struct ModelExprs {
+    /// Nutrient concentrations (g/L at the reference dilution), indexed by `Nutrient as usize`
+    pub nutrients: Vec<Expression>,
+    /// EC of the final solution (mS/cm), linearised at a fixed ionic strength
+    pub ec: Expression,
+    /// Cations and anions of the final solution (meq/L)
+    pub cations: Expression,
+    pub anions: Expression,
+    /// Converts `nutrients` to the final solution
+    pub dilution_ratio: f64,
+}
+
+/// A salt dissolved in one stock solution tank
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Placement {
+    /// Index into the enabled salts
+    pub salt: usize,
+    pub tank: String,
+}
+
+/// One amount variable per placed salt (g/L) and one expression per nutrient built from them
+pub(crate) struct SaltModel {
+    pub vars: ProblemVariables,
+    pub placements: Vec<Placement>,
+    /// Amount of each placement in g per litre of its tank's stock solution
+    pub salt_vars: Vec<Variable>,
+    pub exprs: ModelExprs,
+    /// Binary "salt used" indicator per salt; empty unless the options count salts
+    pub used_vars: Vec<Variable>,
+}
+
+impl SaltModel {
+    /// One variable per placement; the source water enters every nutrient expression as a constant
+    pub fn new(problem: &OptimizationProblem, salts: &[&Salt]) -> Self {
+        let mut vars = variables!();
+        let baseline = problem.baseline();
+        let placements = problem.placements(salts);
+
+        // Add one variable for each placement (amount in g/L)
+        let salt_vars: Vec<Variable> = placements.iter()
+            .map(|_| vars.add(variable().min(0.0)))
+            .collect();
+
+        // A tank diluted more strongly than the reference delivers less per g/L
+        let contributions: Vec<NutrientVector> = placements.iter()
+            .map(|p| salts[p.salt].composition.scaled(problem.tank_scale(&p.tank)))
+            .collect();
+
+        // Build one expression per nutrient, in the order of Nutrient::ALL
+        let nutrients: Vec<Expression> = Nutrient::ALL.iter()
+            .map(|&nutrient| {
+                contributions.iter().zip(&salt_vars)
+                    .filter(|(contribution, _)| contribution[nutrient] != 0.0)
+                    .fold(Expression::from(baseline[nutrient]), |acc, (contribution, &var)| acc + var * contribution[nutrient])
+            })
+            .collect();
+
+        // Activity coefficients at the expected ionic strength keep the EC linear
+        let ec_coefficients = match problem.ec_ionic_strength {
+            Some(ionic_strength) => ec::coefficients(ionic_strength),
+            None => ec::coefficients_near(problem.targets.ec_max.unwrap_or(0.0)),
+        };
+        let ec = Nutrient::ALL.iter()
+            .filter(|&&n| ec_coefficients[n] != 0.0)
+            .fold(Expression::from(0.0), |acc, &n| {
+                acc + nutrients[n as usize].clone() * (ec_coefficients[n] / problem.options.dilution_ratio)
+            });
+
+        let mut cations = Expression::from(0.0);
+        let mut anions = Expression::from(0.0);
+        for nutrient in Nutrient::ALL {
+            let meq = nutrients[nutrient as usize].clone() * (charge::meq_per_gram(nutrient) / problem.options.dilution_ratio);
+            match nutrient.charge() {
+                Some(z) if z > 0.0 => cations += meq,
+                Some(_) => anions += meq,
+                None => {}
+            }
+        }
+
+        let exprs = ModelExprs { nutrients, ec, cations, anions, dilution_ratio: problem.options.dilution_ratio };
+        SaltModel { vars, placements, salt_vars, exprs, used_vars: Vec::new() }
+    }
+
+    pub fn total_mass(&self) -> Expression {
+        self.salt_vars.iter().fold(Expression::from(0.0), |acc, &var| acc + var)
+    }
+
+    /// Purchase cost per litre of stock solution at the reference dilution; unpriced salts are free
+    pub fn total_cost(&self, problem: &OptimizationProblem, salts: &[&Salt]) -> Expression {
+        self.placements.iter().zip(&self.salt_vars)
+            .filter_map(|(p, &var)| {
+                salts[p.salt].price.as_ref()
+                    .map(|price| var * (price.per_kg / 1000.0 * problem.tank_scale(&p.tank)))
+            })
+            .fold(Expression::from(0.0), |acc, cost| acc + cost)
+    }
+
+    /// Largest possible amount of each placement (g/L) from its dose range and
+    /// solubility; the nutrient bounds are left out, as they are soft for the
+    /// deviation objective and in the diagnosis
+    pub fn amount_limits(&self, salts: &[&Salt], temperature: f64) -> Vec<f64> {
+        self.placements.iter()
+            .map(|p| {
+                let salt = salts[p.salt];
+                let soluble = salt.solubility.as_ref().map_or(f64::INFINITY, |s| s.at(temperature));
+                MAX_SALT_AMOUNT.min(salt.dose.max).min(soluble)
+            })
+            .collect()
+    }
+
+    /// Keeps the total amount of every salt within its dose range
+    pub fn dose_constraints(&self, salts: &[&Salt]) -> Vec<Constraint> {
+        let mut totals: BTreeMap<usize, Expression> = BTreeMap::new();
+        for (p, &var) in self.placements.iter().zip(&self.salt_vars) {
+            *totals.entry(p.salt).or_default() += var;
+        }
+        let mut constraints = Vec::new();
+        for (salt, total) in totals {
+            let dose = salts[salt].dose;
+            if dose.min > 0.0 {
+                constraints.push(constraint!(total.clone() >= dose.min));
+            }
+            if dose.max.is_finite() {
+                constraints.push(constraint!(total <= dose.max));
+            }
+        }
+        constraints
+    }
+
+    /// Share of the solubility capacity used in each tank; salts without a
+    /// known solubility are left out
+    pub fn tank_loads(&self, salts: &[&Salt], temperature: f64) -> BTreeMap<String, Expression> {
+        let mut loads: BTreeMap<String, Expression> = BTreeMap::new();
+        for (p, &var) in self.placements.iter().zip(&self.salt_vars) {
+            if let Some(solubility) = &salts[p.salt].solubility {
+                *loads.entry(p.tank.clone()).or_default() += var * (1.0 / solubility.at(temperature));
+            }
+        }
+        loads
+    }
+
+    /// Keeps the combined salt load of every tank within its solubility
+    pub fn solubility_constraints(&self, salts: &[&Salt], temperature: f64) -> Vec<Constraint> {
+        self.tank_loads(salts, temperature)
+            .into_values()
+            .map(|load| constraint!(load <= 1.0))
+            .collect()
+    }
+
+    /// Ties the amounts to the "salt used" indicators: an unused salt stays
+    /// out of every tank, a used one is weighed in at least the minimum amount
+    /// per tank, and at most `max_salts` salts are used
+    pub fn usage_constraints(&mut self, problem: &OptimizationProblem, salts: &[&Salt]) -> Vec<Constraint> {
+        if !problem.options.counts_salts() {
+            return Vec::new();
+        }
+        let limits = self.amount_limits(salts, problem.options.temperature);
+        self.used_vars = salts.iter().map(|_| self.vars.add(variable().binary())).collect();
+        let min_amount = problem.options.min_weighing.map_or(0.0, |g| g / problem.options.batch_volume);
+
+        let mut constraints = Vec::new();
+        for ((p, &var), limit) in self.placements.iter().zip(&self.salt_vars).zip(limits) {
+            let used = self.used_vars[p.salt];
+            // A salt split over several tanks is weighed separately into each
+            let weighed = if min_amount > 0.0 && self.placements.iter().filter(|q| q.salt == p.salt).count() > 1 {
+                let weighed = self.vars.add(variable().binary());
+                constraints.push(constraint!(weighed <= used));
+                weighed
+            } else {
+                used
+            };
+            constraints.push(constraint!(var <= limit * weighed));
+            if min_amount > 0.0 {
+                constraints.push(constraint!(var >= min_amount * weighed));
+            }
+        }
+        if let Some(max_salts) = problem.options.max_salts {
+            let count: Expression = self.used_vars.iter().sum();
+            constraints.push(constraint!(count <= max_salts as f64));
+        }
+        constraints
+    }
+
+    /// Constraints that hold regardless of the nutrient bounds: dose ranges,
+    /// solubility, salt usage, and with automatic tank assignment the precipitation rules and one
+    /// tank per salt unless splitting is allowed
+    pub fn salt_constraints(&mut self, problem: &OptimizationProblem, salts: &[&Salt]) -> Vec<Constraint> {
+        let mut constraints = self.dose_constraints(salts);
+        constraints.extend(self.solubility_constraints(salts, problem.options.temperature));
+        constraints.extend(self.usage_constraints(problem, salts));
+        if !problem.options.auto_assign_tanks {
+            return constraints;
+        }
+
+        let limits = self.amount_limits(salts, problem.options.temperature);
+        let mut tanks: Vec<&str> = self.placements.iter().map(|p| p.tank.as_str()).collect();
+        tanks.sort_unstable();
+        tanks.dedup();
+        for tank in tanks {
+            let in_tank = || self.placements.iter().zip(&self.salt_vars).zip(&limits).filter(|((p, _), _)| p.tank == tank);
+            // Summed tank expression of a per-gram quantity, with its largest
+            // value; None if no salt in the tank contributes
+            let sum = |per_gram: &dyn Fn(&Salt) -> f64| {
+                let terms: Vec<(Expression, f64)> = in_tank()
+                    .filter(|((p, _), _)| per_gram(salts[p.salt]) != 0.0)
+                    .map(|((p, &var), &limit)| (var * per_gram(salts[p.salt]), limit * per_gram(salts[p.salt]).max(0.0)))
+                    .collect();
+                (!terms.is_empty()).then(|| terms.into_iter().fold((Expression::from(0.0), 0.0), |(e, m), (term, max)| (e + term, m + max)))
+            };
+            for rule in RULES.iter() {
+                // Tank concentrations (mmol/L), not scaled by the dilution
+                let (Some((first, first_max)), Some((second, second_max))) =
+                    (sum(&|salt| rule.first.millimoles(salt)), sum(&|salt| rule.second.millimoles(salt))) else {
+                    continue;
+                };
+                // The tank stays in one box below the product limit, or for a
+                // pH-dependent rule at or below that pH; the largest summed
+                // concentrations make each unchosen limit inactive
+                let mut choices = Vec::new();
+                for (first_limit, second_limit) in rule.steps() {
+                    let chosen = self.vars.add(variable().binary());
+                    if first_limit < first_max {
+                        constraints.push(constraint!(first.clone() <= first_limit + first_max * (1.0 - chosen)));
+                    }
+                    if second_limit < second_max {
+                        constraints.push(constraint!(second.clone() <= second_limit + second_max * (1.0 - chosen)));
+                    }
+                    choices.push(chosen);
+                }
+                if let Some(ph) = rule.above_ph {
+                    // Net charge at the limit is at most 0 exactly when the tank pH is at most the limit
+                    let coefficients = ph::charge_coefficients(ph);
+                    let per_gram = |salt: &Salt| salt.composition.iter().map(|(n, fraction)| fraction * coefficients[n]).sum::<f64>();
+                    if let Some((charge, charge_max)) = sum(&per_gram) {
+                        let chosen = self.vars.add(variable().binary());
+                        let free = ph::free_charge(ph);
+                        constraints.push(constraint!(charge + free <= (charge_max + free.max(0.0)) * (1.0 - chosen)));
+                        choices.push(chosen);
+                    }
+                }
+                let chosen: Expression = choices.into_iter().sum();
+                constraints.push(constraint!(chosen >= 1.0));
+            }
+        }
+
+        if !problem.options.split_salts {
+            let mut per_salt: BTreeMap<usize, Expression> = BTreeMap::new();
+            for ((p, &var), limit) in self.placements.iter().zip(&self.salt_vars).zip(limits) {
+                let in_tank = self.vars.add(variable().binary());
+                constraints.push(constraint!(var <= limit * in_tank));
+                *per_salt.entry(p.salt).or_default() += in_tank;
+            }
+            constraints.extend(per_salt.into_values().map(|tanks| constraint!(tanks <= 1.0)));
+        }
+        constraints
+    }
+}
+
+/// Optimizes fertilizer recipe using linear programming
+///
+/// With [`Objective::Mass`], [`Objective::Cost`] and [`Objective::SaltCount`]
+/// the nutrient ranges in `problem.targets` are hard constraints; if they
+/// cannot be met, the error is [`Unsolvable`], and
+/// [`diagnose`](crate::diagnosis::diagnose) names the conflicting bounds. [`Objective::Deviation`] minimises the
+/// weighted relative deviation from the targets instead and always yields a
+/// recipe. Only enabled salts are considered; minimising the cost requires
+/// their prices to share one currency. A recipe whose estimated EC stays
+/// above the limit after the refinement passes lists it in its deviations.
+pub fn optimize_recipe(problem: &OptimizationProblem) -> Result<OptimizationResult> {
+    if problem.uses_cost() {
+        problem.currency()?;
+    }
+    let mut result = solve_recipe(problem)?;
+    let Some(ec_max) = problem.targets.ec_max else {
+        return Ok(result);
     };
 
-    // Create and solve the model with all nutrient constraints
-    let total_n_expr = nh4_expr.clone() + no3_expr.clone();
-    let solution = vars.minimise(total_mass)
-        .using(microlp)
-        // Macronutrient constraints
-        .with(constraint!(total_n_expr.clone() >= min_n))
-        .with(constraint!(total_n_expr.clone() <= max_n))
-        .with(constraint!(nh4_expr.clone() == nh4_ratio * total_n_expr.clone()))
-        .with(constraint!(k_expr.clone() >= min_k))
-        .with(constraint!(k_expr.clone() <= max_k))
-        .with(constraint!(p_expr.clone() >= min_p))
-        .with(constraint!(p_expr.clone() <= max_p))
-        .with(constraint!(ca_expr.clone() >= min_ca))
-        .with(constraint!(ca_expr.clone() <= max_ca))
-        .with(constraint!(mg_expr.clone() >= min_mg))
-        .with(constraint!(mg_expr.clone() <= max_mg))
-        .with(constraint!(s_expr.clone() >= min_s))
-        .with(constraint!(s_expr.clone() <= max_s))
-        .with(constraint!(cl_expr.clone() >= adj_min_cl))
-        .with(constraint!(cl_expr.clone() <= adj_max_cl))
-        // Micronutrient constraints (converted to g/L for consistency)
-        .with(constraint!(fe_expr.clone() >= min_fe / 1000.0))
-        .with(constraint!(fe_expr.clone() <= max_fe / 1000.0))
-        .with(constraint!(mn_expr.clone() >= min_mn / 1000.0))
-        .with(constraint!(mn_expr.clone() <= max_mn / 1000.0))
-        .with(constraint!(zn_expr.clone() >= min_zn / 1000.0))
-        .with(constraint!(zn_expr.clone() <= max_zn / 1000.0))
-        .with(constraint!(cu_expr.clone() >= min_cu / 1000.0))
-        .with(constraint!(cu_expr.clone() <= max_cu / 1000.0))
-        .with(constraint!(b_expr.clone() >= min_b / 1000.0))
-        .with(constraint!(b_expr.clone() <= max_b / 1000.0))
-        .with(constraint!(mo_expr.clone() >= min_mo / 1000.0))
-        .with(constraint!(mo_expr.clone() <= max_mo / 1000.0))
-        .solve()?;
+    // The EC bound is linearised at a guessed ionic strength; re-solve at the
+    // ionic strength of the previous recipe until the estimate stays within the limit
+    let mut problem = problem.clone();
+    for _ in 0..EC_REFINEMENTS {
+        if result.ec <= ec_max * (1.0 + EC_TOLERANCE) {
+            break;
+        }
+        problem.ec_ionic_strength = Some(ec::ionic_strength(&result.final_totals));
+        result = solve_recipe(&problem)?;
+    }
+    // Still above: report it like a missed bound rather than as a valid recipe
+    let reported = result.deviations.iter().any(|d| d.bound.subject == BoundSubject::Ec);
+    if result.ec > ec_max * (1.0 + EC_TOLERANCE) && !reported {
+        let bound = Bound { subject: BoundSubject::Ec, side: BoundSide::Max, value: ec_max };
+        result.deviations.push(Deviation { bound, actual: result.ec });
+    }
+    Ok(result)
+}
+
+fn solve_recipe(problem: &OptimizationProblem) -> Result<OptimizationResult> {
+    match solve_with_bounds(problem, &problem.bounds()) {
+        Err(ResolutionError::Infeasible) => Err(Unsolvable.into()),
+        result => Ok(result?),
+    }
+}
+
+/// Solves against the given hard bounds instead of the problem's own, without
+/// diagnosing infeasibility; the deviation objective still uses the problem's targets
+pub(crate) fn solve_with_bounds(problem: &OptimizationProblem, bounds: &[Bound]) -> Result<OptimizationResult, ResolutionError> {
+    solve_criteria(problem, bounds, &[]).map(|(result, _)| result)
+}
+
+/// Like [`solve_with_bounds`], also returning the value of each given criterion
+pub(crate) fn solve_criteria(problem: &OptimizationProblem, bounds: &[Bound], criteria: &[Criterion]) -> Result<(OptimizationResult, Vec<f64>), ResolutionError> {
+    let salts = problem.enabled_salts();
+    let mut salt_model = SaltModel::new(problem, &salts);
+    let total_mass = salt_model.total_mass();
+    let total_cost = salt_model.total_cost(problem, &salts);
+    let salt_constraints = salt_model.salt_constraints(problem, &salts);
+    let tank_loads = salt_model.tank_loads(&salts, problem.options.temperature);
+    let SaltModel { mut vars, placements, salt_vars, exprs, used_vars, .. } = salt_model;
+    let salt_count: Expression = used_vars.iter().sum();
+
+    // Bounds are soft when the deviation is minimised or capped
+    let soft_bounds = match problem.criterion {
+        Some(criterion) => criterion == Criterion::Deviation,
+        None => problem.options.objective == Objective::Deviation,
+    } || problem.criterion_limits.iter().any(|(c, _)| *c == Criterion::Deviation);
+    let elastic = match soft_bounds {
+        true => ElasticBound::for_bounds(&mut vars, &problem.deviation_bounds()),
+        false => Vec::new(),
+    };
+    let deviation = elastic.iter().fold(Expression::from(0.0), |acc, e| {
+        acc + problem.weight(e.bound.subject) * e.relative_slack()
+    });
+    let criterion_expr = |criterion: Criterion| match criterion {
+        Criterion::Mass => total_mass.clone(),
+        Criterion::Cost => total_cost.clone(),
+        Criterion::Nutrient(nutrient) => exprs.nutrients[nutrient as usize].clone(),
+        Criterion::Deviation => deviation.clone(),
+    };
+
+    // Fewer salts break ties wherever salts are counted
+    let tie_break = total_mass.clone() * TIE_BREAK_MASS_WEIGHT + salt_count.clone() * TIE_BREAK_SALT_WEIGHT;
+    let objective = match (problem.criterion, problem.options.objective) {
+        (Some(criterion), _) => criterion_expr(criterion) + tie_break,
+        (None, Objective::Mass) => total_mass.clone() + salt_count * TIE_BREAK_SALT_WEIGHT,
+        (None, Objective::Cost) => total_cost.clone() + tie_break,
+        (None, Objective::SaltCount) => salt_count + total_mass.clone() * TIE_BREAK_MASS_WEIGHT,
+        // Minimize weighted deviation, preferring lighter recipes among equally close ones
+        (None, Objective::Deviation) => deviation.clone() + tie_break,
+    };
+
+    let mut model = vars.minimise(objective).using(microlp);
+    for &(criterion, limit) in &problem.criterion_limits {
+        model.add_constraint(constraint!(criterion_expr(criterion) <= limit));
+    }
+    if soft_bounds {
+        for e in &elastic {
+            model.add_constraint(e.constraint(&exprs));
+        }
+    } else {
+        for bound in bounds {
+            model.add_constraint(bound.constraint(&exprs));
+        }
+    }
+    for constraint in salt_constraints {
+        model.add_constraint(constraint);
+    }
+
+    let solution = model.solve()?;
 
     // Collect results
     let mut recipe = Vec::new();
-    for (i, salt) in salts.iter().enumerate() {
-        let qty = solution.value(salt_vars[i]);
+    for (p, &var) in placements.iter().zip(&salt_vars) {
+        let qty = solution.value(var);
         if qty > 1e-6 {
-            recipe.push((salt.name.to_string(), qty));
-        }
-    }
-
-    let nh4_actual = solution.eval(&nh4_expr);
-    let no3_actual = solution.eval(&no3_expr);
-    let k_actual = solution.eval(&k_expr);
-    let p_actual = solution.eval(&p_expr);
-    let ca_actual = solution.eval(&ca_expr);
-    let mg_actual = solution.eval(&mg_expr);
-    let s_actual = solution.eval(&s_expr);
-    let cl_actual = solution.eval(&cl_expr);
-    let fe_actual = solution.eval(&fe_expr) * 1000.0; // Convert to mg/L
-    let mn_actual = solution.eval(&mn_expr) * 1000.0;
-    let zn_actual = solution.eval(&zn_expr) * 1000.0;
-    let cu_actual = solution.eval(&cu_expr) * 1000.0;
-    let b_actual = solution.eval(&b_expr) * 1000.0;
-    let mo_actual = solution.eval(&mo_expr) * 1000.0;
-
-    Ok(OptimizationResult {
-        recipe,
-        nh4_actual,
-        no3_actual,
-        k_actual,
-        p_actual,
-        ca_actual,
-        mg_actual,
-        s_actual,
-        cl_actual,
-        fe_actual,
-        mn_actual,
-        zn_actual,
-        cu_actual,
-        b_actual,
-        mo_actual,
-    })
+            recipe.push(RecipeEntry { salt: salts[p.salt].name.clone(), tank: p.tank.clone(), amount: qty });
+        }
+    }
+
+    let mut totals = NutrientVector::zero();
+    for nutrient in Nutrient::ALL {
+        totals[nutrient] = solution.eval(&exprs.nutrients[nutrient as usize]);
+    }
+
+    let deviations = elastic.iter()
+        .filter(|e| e.is_violated(&solution))
+        .map(|e| Deviation {
+            bound: e.bound,
+            actual: e.bound.subject.evaluate(&solution, &exprs),
+        })
+        .collect();
+
+    let final_totals = totals.scaled(1.0 / problem.options.dilution_ratio);
+    let cost = recipe_cost(&recipe, &salts, &problem.options);
+    let tank_loads = tank_loads.into_iter()
+        .map(|(tank, load)| (tank, solution.eval(&load)))
+        .collect();
+    let conflicts = compatibility::recipe_conflicts(&recipe, &salts);
+    let ec = ec::estimate_ec(&final_totals);
+    let charge_balance = ChargeBalance::of(&final_totals);
+    let ph = ph::estimate_ph(&final_totals);
+
+    let values = criteria.iter().map(|&c| solution.eval(criterion_expr(c))).collect();
+
+    Ok((OptimizationResult { recipe, totals, final_totals, deviations, cost, tank_loads, conflicts, ec, charge_balance, ph }, values))
+}
+
+/// Cost of a recipe per litre of every stock solution and per 1000 L of final solution
+pub fn recipe_cost(recipe: &[RecipeEntry], salts: &[&Salt], options: &OptimizationOptions) -> RecipeCost {
+    let mut cost = RecipeCost::default();
+    for entry in recipe {
+        let Some(salt) = salts.iter().find(|s| s.name == entry.salt) else {
+            continue;
+        };
+        match &salt.price {
+            Some(price) => {
+                let per_liter = entry.amount / 1000.0 * price.per_kg;
+                *cost.per_liter_stock.entry(price.currency.clone()).or_default() += per_liter;
+                // 1000 L of final solution take 1000 / dilution litres of the salt's tank
+                *cost.per_1000_l_final.entry(price.currency.clone()).or_default() +=
+                    per_liter * 1000.0 / options.dilution_for(&entry.tank);
+            }
+            None if !cost.unpriced_salts.contains(&entry.salt) => cost.unpriced_salts.push(entry.salt.clone()),
+            None => {}
+        }
+    }
+    cost
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula;
+    use crate::models::{NutrientTarget, Price, UnitSystem};
+
+    fn potassium_nitrate() -> Salt {
+        let mut salt = Salt::custom("KNO₃".to_string(), "KNO3".to_string(), formula::composition("KNO3", 1.0).unwrap());
+        salt.stock_solution = "A".to_string();
+        salt
+    }
+
+    #[test]
+    fn bounds_display_in_the_targets_basis_and_units() {
+        let stock = OptimizationProblem::new(NutrientTargets::unconstrained());
+        let potassium = Bound { subject: BoundSubject::Nutrient(Nutrient::K), side: BoundSide::Max, value: 25.0 };
+        assert_eq!(potassium.describe(&stock.display_units()), "K⁺ max 25 g l⁻¹");
+
+        // 25 g/L in the stock solution are 250 mg/L or 6.394 mmol/L after 1:100
+        let mut targets = NutrientTargets::unconstrained();
+        targets.basis = TargetBasis::Final;
+        targets.set(Nutrient::K, NutrientTarget { unit: Unit::MillimolesPerLiter, ..NutrientTarget::unbounded(Nutrient::K) });
+        let units = DisplayUnits::of(&targets, 100.0);
+        assert_eq!(potassium.describe(&units), "K⁺ max 6.394 mmol l⁻¹");
+        let nitrogen = Bound { subject: BoundSubject::Nitrogen, side: BoundSide::Min, value: 14.0 };
+        assert_eq!(nitrogen.describe(&units), "N min 140 mg l⁻¹");
+        let deviation = Deviation { bound: potassium, actual: 27.0 };
+        assert_eq!(deviation.describe(&units), "K⁺ max 6.394 mmol l⁻¹: 6.906 mmol l⁻¹ (+0.512)");
+    }
+
+    #[test]
+    fn bounds_display_as_the_entered_targets() {
+        for system in UnitSystem::ALL {
+            let targets = NutrientTargets::default()
+                .rebase(TargetBasis::Final, |n| system.unit_for(n, TargetBasis::Final), 100.0);
+            let problem = OptimizationProblem::new(targets.clone());
+            let units = problem.display_units();
+            for bound in problem.bounds() {
+                let range = match bound.subject {
+                    BoundSubject::Nitrogen => targets.nitrogen,
+                    BoundSubject::Nutrient(nutrient) => targets.get(nutrient).range,
+                    _ => continue,
+                };
+                let entered = match bound.side {
+                    BoundSide::Min => range.min,
+                    BoundSide::Max => range.max,
+                };
+                let shown = bound.display_value(&units);
+                assert!((shown - entered).abs() <= 1e-9 * entered.abs().max(1.0), "{:?}: {} vs {}", system, shown, entered);
+            }
+        }
+    }
+
+    #[test]
+    fn cost_objective_rejects_mixed_currencies() {
+        let targets = NutrientTargets::unconstrained().with_range(Nutrient::No3, 50.0, f64::INFINITY);
+        let mut cheap = potassium_nitrate().with_price(Price::new(1.5, "EUR"));
+        cheap.name = "KNO₃ (EUR)".to_string();
+        let expensive = potassium_nitrate().with_price(Price::new(2.0, "CHF"));
+        let problem = OptimizationProblem::new(targets)
+            .salts([cheap, expensive])
+            .tanks(["A".to_string()]);
+
+        assert!(problem.currency().is_err());
+        assert!(problem.clone().objective(Objective::Cost).solve().is_err());
+        // Without the cost objective each currency is summed on its own
+        assert!(problem.objective(Objective::Mass).solve().is_ok());
+    }
+
+    #[test]
+    fn deviation_with_max_salts_may_exceed_a_max_bound() {
+        // 50 g/L NO₃⁻ from KNO₃ brings about 32 g/L K⁺, far over the K⁺ maximum
+        let targets = NutrientTargets::unconstrained()
+            .with_range(Nutrient::K, 0.0, 10.0)
+            .with_range(Nutrient::No3, 50.0, f64::INFINITY)
+            .with_weight(Nutrient::No3, 10.0);
+        let options = OptimizationOptions { objective: Objective::Deviation, max_salts: Some(3), ..Default::default() };
+        let result = OptimizationProblem::new(targets)
+            .salts([potassium_nitrate()])
+            .options(options)
+            .tanks(["A".to_string()])
+            .solve()
+            .unwrap();
+
+        assert!(result.totals[Nutrient::No3] > 49.0, "NO₃⁻ {}", result.totals[Nutrient::No3]);
+        assert!(result.totals[Nutrient::K] > 10.0, "K⁺ {}", result.totals[Nutrient::K]);
+        assert!(result.deviations.iter().any(|d| d.bound.subject == BoundSubject::Nutrient(Nutrient::K)));
+    }
+
+    #[test]
+    fn tight_ec_limit_is_kept_or_reported() {
+        let problem = |ec_max: f64, objective: Objective| {
+            let mut targets = NutrientTargets::default();
+            targets.ec_max = Some(ec_max);
+            let options = OptimizationOptions { objective, ..Default::default() };
+            OptimizationProblem::new(targets)
+                .salts(crate::data::get_predefined_salts())
+                .options(options)
+                .tanks(["A".to_string(), "B".to_string()])
+        };
+        let ec_deviations = |result: &OptimizationResult| {
+            result.deviations.iter().filter(|d| d.bound.subject == BoundSubject::Ec).count()
+        };
+
+        for ec_max in [2.2, 2.24, 2.28, 2.32] {
+            match problem(ec_max, Objective::Mass).solve() {
+                Ok(result) => assert!(
+                    result.ec <= ec_max * (1.0 + EC_TOLERANCE) || ec_deviations(&result) == 1,
+                    "EC {} over {} without a deviation", result.ec, ec_max
+                ),
+                Err(e) => assert!(e.is::<crate::diagnosis::Unsolvable>()),
+            }
+        }
+
+        // Far too tight for the targets: the closest recipe misses the EC limit once
+        let result = problem(1.0, Objective::Deviation).solve().unwrap();
+        assert!(result.ec > 1.0);
+        assert_eq!(ec_deviations(&result), 1);
+    }
+
+    #[test]
+    fn auto_assignment_separates_calcium_from_sulfate() {
+        let salt = |formula: &str| {
+            let mut salt = Salt::custom(formula.to_string(), formula.to_string(), formula::composition(formula, 1.0).unwrap());
+            salt.stock_solution = "A".to_string();
+            salt
+        };
+        let targets = NutrientTargets::unconstrained()
+            .with_range(Nutrient::Ca, 10.0, f64::INFINITY)
+            .with_range(Nutrient::S, 10.0, f64::INFINITY);
+        let options = OptimizationOptions { auto_assign_tanks: true, ..Default::default() };
+        let result = OptimizationProblem::new(targets)
+            .salts([salt("Ca(NO3)2·4H2O"), salt("K2SO4")])
+            .options(options)
+            .tanks(["A".to_string(), "B".to_string()])
+            .solve()
+            .unwrap();
+
+        assert!(result.conflicts.is_empty(), "{:?}", result.conflicts);
+        assert_eq!(result.recipe.len(), 2);
+        assert_ne!(result.recipe[0].tank, result.recipe[1].tank);
+    }
+}
@@ -0,0 +1,138 @@
+//! Pareto fronts between two criteria, e.g. cost and chloride
+//!
+//! Epsilon-constraint method: the second criterion is capped at evenly spaced
+//! values between its own minimum and its value at the minimum of the first,
+//! and the first criterion is minimised under each cap. Recipes that another
+//! one beats in both criteria are dropped, as are duplicates.
+
+use anyhow::{bail, Result};
+use good_lp::ResolutionError;
+
+use crate::diagnosis::Unsolvable;
+use crate::ec;
+use crate::models::OptimizationResult;
+use crate::optimizer::{solve_criteria, Criterion, OptimizationProblem};
+
+/// Most caps a front may be computed with
+pub const MAX_POINTS: usize = 51;
+
+/// Relative difference below which two criterion values count as equal
+const VALUE_TOLERANCE: f64 = 1e-6;
+
+/// A recipe on the front with its values of both criteria (solver units)
+#[derive(Debug, Clone)]
+pub struct ParetoPoint {
+    pub first: f64,
+    pub second: f64,
+    pub result: OptimizationResult,
+}
+
+/// Non-dominated recipes for two criteria, sorted by the second criterion
+pub fn pareto_front(problem: &OptimizationProblem, first: Criterion, second: Criterion, points: usize) -> Result<Vec<ParetoPoint>> {
+    if first == second {
+        bail!("Bitte zwei verschiedene Kriterien wählen");
+    }
+    if first == Criterion::Cost || second == Criterion::Cost {
+        problem.currency()?;
+    }
+    if !(2..=MAX_POINTS).contains(&points) {
+        bail!("Die Anzahl der Punkte muss zwischen 2 und {} liegen", MAX_POINTS);
+    }
+
+    // Fix the EC linearisation at the problem's own optimum
+    let mut base = problem.clone();
+    if base.targets.ec_max.is_some() {
+        if let Ok(optimal) = problem.solve() {
+            base.ec_ionic_strength = Some(ec::ionic_strength(&optimal.final_totals));
+        }
+    }
+    let bounds = base.bounds();
+    let solve = |criterion: Criterion, cap: Option<f64>| {
+        let mut capped = base.clone();
+        capped.criterion = Some(criterion);
+        capped.criterion_limits = cap.map(|limit| vec![(second, limit)]).unwrap_or_default();
+        solve_criteria(&capped, &bounds, &[first, second]).map(|(result, values)| ParetoPoint {
+            first: values[0],
+            second: values[1],
+            result,
+        })
+    };
+
+    let best_first = solve(first, None).map_err(|e| match e {
+        ResolutionError::Infeasible => Unsolvable.into(),
+        e => anyhow::Error::from(e),
+    })?;
+    let best_second = solve(second, None)?;
+
+    let (low, high) = (best_second.second, best_first.second);
+    let mut front: Vec<ParetoPoint> = (0..points)
+        .filter_map(|i| {
+            let cap = low + (high - low) * i as f64 / (points - 1) as f64;
+            // A little headroom so that the cap at the minimum stays solvable
+            solve(first, Some(cap + (high - low).abs() * VALUE_TOLERANCE + VALUE_TOLERANCE)).ok()
+        })
+        .collect();
+    front.push(best_first);
+
+    front.sort_by(|a, b| a.second.total_cmp(&b.second).then(a.first.total_cmp(&b.first)));
+    let dominated = |point: &ParetoPoint, other: &ParetoPoint| {
+        let no_worse = other.first <= point.first + tolerance(point.first) && other.second <= point.second + tolerance(point.second);
+        let better = other.first < point.first - tolerance(point.first) || other.second < point.second - tolerance(point.second);
+        no_worse && better
+    };
+    let mut kept: Vec<ParetoPoint> = Vec::new();
+    for point in front.iter() {
+        let duplicate = kept.iter().any(|k| {
+            (k.first - point.first).abs() <= tolerance(point.first) && (k.second - point.second).abs() <= tolerance(point.second)
+        });
+        if !duplicate && !front.iter().any(|other| dominated(point, other)) {
+            kept.push(point.clone());
+        }
+    }
+    Ok(kept)
+}
+
+fn tolerance(value: f64) -> f64 {
+    value.abs().max(1.0) * VALUE_TOLERANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula;
+    use crate::models::{Nutrient, NutrientTargets, Price, Salt};
+
+    fn salt(formula: &str, per_kg: f64) -> Salt {
+        let mut salt = Salt::custom(formula.to_string(), formula.to_string(), formula::composition(formula, 1.0).unwrap())
+            .with_price(Price::new(per_kg, "EUR"));
+        salt.stock_solution = "A".to_string();
+        salt
+    }
+
+    #[test]
+    fn cost_against_chloride_is_monotone_between_the_optima() {
+        // Potassium from cheap KCl or from chloride-free but dearer KNO₃
+        let targets = NutrientTargets::unconstrained().with_range(Nutrient::K, 10.0, f64::INFINITY);
+        let problem = OptimizationProblem::new(targets)
+            .salts([salt("KNO₃", 2.0), salt("KCl", 0.5)])
+            .tanks(["A".to_string()]);
+        let front = pareto_front(&problem, Criterion::Cost, Criterion::Nutrient(Nutrient::Cl), 5).unwrap();
+        assert!(front.len() >= 3, "{}", front.len());
+
+        // Less chloride always costs more
+        for pair in front.windows(2) {
+            assert!(pair[1].second > pair[0].second && pair[1].first < pair[0].first);
+        }
+
+        // Ends: KNO₃ only (no chloride) and KCl only (cheapest)
+        let (kno3, kcl) = (formula::composition("KNO₃", 1.0).unwrap(), formula::composition("KCl", 1.0).unwrap());
+        let close = |actual: f64, expected: f64| (actual - expected).abs() <= 1e-4 * expected;
+        let chloride_free = front.first().unwrap();
+        // The caps leave a little headroom above the chloride minimum of 0
+        assert!(chloride_free.second < 1e-4, "{}", chloride_free.second);
+        assert!(close(chloride_free.first, 10.0 / kno3[Nutrient::K] * 2.0 / 1000.0));
+        let cheapest = front.last().unwrap();
+        assert!(close(cheapest.first, 10.0 / kcl[Nutrient::K] * 0.5 / 1000.0));
+        assert!(close(cheapest.second, 10.0 / kcl[Nutrient::K] * kcl[Nutrient::Cl]));
+    }
+}
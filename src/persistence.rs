@@ -0,0 +1,569 @@
+//! Workspace files and the copy kept in the browser's localStorage
+//!
+//! The stored JSON carries a schema version. Older versions are migrated step
+//! by step before deserialising; fields that were only added need no step,
+//! their serde defaults fill them in (see [`Salt`] and
+//! [`OptimizationResult`](crate::models::OptimizationResult)). A workspace
+//! that cannot be read is copied to a backup key instead of being overwritten.
+//!
+//! # Format
+//!
+//! One JSON object; every part except `version` may be left out and then
+//! takes its default. Field names are those of the Rust types:
+//!
+//! - `version`: schema version, currently 2
+//! - `salts`: list of [`Salt`]s. A predefined salt is stored as
+//!   `{ "predefined": name }` plus only the fields that differ from the salt
+//!   database, so corrections of the database reach stored workspaces; one
+//!   that is no longer in the database is left out. `stock_solution` names a tank or is
+//!   `"Unassigned"`; `composition` maps nutrient names (`"Nh4"`, `"No3"`,
+//!   `"K"`, … as in [`Nutrient`](crate::models::Nutrient)) to mass fractions;
+//!   `kind` is `"Salt"`, `"Acid"` or `"Base"`; `dose` is in g/L of stock
+//!   solution; `solubility.points` lists `[°C, g/L]` pairs
+//! - `stock_solutions`: tank names, e.g. `["A", "B"]`
+//! - `targets`: [`NutrientTargets`] with `basis` (`"Stock"` or `"Final"`),
+//!   `nitrogen`, `nh4_ratio` (0 to 1), `ec_max`, `ph` and `nutrients`, which
+//!   maps nutrient names to `{ "range", "unit", "weight", "target" }`
+//! - `options`: [`OptimizationOptions`]
+//! - `water`: `concentrations` in mg/L, by nutrient name
+//! - `comparison_history`: saved recipes with their `timestamp` label
+//!
+//! Every range is `{ "min": …, "max": … }`; a `max` of `null` leaves it open.
+//! Nutrients missing from a map count as zero.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::data::{get_predefined_salts, predefined_salt};
+use crate::models::{ComparisonEntry, Nutrient, NutrientTargets, Range, Salt, WaterAnalysis};
+use crate::optimizer::OptimizationOptions;
+
+/// Schema version written by this build
+pub const WORKSPACE_VERSION: u32 = 2;
+
+/// localStorage key of the workspace
+pub const STORAGE_KEY: &str = "nh4no3.workspace";
+
+/// localStorage key a workspace that could not be read or was replaced by a
+/// share link is moved to
+pub const BACKUP_KEY: &str = "nh4no3.workspace.backup";
+
+/// Rounding slack for the sum of a salt's mass fractions
+const COMPOSITION_TOLERANCE: f64 = 1e-3;
+
+/// Everything entered in the optimizer; missing parts load as their defaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Workspace {
+    pub version: u32,
+    pub salts: Vec<Salt>,
+    pub stock_solutions: Vec<String>,
+    pub targets: NutrientTargets,
+    pub options: OptimizationOptions,
+    pub water: WaterAnalysis,
+    pub comparison_history: Vec<ComparisonEntry>,
+}
+
+impl Default for Workspace {
+    /// Workspace shown on first load
+    fn default() -> Self {
+        Workspace {
+            version: WORKSPACE_VERSION,
+            salts: get_predefined_salts(),
+            stock_solutions: vec!["A".to_string(), "B".to_string()],
+            targets: NutrientTargets::default(),
+            options: OptimizationOptions::default(),
+            water: WaterAnalysis::pure(),
+            comparison_history: Vec::new(),
+        }
+    }
+}
+
+impl Workspace {
+    /// Compact JSON for the browser storage
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self.to_value()?)?)
+    }
+
+    /// Indented JSON for workspace files, which are meant to be diffed
+    pub fn to_pretty_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.to_value()?)?)
+    }
+
+    /// The stored form, with predefined salts as overrides
+    pub(crate) fn to_value(&self) -> Result<Value> {
+        let mut value = serde_json::to_value(Workspace { version: WORKSPACE_VERSION, ..self.clone() })?;
+        value["salts"] = self.salts.iter().map(stored_salt).collect::<Result<_>>()?;
+        Ok(value)
+    }
+
+    /// Reads and validates a workspace of this or any older schema version
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(json).context("Der Arbeitsbereich ist kein gültiges JSON")?;
+        Workspace::from_value(value)
+    }
+
+    /// Like [`from_json`](Self::from_json), for JSON that is already parsed
+    pub fn from_value(value: Value) -> Result<Self> {
+        let mut value = migrate(value)?;
+        expand_salts(&mut value)?;
+        let workspace: Workspace = serde_path_to_error::deserialize(value)
+            .map_err(|e| anyhow!("Feld {}: {}", e.path(), e.inner()))?;
+        workspace.validate()?;
+        Ok(workspace)
+    }
+
+    /// Checks the values the types alone do not restrict, naming the
+    /// offending field of every problem, e.g. `salts[2].composition.K`
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        let mut check = |valid: bool, field: String, message: &str| {
+            if !valid {
+                problems.push(format!("{}: {}", field, message));
+            }
+        };
+
+        for (i, tank) in self.stock_solutions.iter().enumerate() {
+            let field = format!("stock_solutions[{}]", i);
+            check(!tank.trim().is_empty() && tank != "Unassigned", field.clone(), "ungültiger Name der Stammlösung");
+            check(!self.stock_solutions[..i].contains(tank), field, "Stammlösung doppelt vorhanden");
+        }
+
+        for (i, salt) in self.salts.iter().enumerate() {
+            let field = |name: &str| format!("salts[{}].{} ({})", i, name, salt.name);
+            check(!self.salts[..i].iter().any(|s| s.name == salt.name), field("name"), "Salz doppelt vorhanden");
+            check(
+                salt.stock_solution == "Unassigned" || self.stock_solutions.contains(&salt.stock_solution),
+                field("stock_solution"),
+                "unbekannte Stammlösung",
+            );
+            check_salt(&mut check, salt, |f| field(&f.path()));
+        }
+
+        let targets = &self.targets;
+        check_range(&mut check, targets.nitrogen, "targets.nitrogen".to_string());
+        if let Some(ratio) = targets.nh4_ratio {
+            check((0.0..=1.0).contains(&ratio), "targets.nh4_ratio".to_string(), "Anteil muss zwischen 0 und 1 liegen");
+        }
+        if let Some(ec) = targets.ec_max {
+            check(ec > 0.0, "targets.ec_max".to_string(), "EC muss größer als 0 sein");
+        }
+        check_range(&mut check, targets.ph, "targets.ph".to_string());
+        for (nutrient, target) in targets.iter() {
+            let field = |name: &str| format!("targets.nutrients.{:?}.{}", nutrient, name);
+            check_range(&mut check, target.range, field("range"));
+            if let Some(weight) = target.weight {
+                check(weight.is_finite() && weight >= 0.0, field("weight"), "Gewichtung darf nicht negativ sein");
+            }
+        }
+
+        let options = &self.options;
+        check(options.dilution_ratio.is_finite() && options.dilution_ratio > 0.0, "options.dilution_ratio".to_string(), "Verdünnung muss größer als 0 sein");
+        for (tank, ratio) in options.tank_dilutions.iter() {
+            check(ratio.is_finite() && *ratio > 0.0, format!("options.tank_dilutions.{}", tank), "Verdünnung muss größer als 0 sein");
+        }
+        check(options.temperature.is_finite(), "options.temperature".to_string(), "Temperatur muss eine Zahl sein");
+        check(options.batch_volume.is_finite() && options.batch_volume > 0.0, "options.batch_volume".to_string(), "Ansatzvolumen muss größer als 0 sein");
+        check(options.max_salts != Some(0), "options.max_salts".to_string(), "mindestens 1 Salz muss erlaubt sein");
+        if let Some(grams) = options.min_weighing {
+            check(grams.is_finite() && grams >= 0.0, "options.min_weighing".to_string(), "Mindesteinwaage darf nicht negativ sein");
+        }
+        if let Some(tolerance) = options.charge_balance_tolerance {
+            check(tolerance.is_finite() && tolerance >= 0.0, "options.charge_balance_tolerance".to_string(), "Toleranz darf nicht negativ sein");
+        }
+
+        for (nutrient, mg_per_l) in self.water.concentrations.non_zero() {
+            check(mg_per_l.is_finite() && mg_per_l >= 0.0, format!("water.concentrations.{:?}", nutrient), "Konzentration darf nicht negativ sein");
+        }
+
+        if !problems.is_empty() {
+            bail!("Ungültiger Arbeitsbereich – {}", problems.join("; "));
+        }
+        Ok(())
+    }
+}
+
+/// Checks a salt from the salt form before it joins `others`, with the same
+/// rules [`Workspace::validate`] applies when the workspace is loaded again
+pub fn validate_salt(salt: &Salt, others: &[Salt]) -> Result<()> {
+    let mut problems = Vec::new();
+    let mut check = |valid: bool, field: String, message: &str| {
+        if !valid {
+            problems.push(format!("{}: {}", field, message));
+        }
+    };
+    check(!others.iter().any(|s| s.name == salt.name), SaltField::Name.label(), "ein Salz dieses Namens gibt es schon");
+    check_salt(&mut check, salt, SaltField::label);
+
+    if !problems.is_empty() {
+        bail!("{}", problems.join("; "));
+    }
+    Ok(())
+}
+
+/// Value of a salt checked on its own, see [`check_salt`]
+#[derive(Clone, Copy)]
+enum SaltField {
+    Name,
+    Fraction(Nutrient),
+    Composition,
+    Dose,
+    Density,
+    Price,
+    Currency,
+    Solubility(usize),
+}
+
+impl SaltField {
+    /// Path of the field in the workspace JSON
+    fn path(self) -> String {
+        match self {
+            SaltField::Name => "name".to_string(),
+            SaltField::Fraction(nutrient) => format!("composition.{:?}", nutrient),
+            SaltField::Composition => "composition".to_string(),
+            SaltField::Dose => "dose".to_string(),
+            SaltField::Density => "density".to_string(),
+            SaltField::Price => "price.per_kg".to_string(),
+            SaltField::Currency => "price.currency".to_string(),
+            SaltField::Solubility(i) => format!("solubility.points[{}]", i),
+        }
+    }
+
+    /// Name of the field in the salt form
+    fn label(self) -> String {
+        match self {
+            SaltField::Name => "Name".to_string(),
+            SaltField::Fraction(nutrient) => format!("Anteil {}", nutrient.symbol()),
+            SaltField::Composition => "Zusammensetzung".to_string(),
+            SaltField::Dose => "Dosis".to_string(),
+            SaltField::Density => "Dichte".to_string(),
+            SaltField::Price => "Preis".to_string(),
+            SaltField::Currency => "Währung".to_string(),
+            SaltField::Solubility(_) => "Löslichkeit".to_string(),
+        }
+    }
+}
+
+fn check_salt(check: &mut impl FnMut(bool, String, &str), salt: &Salt, field: impl Fn(SaltField) -> String) {
+    check(!salt.name.trim().is_empty(), field(SaltField::Name), "Name fehlt");
+    for (nutrient, fraction) in salt.composition.non_zero() {
+        check((0.0..=1.0).contains(&fraction), field(SaltField::Fraction(nutrient)), "Massenanteil muss zwischen 0 und 1 liegen");
+    }
+    let total: f64 = salt.composition.iter().map(|(_, fraction)| fraction).sum();
+    check(total <= 1.0 + COMPOSITION_TOLERANCE, field(SaltField::Composition), "Massenanteile ergeben mehr als 100 %");
+    check_range(check, salt.dose, field(SaltField::Dose));
+    if let Some(density) = salt.density {
+        check(density.is_finite() && density > 0.0, field(SaltField::Density), "Dichte muss größer als 0 sein");
+    }
+    if let Some(price) = &salt.price {
+        check(price.per_kg.is_finite() && price.per_kg >= 0.0, field(SaltField::Price), "Preis darf nicht negativ sein");
+        check(!price.currency.trim().is_empty(), field(SaltField::Currency), "Währung fehlt");
+    }
+    for (j, &(temperature, g_per_l)) in salt.solubility.iter().flat_map(|s| s.points.iter()).enumerate() {
+        check(
+            temperature.is_finite() && g_per_l.is_finite() && g_per_l > 0.0,
+            field(SaltField::Solubility(j)),
+            "Löslichkeit muss größer als 0 sein",
+        );
+    }
+}
+
+fn check_range(check: &mut impl FnMut(bool, String, &str), range: Range, field: String) {
+    check(range.min.is_finite() && range.min >= 0.0, field.clone(), "Minimum muss eine Zahl ≥ 0 sein");
+    check(range.max >= range.min, field, "Maximum liegt unter dem Minimum");
+}
+
+/// Stored form of a salt: a predefined salt becomes `{ "predefined": name }`
+/// with the fields that differ from the salt database, a custom one is
+/// written out in full
+pub(crate) fn stored_salt(salt: &Salt) -> Result<Value> {
+    let mut value = serde_json::to_value(salt)?;
+    let Some(default) = predefined_salt(&salt.name).filter(|_| !salt.is_custom) else {
+        return Ok(value);
+    };
+    let default = serde_json::to_value(default)?;
+    if let (Value::Object(fields), Value::Object(defaults)) = (&mut value, &default) {
+        fields.retain(|key, field| key != "name" && key != "is_custom" && defaults.get(key) != Some(field));
+        fields.insert("predefined".to_string(), json!(salt.name));
+    }
+    Ok(value)
+}
+
+/// Replaces every predefined salt reference in `value["salts"]` by the
+/// database values with its overrides on top; references to salts the
+/// database no longer has are dropped
+fn expand_salts(value: &mut Value) -> Result<()> {
+    let Some(salts) = value.get_mut("salts").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+    let mut expanded = Vec::with_capacity(salts.len());
+    for entry in salts.drain(..) {
+        let Some(name) = entry.get("predefined").and_then(Value::as_str) else {
+            expanded.push(entry);
+            continue;
+        };
+        let Some(default) = predefined_salt(name) else {
+            continue;
+        };
+        let mut salt = serde_json::to_value(default)?;
+        if let (Value::Object(fields), Value::Object(overrides)) = (&mut salt, entry) {
+            fields.extend(overrides.into_iter().filter(|(key, _)| key != "predefined"));
+        }
+        expanded.push(salt);
+    }
+    *salts = expanded;
+    Ok(())
+}
+
+/// Brings a stored workspace up to [`WORKSPACE_VERSION`]
+///
+/// Each schema change that serde defaults cannot cover (renamed or
+/// restructured fields) adds a step here, e.g. `if version < 2 { … }`.
+fn migrate(mut value: Value) -> Result<Value> {
+    let version = value.get("version")
+        .and_then(Value::as_u64)
+        .context("Dem gespeicherten Arbeitsbereich fehlt die Versionsangabe")?;
+    if version > u64::from(WORKSPACE_VERSION) {
+        bail!(
+            "Der gespeicherte Arbeitsbereich stammt aus einer neueren Version (Schema {}, unterstützt bis {})",
+            version,
+            WORKSPACE_VERSION
+        );
+    }
+    if version < 2 {
+        migrate_predefined_salts(&mut value);
+    }
+    value["version"] = WORKSPACE_VERSION.into();
+    Ok(value)
+}
+
+/// Version 1 stored full copies of the predefined salts, which froze their
+/// database values. Only what the user sets for them is kept: state, tank,
+/// price and a dose other than the open default; the rest comes from the
+/// database again.
+fn migrate_predefined_salts(value: &mut Value) {
+    let Some(salts) = value.get_mut("salts").and_then(Value::as_array_mut) else {
+        return;
+    };
+    let open_dose = serde_json::to_value(Range::unbounded()).unwrap_or_default();
+    for entry in salts.iter_mut() {
+        let is_custom = entry.get("is_custom").and_then(Value::as_bool).unwrap_or(false);
+        let Some(name) = entry.get("name").and_then(Value::as_str).filter(|name| !is_custom && predefined_salt(name).is_some()) else {
+            continue;
+        };
+        let mut reference = json!({ "predefined": name });
+        for key in ["enabled", "stock_solution", "price"] {
+            if let Some(field) = entry.get(key).filter(|field| !field.is_null()) {
+                reference[key] = field.clone();
+            }
+        }
+        if let Some(dose) = entry.get("dose").filter(|dose| **dose != open_dose) {
+            reference["dose"] = dose.clone();
+        }
+        *entry = reference;
+    }
+}
+
+/// Workspace of the last session, `None` if nothing was stored yet
+///
+/// A stored workspace that cannot be read is moved to [`BACKUP_KEY`], so the
+/// next save does not destroy it.
+pub fn load() -> Result<Option<Workspace>> {
+    let Some(json) = storage::read(STORAGE_KEY)? else {
+        return Ok(None);
+    };
+    match Workspace::from_json(&json) {
+        Ok(workspace) => Ok(Some(workspace)),
+        Err(e) => {
+            storage::write(BACKUP_KEY, &json)?;
+            Err(e.context(format!("Ein neuer Arbeitsbereich wurde angelegt, der alte liegt unter \"{}\"", BACKUP_KEY)))
+        }
+    }
+}
+
+pub fn save(workspace: &Workspace) -> Result<()> {
+    storage::write(STORAGE_KEY, &workspace.to_json()?)
+}
+
+/// Keeps a copy of a workspace under [`BACKUP_KEY`] before it is replaced
+pub fn backup(workspace: &Workspace) -> Result<()> {
+    storage::write(BACKUP_KEY, &workspace.to_json()?)
+}
+
+/// Forgets the stored workspace, so the next load starts from the defaults
+pub fn clear() -> Result<()> {
+    storage::remove(STORAGE_KEY)
+}
+
+#[cfg(target_arch = "wasm32")]
+mod storage {
+    use anyhow::{anyhow, Result};
+
+    fn local_storage() -> Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| anyhow!("Der Browser-Speicher (localStorage) ist nicht verfügbar"))
+    }
+
+    pub fn read(key: &str) -> Result<Option<String>> {
+        local_storage()?
+            .get_item(key)
+            .map_err(|e| anyhow!("Lesen aus dem Browser-Speicher fehlgeschlagen: {:?}", e))
+    }
+
+    pub fn write(key: &str, value: &str) -> Result<()> {
+        local_storage()?
+            .set_item(key, value)
+            .map_err(|e| anyhow!("Speichern im Browser-Speicher fehlgeschlagen (Speicher voll?): {:?}", e))
+    }
+
+    pub fn remove(key: &str) -> Result<()> {
+        local_storage()?
+            .remove_item(key)
+            .map_err(|e| anyhow!("Löschen aus dem Browser-Speicher fehlgeschlagen: {:?}", e))
+    }
+}
+
+/// Outside the browser there is no storage; nothing is kept between runs
+#[cfg(not(target_arch = "wasm32"))]
+mod storage {
+    use anyhow::Result;
+
+    pub fn read(_key: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    pub fn write(_key: &str, _value: &str) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn remove(_key: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Price, SaltKind};
+
+    fn salt_named<'a>(workspace: &'a Workspace, name: &str) -> &'a Salt {
+        workspace.salts.iter().find(|salt| salt.name == name).unwrap()
+    }
+
+    #[test]
+    fn round_trip_keeps_the_workspace() {
+        let mut workspace = Workspace::default();
+        workspace.salts[0].stock_solution = "B".to_string();
+        workspace.salts[1].price = Some(Price::new(1.2, "EUR"));
+        workspace.salts.push(Salt::custom("Fe-EDTA".to_string(), String::new(), Default::default()));
+
+        let read = Workspace::from_json(&workspace.to_json().unwrap()).unwrap();
+        assert_eq!(read.salts, workspace.salts);
+        assert_eq!(read.to_value().unwrap(), workspace.to_value().unwrap());
+        let pretty = Workspace::from_json(&workspace.to_pretty_json().unwrap()).unwrap();
+        assert_eq!(pretty.salts, workspace.salts);
+    }
+
+    #[test]
+    fn predefined_salts_are_stored_as_overrides() {
+        let mut salt = predefined_salt("KNO₃").unwrap();
+        assert_eq!(stored_salt(&salt).unwrap(), json!({ "predefined": "KNO₃" }));
+
+        salt.stock_solution = "A".to_string();
+        salt.solubility = None;
+        assert_eq!(stored_salt(&salt).unwrap(), json!({ "predefined": "KNO₃", "stock_solution": "A", "solubility": null }));
+
+        let custom = Salt { is_custom: true, ..salt };
+        assert_eq!(stored_salt(&custom).unwrap()["name"], "KNO₃");
+    }
+
+    #[test]
+    fn database_values_reach_stored_workspaces() {
+        // Nothing but the tank stored: composition and dose come from the database
+        let json = r#"{ "version": 2, "stock_solutions": ["A", "B"], "salts": [{ "predefined": "Ferty 72", "stock_solution": "B" }] }"#;
+        let workspace = Workspace::from_json(json).unwrap();
+        let ferty = predefined_salt("Ferty 72").unwrap();
+        assert_eq!(workspace.salts, [Salt { stock_solution: "B".to_string(), ..ferty }]);
+    }
+
+    #[test]
+    fn removed_predefined_salts_are_dropped() {
+        let json = r#"{ "version": 2, "salts": [{ "predefined": "Gibt es nicht" }, { "predefined": "KNO₃" }] }"#;
+        let workspace = Workspace::from_json(json).unwrap();
+        assert_eq!(workspace.salts.len(), 1);
+    }
+
+    #[test]
+    fn version_1_takes_predefined_values_from_the_database() {
+        let ferty = Salt { stock_solution: "B".to_string(), dose: Range::unbounded(), composition: Default::default(), ..predefined_salt("Ferty 72").unwrap() };
+        let acid = Salt { enabled: true, dose: Range::new(0.0, 5.0), ..predefined_salt("Salpetersäure 65 %").unwrap() };
+        let custom = Salt::custom("KNO₃".to_string(), "KNO₃".to_string(), Default::default()).with_kind(SaltKind::Base);
+        let json = json!({
+            "version": 1,
+            "stock_solutions": ["A", "B"],
+            "salts": [ferty, acid, custom],
+        });
+
+        let workspace = Workspace::from_value(json).unwrap();
+        assert_eq!(workspace.version, WORKSPACE_VERSION);
+        let ferty = salt_named(&workspace, "Ferty 72");
+        let default = predefined_salt("Ferty 72").unwrap();
+        assert_eq!(ferty.stock_solution, "B");
+        assert_eq!(ferty.composition, default.composition);
+        assert_eq!(ferty.dose, default.dose);
+        let acid = salt_named(&workspace, "Salpetersäure 65 %");
+        assert!(acid.enabled);
+        assert_eq!(acid.dose, Range::new(0.0, 5.0));
+        // A custom salt of a predefined name stays as it is
+        let custom = salt_named(&workspace, "KNO₃");
+        assert!(custom.is_custom);
+        assert_eq!(custom.kind, SaltKind::Base);
+    }
+
+    #[test]
+    fn comparison_entries_survive_missing_fields_and_removed_rules() {
+        let json = r#"{
+            "version": 2,
+            "comparison_history": [{
+                "timestamp": "12:00",
+                "result": {
+                    "recipe": [{ "salt": "KNO₃", "tank": "B", "amount": 10.0 }],
+                    "conflicts": [
+                        { "rule": "Magnesiumphosphat", "tank": "B", "first": ["MgSO₄·7H₂O"], "second": ["KH₂PO₄"] },
+                        { "rule": "Gips", "tank": "A", "first": ["CaCl₂·2H₂O"], "second": ["K₂SO₄"] }
+                    ],
+                    "cost": {}
+                }
+            }]
+        }"#;
+        let workspace = Workspace::from_json(json).unwrap();
+        let result = &workspace.comparison_history[0].result;
+        assert_eq!(result.recipe.len(), 1);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].rule.name, "Gips");
+        assert!(result.charge_balance.cations.is_empty());
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let json = format!(r#"{{ "version": {} }}"#, WORKSPACE_VERSION + 1);
+        assert!(Workspace::from_json(&json).is_err());
+    }
+
+    #[test]
+    fn salt_form_rules_match_loading() {
+        let others = get_predefined_salts();
+        let mut salt = Salt::custom("Eigenes Salz".to_string(), String::new(), Default::default());
+        assert!(validate_salt(&salt, &others).is_ok());
+
+        salt.price = Some(Price::new(-1.0, "EUR"));
+        assert!(validate_salt(&salt, &others).is_err());
+        salt.price = None;
+        salt.composition[Nutrient::K] = 0.7;
+        salt.composition[Nutrient::No3] = 0.7;
+        assert!(validate_salt(&salt, &others).is_err());
+        assert!(validate_salt(&Salt::custom("KNO₃".to_string(), String::new(), Default::default()), &others).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! pH estimate of the final nutrient solution
+//!
+//! Strong ions are fully dissociated; phosphate, carbonate (HCO₃⁻ counts as
+//! total dissolved carbonate, no CO₂ escapes) and ammonium follow their acid
+//! equilibria at 25 °C. The pH is where the net charge of all species is zero.
+//! Activity corrections are left out, which keeps the charge at a fixed pH
+//! linear in the concentrations and thus usable as an LP constraint.
+
+use crate::charge;
+use crate::models::{Nutrient, NutrientVector};
+
+/// pKa values of phosphoric acid
+const PHOSPHATE_PKA: [f64; 3] = [2.15, 7.20, 12.35];
+
+/// pKa values of carbonic acid
+const CARBONATE_PKA: [f64; 2] = [6.35, 10.33];
+
+/// pKa of ammonium
+const AMMONIUM_PKA: f64 = 9.25;
+
+/// Ionic product of water
+const PKW: f64 = 14.0;
+
+/// Search interval and precision of [`estimate_ph`]
+const PH_RANGE: (f64, f64) = (0.0, 14.0);
+const PH_PRECISION: f64 = 1e-4;
+
+/// Mean number of protons released per molecule of a polyprotic acid
+fn dissociation(ph: f64, pkas: &[f64]) -> f64 {
+    // Relative amounts of the species H_nA, H_(n-1)A⁻, … with 0, 1, … protons released
+    let mut species = vec![1.0];
+    for pka in pkas {
+        let previous = *species.last().unwrap_or(&1.0);
+        species.push(previous * 10f64.powf(ph - pka));
+    }
+    let total: f64 = species.iter().sum();
+    species.iter().enumerate().map(|(released, amount)| released as f64 * amount).sum::<f64>() / total
+}
+
+/// Net charge (meq) per g of each nutrient at a given pH
+pub fn charge_coefficients(ph: f64) -> NutrientVector {
+    let mut coefficients = NutrientVector::zero();
+    for nutrient in Nutrient::ALL {
+        let meq_per_mol = 1000.0 / nutrient.molar_mass();
+        coefficients[nutrient] = match nutrient {
+            Nutrient::P => -dissociation(ph, &PHOSPHATE_PKA) * meq_per_mol,
+            Nutrient::Hco3 => -dissociation(ph, &CARBONATE_PKA) * meq_per_mol,
+            Nutrient::Nh4 => (1.0 - dissociation(ph, &[AMMONIUM_PKA])) * meq_per_mol,
+            _ => nutrient.charge().map_or(0.0, |z| z.signum() * charge::meq_per_gram(nutrient)),
+        };
+    }
+    coefficients
+}
+
+/// Charge (meq/L) of free H⁺ minus OH⁻ at a given pH
+pub fn free_charge(ph: f64) -> f64 {
+    1000.0 * (10f64.powf(-ph) - 10f64.powf(ph - PKW))
+}
+
+/// Net charge (meq/L) of a solution given in g/L if it had the given pH;
+/// falls as the pH rises
+pub fn net_charge(concentrations: &NutrientVector, ph: f64) -> f64 {
+    let coefficients = charge_coefficients(ph);
+    concentrations.iter()
+        .map(|(nutrient, g_per_l)| g_per_l * coefficients[nutrient])
+        .sum::<f64>()
+        + free_charge(ph)
+}
+
+/// Estimated pH of a solution given in g/L
+pub fn estimate_ph(concentrations: &NutrientVector) -> f64 {
+    let (mut low, mut high) = PH_RANGE;
+    while high - low > PH_PRECISION {
+        let mid = (low + high) / 2.0;
+        if net_charge(concentrations, mid) > 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Solution of the given nutrients in mmol/L, converted to g/L
+    fn millimolar(amounts: &[(Nutrient, f64)]) -> NutrientVector {
+        let mut concentrations = NutrientVector::zero();
+        for &(nutrient, mmol) in amounts {
+            concentrations[nutrient] = mmol / 1000.0 * nutrient.molar_mass();
+        }
+        concentrations
+    }
+
+    #[test]
+    fn half_neutralised_bicarbonate_buffers_at_its_pka() {
+        // 2 mmol/L carbonate with 1 mmol/L K⁺: half of it is HCO₃⁻, half CO₂
+        let buffer = millimolar(&[(Nutrient::K, 1.0), (Nutrient::Hco3, 2.0)]);
+        let ph = estimate_ph(&buffer);
+        assert!((ph - CARBONATE_PKA[0]).abs() < 0.02, "{}", ph);
+        assert!(net_charge(&buffer, ph).abs() < 1e-3);
+    }
+
+    #[test]
+    fn bicarbonate_alone_sits_between_its_pkas() {
+        let khco3 = millimolar(&[(Nutrient::K, 10.0), (Nutrient::Hco3, 10.0)]);
+        let ph = estimate_ph(&khco3);
+        assert!((ph - (CARBONATE_PKA[0] + CARBONATE_PKA[1]) / 2.0).abs() < 0.05, "{}", ph);
+    }
+
+    #[test]
+    fn net_charge_falls_with_the_ph() {
+        let solution = millimolar(&[(Nutrient::Nh4, 1.0), (Nutrient::P, 1.0), (Nutrient::Hco3, 0.5)]);
+        let charges: Vec<f64> = (0..=14).map(|ph| net_charge(&solution, ph as f64)).collect();
+        assert!(charges.windows(2).all(|pair| pair[1] < pair[0]));
+    }
+}
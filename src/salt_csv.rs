@@ -0,0 +1,570 @@
+//! Salt library import and export as CSV, e.g. from supplier product sheets
+//!
+//! Columns are assigned by a [`ColumnMapping`], which [`ColumnMapping::guess`]
+//! fills from the header row. Nutrient contents may be given in % or as mass
+//! fraction, and as the nutrient itself, as the fertilizer-label oxide (K₂O,
+//! P₂O₅, …) or, for NH₄⁺ and NO₃⁻, as nitrogen. Comma, semicolon and tab
+//! separators and decimal commas are recognised. A solubility is a single
+//! value or a temperature curve written as "0: 133 / 20: 316" (°C: g/L).
+//! [`export`] writes the format the guess reads back.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+
+use crate::formula;
+use crate::models::{Nutrient, NutrientVector, Price, Range, Salt, SaltKind, Solubility};
+
+/// Molar mass of nitrogen (g/mol), for contents given as NH₄⁺-N or NO₃⁻-N
+const NITROGEN_MOLAR_MASS: f64 = 14.007;
+
+/// Rounding slack for the sum of a salt's mass fractions
+const COMPOSITION_TOLERANCE: f64 = 1e-3;
+
+/// Largest difference of any mass fraction for two salts to count as the same product
+const SAME_COMPOSITION: f64 = 1e-4;
+
+/// Currency of prices when no currency column is mapped
+const DEFAULT_CURRENCY: &str = "EUR";
+
+/// Rows of a CSV file below its header
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Reads CSV text; the separator is taken from the header row
+    pub fn parse(text: &str) -> Result<Table> {
+        let text = text.trim_start_matches('\u{feff}');
+        let header_line = text.lines().next().unwrap_or_default();
+        let delimiter = [';', '\t', ',']
+            .into_iter()
+            .max_by_key(|&d| header_line.matches(d).count())
+            .filter(|&d| header_line.contains(d))
+            .unwrap_or(',');
+
+        let mut records = parse_records(text, delimiter)?
+            .into_iter()
+            .filter(|record| record.iter().any(|cell| !cell.trim().is_empty()));
+        let Some(headers) = records.next() else {
+            bail!("Die Datei enthält keine Zeilen");
+        };
+        let rows: Vec<Vec<String>> = records.collect();
+        if rows.is_empty() {
+            bail!("Die Datei enthält nur die Kopfzeile");
+        }
+        Ok(Table { headers: headers.into_iter().map(|h| h.trim().to_string()).collect(), rows })
+    }
+
+    fn cell<'a>(&'a self, row: &'a [String], column: Option<usize>) -> &'a str {
+        column.and_then(|c| row.get(c)).map_or("", |cell| cell.trim())
+    }
+
+    fn header(&self, column: usize) -> &str {
+        self.headers.get(column).map_or("?", String::as_str)
+    }
+}
+
+// Splits CSV text into records; quoted cells may contain separators, quotes ("") and line breaks
+fn parse_records(text: &str, delimiter: char) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if cell.trim().is_empty() => {
+                cell.clear();
+                quoted = true;
+            }
+            c if quoted => cell.push(c),
+            c if c == delimiter => record.push(std::mem::take(&mut cell)),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut cell));
+                records.push(std::mem::take(&mut record));
+            }
+            c => cell.push(c),
+        }
+    }
+    if quoted {
+        bail!("Ein Anführungszeichen wird nicht geschlossen");
+    }
+    if !cell.is_empty() || !record.is_empty() {
+        record.push(cell);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Whether nutrient contents are percentages or mass fractions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContentUnit {
+    #[default]
+    Percent,
+    Fraction,
+}
+
+impl ContentUnit {
+    pub const ALL: [ContentUnit; 2] = [ContentUnit::Percent, ContentUnit::Fraction];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ContentUnit::Percent => "%",
+            ContentUnit::Fraction => "Anteil (0–1)",
+        }
+    }
+
+    fn per_fraction(self) -> f64 {
+        match self {
+            ContentUnit::Percent => 100.0,
+            ContentUnit::Fraction => 1.0,
+        }
+    }
+}
+
+/// What a nutrient column measures
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContentForm {
+    /// The nutrient as tracked by the optimizer, e.g. K or NO₃⁻
+    #[default]
+    Nutrient,
+    /// Fertilizer-label oxide such as K₂O or P₂O₅
+    Oxide,
+    /// Nitrogen in NH₄⁺ or NO₃⁻, as on fertilizer labels
+    Nitrogen,
+}
+
+impl ContentForm {
+    /// Forms in which a nutrient can be given
+    pub fn available(nutrient: Nutrient) -> Vec<ContentForm> {
+        let mut forms = vec![ContentForm::Nutrient];
+        if nutrient.oxide().is_some() {
+            forms.push(ContentForm::Oxide);
+        }
+        if nutrient.is_nitrogen() {
+            forms.push(ContentForm::Nitrogen);
+        }
+        forms
+    }
+
+    /// e.g. "K⁺", "K₂O" or "NO₃⁻-N"
+    pub fn label(self, nutrient: Nutrient) -> String {
+        match (self, nutrient.oxide()) {
+            (ContentForm::Oxide, Some((oxide, _))) => oxide.to_string(),
+            (ContentForm::Nitrogen, _) => format!("{}-N", nutrient.symbol()),
+            _ => nutrient.symbol().to_string(),
+        }
+    }
+
+    /// Mass of this form per mass of the nutrient
+    fn per_nutrient(self, nutrient: Nutrient) -> f64 {
+        match (self, nutrient.oxide()) {
+            (ContentForm::Oxide, Some((_, factor))) => factor,
+            (ContentForm::Nitrogen, _) => NITROGEN_MOLAR_MASS / nutrient.molar_mass(),
+            _ => 1.0,
+        }
+    }
+
+    // Header spellings, normalised as by `normalize`
+    fn headers(self, nutrient: Nutrient) -> Vec<String> {
+        match self {
+            ContentForm::Nutrient => vec![normalize(nutrient.symbol()), normalize(nutrient.name()), normalize(nutrient.key())],
+            ContentForm::Oxide => vec![normalize(&self.label(nutrient))],
+            ContentForm::Nitrogen => {
+                let ion = normalize(nutrient.symbol());
+                vec![format!("{}N", ion), format!("N{}", ion)]
+            }
+        }
+    }
+}
+
+/// Which column holds which salt property; `None` leaves it empty
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ColumnMapping {
+    pub name: Option<usize>,
+    pub formula: Option<usize>,
+    /// "Salz", "Säure" or "Lauge"
+    pub kind: Option<usize>,
+    /// Column and form of each nutrient given in the file
+    pub nutrients: BTreeMap<Nutrient, (usize, ContentForm)>,
+    pub unit: ContentUnit,
+    /// Price per kg
+    pub price: Option<usize>,
+    pub currency: Option<usize>,
+    /// g/L at the reference temperature, or a curve
+    pub solubility: Option<usize>,
+    /// Dose range in g/L of stock solution
+    pub dose_min: Option<usize>,
+    pub dose_max: Option<usize>,
+    /// g/mL, marks the product as a liquid
+    pub density: Option<usize>,
+}
+
+impl ColumnMapping {
+    /// Mapping suggested by the header row
+    pub fn guess(table: &Table) -> Self {
+        let headers: Vec<String> = table.headers.iter().map(|h| normalize(h)).collect();
+        let find = |names: &[&str]| headers.iter().position(|h| names.iter().any(|name| h.starts_with(name)));
+        let find_exact = |names: &[&str]| headers.iter().position(|h| names.contains(&h.as_str()));
+
+        let mut nutrients = BTreeMap::new();
+        for nutrient in Nutrient::ALL {
+            let found = ContentForm::available(nutrient).into_iter().find_map(|form| {
+                let spellings = form.headers(nutrient);
+                headers.iter().position(|h| spellings.contains(h)).map(|column| (column, form))
+            });
+            if let Some(found) = found {
+                nutrients.insert(nutrient, found);
+            }
+        }
+
+        // Percent signs in the headers or values above 1 mean percentages
+        let columns: Vec<usize> = nutrients.values().map(|&(column, _)| column).collect();
+        let percent_header = columns.iter().any(|&c| table.headers[c].contains('%'));
+        let above_one = table.rows.iter().any(|row| {
+            columns.iter().any(|&c| parse_number(table.cell(row, Some(c))).ok().flatten().is_some_and(|v| v > 1.0))
+        });
+        let unit = if percent_header || above_one { ContentUnit::Percent } else { ContentUnit::Fraction };
+
+        ColumnMapping {
+            name: find(&["NAME", "PRODUKT", "PRODUCT", "BEZEICHNUNG", "SALZ"]),
+            formula: find(&["FORMEL", "FORMULA", "SUMMENFORMEL"]),
+            kind: find_exact(&["ART", "TYP", "KIND", "TYPE"]),
+            nutrients,
+            unit,
+            price: find(&["PREIS", "PRICE"]),
+            currency: find(&["WÄHRUNG", "WAHRUNG", "WAEHRUNG", "CURRENCY"]),
+            solubility: find(&["LÖSLICHKEIT", "LOSLICHKEIT", "LOESLICHKEIT", "SOLUBILITY"]),
+            dose_min: find(&["DOSISMIN", "MINDOSIS", "DOSEMIN", "MINDOSE"]),
+            dose_max: find(&["DOSISMAX", "MAXDOSIS", "DOSEMAX", "MAXDOSE"]),
+            density: find(&["DICHTE", "DENSITY"]),
+        }
+    }
+}
+
+/// One row of the file as it would enter the library
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    /// Line in the file, the header being line 1
+    pub line: usize,
+    /// The salt, or why the row cannot be read
+    pub salt: Result<Salt, String>,
+    /// Why the salt may already be in the library or earlier in the file
+    pub duplicate: Option<String>,
+}
+
+impl ImportRow {
+    /// Whether the row is taken over unless the user decides otherwise
+    pub fn selected_by_default(&self) -> bool {
+        self.salt.is_ok() && self.duplicate.is_none()
+    }
+}
+
+/// Reads every row with the mapping and checks it against the library
+pub fn preview(table: &Table, mapping: &ColumnMapping, library: &[Salt]) -> Vec<ImportRow> {
+    let mut rows: Vec<ImportRow> = Vec::new();
+    for (i, row) in table.rows.iter().enumerate() {
+        let line = i + 2;
+        let salt = read_salt(table, mapping, row);
+        let duplicate = salt.as_ref().ok().and_then(|salt| {
+            let same_name = |other: &Salt| other.name.trim().eq_ignore_ascii_case(salt.name.trim());
+            if let Some(existing) = library.iter().find(|s| same_name(s)) {
+                return Some(format!("„{}“ ist schon vorhanden – anhaken, um es zu überschreiben", existing.name));
+            }
+            if let Some(earlier) = rows.iter().find(|r| r.salt.as_ref().is_ok_and(&same_name)) {
+                return Some(format!("Name schon in Zeile {}", earlier.line));
+            }
+            library.iter()
+                .find(|other| {
+                    Nutrient::ALL.iter().all(|&n| (other.composition[n] - salt.composition[n]).abs() <= SAME_COMPOSITION)
+                })
+                .map(|other| format!("gleiche Zusammensetzung wie „{}“", other.name))
+        });
+        rows.push(ImportRow { line, salt, duplicate });
+    }
+    rows
+}
+
+// Salt of one row; an empty composition is taken from the formula if it can be parsed
+fn read_salt(table: &Table, mapping: &ColumnMapping, row: &[String]) -> Result<Salt, String> {
+    let number = |column: Option<usize>| -> Result<Option<f64>, String> {
+        parse_number(table.cell(row, column))
+            .map_err(|text| format!("Spalte „{}“: „{}“ ist keine Zahl", column.map_or("?", |c| table.header(c)), text))
+    };
+
+    let name = table.cell(row, mapping.name);
+    if name.is_empty() {
+        return Err("Name fehlt".to_string());
+    }
+    let formula_text = table.cell(row, mapping.formula);
+
+    let mut composition = NutrientVector::zero();
+    for (&nutrient, &(column, form)) in mapping.nutrients.iter() {
+        if let Some(value) = number(Some(column))? {
+            composition[nutrient] = value / mapping.unit.per_fraction() / form.per_nutrient(nutrient);
+        }
+    }
+    if composition.non_zero().next().is_none() {
+        composition = formula::composition(formula_text, 1.0)
+            .map_err(|_| "keine Nährstoffgehalte und keine auswertbare Formel".to_string())?;
+    }
+    if let Some((nutrient, _)) = composition.non_zero().find(|&(_, fraction)| !(0.0..=1.0).contains(&fraction)) {
+        return Err(format!("{}-Gehalt außerhalb von 0 bis 100 %", nutrient.symbol()));
+    }
+    let total: f64 = composition.iter().map(|(_, fraction)| fraction).sum();
+    if total > 1.0 + COMPOSITION_TOLERANCE {
+        return Err(format!("Gehalte ergeben {:.0} % – Einheit prüfen (% oder Anteil)", total * 100.0));
+    }
+
+    let kind_text = table.cell(row, mapping.kind);
+    let kind = match kind_text {
+        "" => SaltKind::default(),
+        text => SaltKind::ALL.into_iter()
+            .find(|kind| kind.label().eq_ignore_ascii_case(text) || format!("{:?}", kind).eq_ignore_ascii_case(text))
+            .ok_or_else(|| format!("unbekannte Art „{}“ (Salz, Säure oder Lauge)", text))?,
+    };
+
+    let mut salt = Salt::custom(name.to_string(), formula_text.to_string(), composition).with_kind(kind);
+    if let Some(density) = number(mapping.density)? {
+        if density <= 0.0 {
+            return Err("Dichte muss größer als 0 sein".to_string());
+        }
+        salt = salt.liquid(density);
+    }
+    if let Some(per_kg) = number(mapping.price)? {
+        if per_kg < 0.0 {
+            return Err("Preis darf nicht negativ sein".to_string());
+        }
+        let currency = match table.cell(row, mapping.currency) {
+            "" => DEFAULT_CURRENCY,
+            currency => currency,
+        };
+        salt = salt.with_price(Price::new(per_kg, currency));
+    }
+    if let Some(solubility) = parse_solubility(table.cell(row, mapping.solubility))? {
+        salt = salt.with_solubility(solubility);
+    }
+    let dose = Range::new(
+        number(mapping.dose_min)?.unwrap_or(0.0),
+        number(mapping.dose_max)?.unwrap_or(f64::INFINITY),
+    );
+    if dose.min < 0.0 || dose.max < dose.min {
+        return Err("Dosis: Minimum muss zwischen 0 und dem Maximum liegen".to_string());
+    }
+    Ok(salt.with_dose(dose))
+}
+
+/// A single g/L value, or points "°C: g/L" separated by "/"
+fn parse_solubility(text: &str) -> Result<Option<Solubility>, String> {
+    let invalid = || format!("Löslichkeit „{}“ ist weder eine Zahl noch eine Kurve „°C: g/l / …“", text);
+    let positive = |value: Option<f64>| value.filter(|v| *v > 0.0).ok_or_else(invalid);
+    if !text.contains(':') {
+        return match parse_number(text).map_err(|_| invalid())? {
+            Some(g_per_l) => Ok(Some(Solubility::at_reference(positive(Some(g_per_l))?))),
+            None => Ok(None),
+        };
+    }
+    let points = text.split('/')
+        .map(|point| {
+            let (temperature, g_per_l) = point.split_once(':').ok_or_else(invalid)?;
+            let temperature = parse_number(&temperature.replace("°C", "")).ok().flatten().ok_or_else(invalid)?;
+            Ok((temperature, positive(parse_number(g_per_l).ok().flatten())?))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(Some(Solubility::curve(&points)))
+}
+
+/// Adds salts to the library; one named like an existing salt replaces its
+/// values but keeps its tank, whether it is enabled and whether it is
+/// predefined (so it can still be reset), and its dose unless the file gives one
+pub fn add_to_library(library: &mut Vec<Salt>, salts: Vec<Salt>) {
+    for salt in salts {
+        match library.iter_mut().find(|s| s.name.trim().eq_ignore_ascii_case(salt.name.trim())) {
+            Some(existing) => {
+                let dose = match salt.dose == Range::unbounded() {
+                    true => existing.dose,
+                    false => salt.dose,
+                };
+                *existing = Salt {
+                    is_custom: existing.is_custom,
+                    enabled: existing.enabled,
+                    stock_solution: existing.stock_solution.clone(),
+                    dose,
+                    ..salt
+                };
+            }
+            None => library.push(salt),
+        }
+    }
+}
+
+/// The library as semicolon-separated CSV with decimal commas, as spreadsheets
+/// in German locales expect; contents in % of the nutrient
+pub fn export(salts: &[Salt]) -> String {
+    let mut headers = vec!["Name".to_string(), "Formel".to_string(), "Art".to_string()];
+    headers.extend(Nutrient::ALL.iter().map(|n| format!("{} %", n.symbol())));
+    headers.extend(["Preis pro kg", "Währung", "Löslichkeit g/l (°C: g/l / …)", "Dichte g/ml", "Dosis min g/l", "Dosis max g/l"].map(String::from));
+
+    // Byte order mark, so that spreadsheets read the file as UTF-8
+    let mut csv = String::from("\u{feff}");
+    csv.push_str(&csv_line(&headers));
+    for salt in salts {
+        let mut cells = vec![salt.name.clone(), salt.formula.clone(), salt.kind.label().to_string()];
+        cells.extend(Nutrient::ALL.iter().map(|&n| format_decimal(salt.composition[n] * 100.0)));
+        cells.push(salt.price.as_ref().map(|p| format_decimal(p.per_kg)).unwrap_or_default());
+        cells.push(salt.price.as_ref().map(|p| p.currency.clone()).unwrap_or_default());
+        cells.push(salt.solubility.as_ref().map(format_solubility).unwrap_or_default());
+        cells.push(salt.density.map(format_decimal).unwrap_or_default());
+        cells.push(format_decimal(salt.dose.min));
+        cells.push(Some(salt.dose.max).filter(|max| max.is_finite()).map(format_decimal).unwrap_or_default());
+        csv.push_str(&csv_line(&cells));
+    }
+    csv
+}
+
+// A single point as a plain value, which holds at every temperature
+fn format_solubility(solubility: &Solubility) -> String {
+    match solubility.points.as_slice() {
+        [(_, g_per_l)] => format_decimal(*g_per_l),
+        points => points.iter()
+            .map(|&(temperature, g_per_l)| {
+                let temperature = if temperature == 0.0 { "0".to_string() } else { format_decimal(temperature) };
+                format!("{}: {}", temperature, format_decimal(g_per_l))
+            })
+            .collect::<Vec<_>>()
+            .join(" / "),
+    }
+}
+
+fn csv_line(cells: &[String]) -> String {
+    let quoted: Vec<String> = cells.iter()
+        .map(|cell| match cell.contains([';', '"', '\n']) {
+            true => format!("\"{}\"", cell.replace('"', "\"\"")),
+            false => cell.clone(),
+        })
+        .collect();
+    format!("{}\r\n", quoted.join(";"))
+}
+
+// Up to six decimals with a decimal comma, zeros empty
+fn format_decimal(value: f64) -> String {
+    if value == 0.0 {
+        return String::new();
+    }
+    let text = format!("{:.6}", value);
+    text.trim_end_matches('0').trim_end_matches('.').replace('.', ",")
+}
+
+/// Number with a decimal point or comma; empty cells and dashes are `None`,
+/// other text is returned as the error
+fn parse_number(text: &str) -> Result<Option<f64>, String> {
+    let cleaned: String = text.chars().filter(|c| !c.is_whitespace() && *c != '%').collect();
+    if cleaned.is_empty() || cleaned == "-" || cleaned == "–" {
+        return Ok(None);
+    }
+    // The later of point and comma is the decimal separator, the other groups thousands
+    let normalized = match (cleaned.rfind('.'), cleaned.rfind(',')) {
+        (Some(point), Some(comma)) if comma > point => cleaned.replace('.', "").replace(',', "."),
+        (Some(_), Some(_)) => cleaned.replace(',', ""),
+        (None, Some(_)) => cleaned.replace(',', "."),
+        _ => cleaned,
+    };
+    normalized.parse::<f64>().map(Some).map_err(|_| text.to_string())
+}
+
+/// Uppercase letters and digits only; subscript digits become plain digits,
+/// superscript charges are dropped, so "NO₃⁻ %" reads as "NO3"
+fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '₀'..='₉' => char::from_digit(c as u32 - '₀' as u32, 10).unwrap_or(c),
+            c => c,
+        })
+        .filter(|c| !matches!(c, '⁰'..='⁹' | '¹' | '²' | '³'))
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::get_predefined_salts;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-6 * a.abs().max(1.0)
+    }
+
+    #[test]
+    fn export_and_import_round_trip() {
+        let mut salts = get_predefined_salts();
+        salts[0].dose = Range::new(5.0, 120.5);
+        salts[4].dose = Range::new(0.0, 80.0);
+        salts[5].price = Some(Price::new(2.35, "CHF"));
+
+        let table = Table::parse(&export(&salts)).unwrap();
+        let mapping = ColumnMapping::guess(&table);
+        let rows = preview(&table, &mapping, &[]);
+        assert_eq!(rows.len(), salts.len());
+
+        for (salt, row) in salts.iter().zip(rows) {
+            let imported = row.salt.unwrap_or_else(|e| panic!("{}: {}", salt.name, e));
+            assert_eq!(imported.name, salt.name);
+            assert_eq!(imported.formula, salt.formula);
+            assert_eq!(imported.kind, salt.kind);
+            assert_eq!(imported.price, salt.price, "{}", salt.name);
+            assert_eq!(imported.density, salt.density, "{}", salt.name);
+            assert_eq!(imported.dose, salt.dose, "{}", salt.name);
+            for nutrient in Nutrient::ALL {
+                assert!(close(imported.composition[nutrient], salt.composition[nutrient]), "{} {}", salt.name, nutrient.symbol());
+            }
+            let points = |s: &Salt| s.solubility.as_ref().map(|s| s.points.clone()).unwrap_or_default();
+            let (expected, actual) = (points(salt), points(&imported));
+            assert_eq!(actual.len(), expected.len(), "{}", salt.name);
+            // A single point holds at every temperature, so only its value matters
+            for (a, e) in actual.iter().zip(&expected) {
+                assert!(close(a.1, e.1) && (expected.len() == 1 || close(a.0, e.0)), "{}: {:?} statt {:?}", salt.name, actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn import_keeps_predefined_state_and_dose() {
+        let mut library = get_predefined_salts();
+        let index = library.iter().position(|s| s.name == "KNO₃").unwrap();
+        library[index].dose = Range::new(0.0, 50.0);
+        library[index].enabled = false;
+
+        let csv = "Name;K %;NO3 %;Preis\nKNO₃;38,5;61;1,80\n";
+        let table = Table::parse(csv).unwrap();
+        let rows = preview(&table, &ColumnMapping::guess(&table), &library);
+        assert!(!rows[0].selected_by_default());
+        add_to_library(&mut library, rows.into_iter().map(|row| row.salt.unwrap()).collect());
+
+        let salt = &library[index];
+        assert!(!salt.is_custom);
+        assert!(!salt.enabled);
+        assert_eq!(salt.dose, Range::new(0.0, 50.0));
+        assert!(close(salt.composition[Nutrient::K], 0.385));
+        assert_eq!(salt.price, Some(Price::new(1.8, "EUR")));
+    }
+
+    #[test]
+    fn solubility_curves_and_errors() {
+        let curve = parse_solubility("0: 133 / 20: 316,5 / 40 °C: 639").unwrap().unwrap();
+        assert_eq!(curve.points, vec![(0.0, 133.0), (20.0, 316.5), (40.0, 639.0)]);
+        assert_eq!(parse_solubility("").unwrap(), None);
+        for text in ["x", "20: 0", "20: 100 / : 5", "20: 100 / 40"] {
+            assert!(parse_solubility(text).is_err(), "{}", text);
+        }
+    }
+}
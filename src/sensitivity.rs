@@ -0,0 +1,247 @@
+//! Shadow prices of the bounds and reduced costs of the unused salts
+//!
+//! The solver reports no dual values and the model may be mixed-integer, so
+//! both are measured by re-solving: once with each bound relaxed by a small
+//! step, once with each unused salt forced into the recipe at a small amount.
+//! The EC bound stays linearised at the ionic strength of the optimal recipe,
+//! so all re-solves compare against the same model. Only the mass and cost
+//! objectives have a value that can be traded off this way.
+
+use anyhow::{bail, Result};
+
+use crate::ec;
+use crate::models::{OptimizationResult, Salt};
+use crate::optimizer::{format_number, solve_with_bounds, Bound, BoundSide, DisplayUnits, Objective, OptimizationProblem};
+
+/// Relative step by which a bound is relaxed
+const BOUND_STEP: f64 = 0.01;
+
+/// Step for bounds at zero, in the bound's display unit
+const ZERO_BOUND_STEP: f64 = 0.01;
+
+/// Amount an unused salt is forced into the recipe (g per litre of its stock solution)
+const FORCED_AMOUNT: f64 = 0.01;
+
+/// Objective changes below this are solver noise
+const CHANGE_TOLERANCE: f64 = 1e-7;
+
+/// How strongly the optimal recipe depends on its bounds and salts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sensitivity {
+    pub objective: Objective,
+    /// Currency of the cost objective; empty for the mass objective
+    pub currency: String,
+    /// Basis and units the bounds are described in
+    pub units: DisplayUnits,
+    /// Bounds whose relaxation improves the objective
+    pub bounds: Vec<ShadowPrice>,
+    /// Enabled salts the recipe does not use
+    pub unused_salts: Vec<ReducedCost>,
+}
+
+/// Objective improvement from relaxing a binding bound
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowPrice {
+    pub bound: Bound,
+    /// Salt mass (g) or cost per litre of stock solution saved per display
+    /// unit the bound is relaxed (raised for a max, lowered for a min)
+    pub saving: f64,
+}
+
+/// Objective penalty of bringing an unused salt into the recipe
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReducedCost {
+    pub salt: String,
+    /// Increase of salt mass (g) or cost per g/L of the salt, `None` if the
+    /// salt cannot enter without breaking a bound
+    pub per_gram: Option<f64>,
+    /// Price reduction (share) at which the salt would enter; only for the
+    /// cost objective and priced salts
+    pub price_reduction: Option<f64>,
+}
+
+impl Sensitivity {
+    /// Unit of the objective value, e.g. "g Salz" or "EUR"
+    fn objective_unit(&self) -> &str {
+        match self.objective {
+            Objective::Cost => &self.currency,
+            _ => "g Salz",
+        }
+    }
+
+    /// e.g. "K⁺ max 25 g l⁻¹: Anheben um 1 g l⁻¹ spart 3.2 g Salz je Liter Stammlösung"
+    pub fn describe_bound(&self, price: &ShadowPrice) -> String {
+        let direction = match price.bound.side {
+            BoundSide::Max => "Anheben",
+            BoundSide::Min => "Senken",
+        };
+        let step = match price.bound.subject.unit_label(&self.units) {
+            unit if unit.is_empty() => "1".to_string(),
+            unit => format!("1 {}", unit),
+        };
+        format!(
+            "{}: {} um {} spart {} {} je Liter Stammlösung",
+            price.bound.describe(&self.units),
+            direction,
+            step,
+            format_significant(price.saving),
+            self.objective_unit()
+        )
+    }
+
+    /// e.g. "KCl müsste 35 % günstiger sein, um in die Rezeptur zu kommen"
+    pub fn describe_salt(&self, cost: &ReducedCost) -> String {
+        let Some(per_gram) = cost.per_gram else {
+            return format!("{} lässt sich nicht einsetzen, ohne eine Grenze zu verletzen", cost.salt);
+        };
+        if per_gram <= CHANGE_TOLERANCE {
+            return format!("{} wäre ohne Mehraufwand einsetzbar", cost.salt);
+        }
+        let penalty = format!("+{} {} je g l⁻¹", format_significant(per_gram), self.objective_unit());
+        match (self.objective, cost.price_reduction) {
+            (Objective::Cost, Some(reduction)) if reduction < 1.0 => format!(
+                "{} müsste {:.0} % günstiger sein, um in die Rezeptur zu kommen ({})",
+                cost.salt,
+                reduction * 100.0,
+                penalty
+            ),
+            (Objective::Cost, _) => format!("{} lohnt sich auch kostenlos nicht ({})", cost.salt, penalty),
+            _ => format!("{} erhöht die Salzmasse um {} g je g l⁻¹", cost.salt, format_significant(per_gram)),
+        }
+    }
+}
+
+/// Solves the problem and measures the shadow prices of its bounds and the
+/// reduced costs of the salts left out
+pub fn analyze(problem: &OptimizationProblem) -> Result<Sensitivity> {
+    let objective = problem.options.objective;
+    if !matches!(objective, Objective::Mass | Objective::Cost) {
+        bail!("Die Sensitivitätsanalyse gibt es nur für minimale Salzmasse oder minimale Kosten");
+    }
+
+    // Fix the EC linearisation at the optimal recipe
+    let optimal = problem.solve()?;
+    let mut problem = problem.clone();
+    if problem.targets.ec_max.is_some() {
+        problem.ec_ionic_strength = Some(ec::ionic_strength(&optimal.final_totals));
+    }
+    let bounds = problem.bounds();
+    let units = problem.display_units();
+    let base = solve_with_bounds(&problem, &bounds)?;
+    let base_value = objective_value(&problem, &base);
+
+    let shadow_prices = bounds.iter()
+        .enumerate()
+        .filter_map(|(i, bound)| {
+            // Step in the internal unit that corresponds to one display unit
+            let per_display_unit = 1.0 / bound.subject.display_value(1.0, &units);
+            let step = match bound.value.abs() * BOUND_STEP {
+                0.0 => ZERO_BOUND_STEP * per_display_unit,
+                step => step,
+            };
+            let mut relaxed = bounds.clone();
+            relaxed[i].value += match bound.side {
+                BoundSide::Max => step,
+                BoundSide::Min => -step,
+            };
+            let result = solve_with_bounds(&problem, &relaxed).ok()?;
+            let saving = (base_value - objective_value(&problem, &result)) / step * per_display_unit;
+            (saving > CHANGE_TOLERANCE).then_some(ShadowPrice { bound: *bound, saving })
+        })
+        .collect();
+
+    let min_amount = problem.options.min_weighing.map_or(0.0, |g| g / problem.options.batch_volume);
+    let unused_salts = problem.enabled_salts().into_iter()
+        .filter(|salt| !base.recipe.iter().any(|e| e.salt == salt.name))
+        .map(|salt| {
+            let amount = FORCED_AMOUNT.max(min_amount);
+            let mut forced = problem.clone();
+            if let Some(s) = forced.salts.iter_mut().find(|s| s.name == salt.name) {
+                s.dose.min = amount;
+            }
+            let per_gram = solve_with_bounds(&forced, &bounds).ok()
+                .map(|result| (objective_value(&forced, &result) - base_value) / amount);
+            let price_reduction = match objective {
+                Objective::Cost => per_gram.zip(own_cost(&problem, salt)).map(|(penalty, cost)| penalty / cost),
+                _ => None,
+            };
+            ReducedCost { salt: salt.name.clone(), per_gram, price_reduction }
+        })
+        .collect();
+
+    let currency = match objective {
+        Objective::Cost => problem.currency()?.unwrap_or_default(),
+        _ => String::new(),
+    };
+    Ok(Sensitivity { objective, currency, units, bounds: shadow_prices, unused_salts })
+}
+
+/// Value of the mass or cost objective without its tie-breaks
+fn objective_value(problem: &OptimizationProblem, result: &OptimizationResult) -> f64 {
+    result.recipe.iter()
+        .map(|entry| match problem.options.objective {
+            Objective::Cost => problem.salts.iter()
+                .find(|s| s.name == entry.salt)
+                .and_then(|s| s.price.as_ref())
+                .map_or(0.0, |price| entry.amount * price.per_kg / 1000.0 * problem.tank_scale(&entry.tank)),
+            _ => entry.amount,
+        })
+        .sum()
+}
+
+/// Cost of one g/L of a salt in its own tank, if it has a price
+fn own_cost(problem: &OptimizationProblem, salt: &Salt) -> Option<f64> {
+    salt.price.as_ref()
+        .map(|price| price.per_kg / 1000.0 * problem.tank_scale(&salt.stock_solution))
+        .filter(|cost| *cost > 0.0)
+}
+
+/// Formats a number with three significant digits; costs per g/L are often tiny
+fn format_significant(value: f64) -> String {
+    if value == 0.0 || value.abs() >= 1.0 {
+        return format_number(value);
+    }
+    let decimals = (2.0 - value.abs().log10().floor()) as usize;
+    let text = format!("{:.*}", decimals, value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula;
+    use crate::models::{Nutrient, NutrientTargets};
+    use crate::optimizer::BoundSubject;
+
+    fn salt(formula: &str) -> Salt {
+        let mut salt = Salt::custom(formula.to_string(), formula.to_string(), formula::composition(formula, 1.0).unwrap());
+        salt.stock_solution = "A".to_string();
+        salt
+    }
+
+    #[test]
+    fn binding_minimum_saves_salt_when_lowered() {
+        let targets = NutrientTargets::unconstrained().with_range(Nutrient::K, 10.0, 50.0);
+        let problem = OptimizationProblem::new(targets)
+            .salts([salt("KNO₃"), salt("(NH₄)₂SO₄")])
+            .tanks(["A".to_string()])
+            .objective(Objective::Mass);
+        let sensitivity = analyze(&problem).unwrap();
+
+        // Only the K⁺ minimum binds; each g/L less saves 1 / 0.3867 g KNO₃
+        let [price] = sensitivity.bounds.as_slice() else {
+            panic!("{:?}", sensitivity.bounds);
+        };
+        assert_eq!(price.bound.subject, BoundSubject::Nutrient(Nutrient::K));
+        assert_eq!(price.bound.side, BoundSide::Min);
+        assert!((price.saving - 2.586).abs() < 0.01, "{}", price.saving);
+        let text = sensitivity.describe_bound(price);
+        assert!(text.starts_with("K⁺ min 10 g l⁻¹: Senken um 1 g l⁻¹ spart 2.586 g Salz"), "{}", text);
+
+        // Ammonium sulfate adds its own mass and no potassium
+        let [unused] = sensitivity.unused_salts.as_slice() else {
+            panic!("{:?}", sensitivity.unused_salts);
+        };
+        assert!((unused.per_gram.unwrap() - 1.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,201 @@
+//! Share links that carry a recipe in the URL fragment
+//!
+//! The fragment `#r=…` holds the workspace without the comparison as compact
+//! JSON in URL-safe base64, so opening the link reproduces the targets,
+//! options, water, tanks and salts and with them the recipe. To keep links
+//! short, `null` fields are left out and predefined salts are stored as in
+//! the workspace, by name with the fields that differ from the salt database.
+//! Enabled predefined salts also carry every field that enters the recipe,
+//! so a link gives the same recipe after the database has changed; disabled
+//! ones without any difference are just their name. The fragment never
+//! reaches a server.
+//!
+//! Opening a link keeps the comparison and the custom salts of the browser
+//! the link lacks; the page asks before it replaces anything else.
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::models::Salt;
+use crate::persistence::Workspace;
+
+/// Start of the URL fragment that holds a shared recipe
+pub const FRAGMENT_PREFIX: &str = "#r=";
+
+/// Salt fields that change the recipe, carried for every enabled salt
+const RECIPE_FIELDS: [&str; 5] = ["composition", "kind", "density", "dose", "solubility"];
+
+/// Compact code of everything in the workspace that determines the recipe
+pub fn encode(workspace: &Workspace) -> Result<String> {
+    let mut value = workspace.to_value()?;
+    if let Value::Object(fields) = &mut value {
+        fields.remove("comparison_history");
+    }
+    let salts = value.get_mut("salts").map(Value::take);
+    strip_nulls(&mut value);
+    if let Some(Value::Array(mut salts)) = salts {
+        for (salt, original) in salts.iter_mut().zip(&workspace.salts) {
+            match salt {
+                // A `null` override removes a database value and has to stay
+                Value::Object(fields) if fields.contains_key("predefined") => {
+                    if original.enabled {
+                        let full = serde_json::to_value(original)?;
+                        for key in RECIPE_FIELDS {
+                            fields.insert(key.to_string(), full[key].clone());
+                        }
+                    }
+                    if fields.len() == 1 {
+                        *salt = fields["predefined"].take();
+                    } else {
+                        fields.values_mut().for_each(strip_nulls);
+                    }
+                }
+                _ => strip_nulls(salt),
+            }
+        }
+        value["salts"] = Value::Array(salts);
+    }
+    Ok(URL_SAFE_NO_PAD.encode(serde_json::to_string(&value)?))
+}
+
+/// Removes `null` fields, which read back as `None` or an open upper limit
+fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.retain(|_, field| !field.is_null());
+            fields.values_mut().for_each(strip_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+/// Workspace with the shared recipe; the comparison is kept from `current`,
+/// and so are its custom salts missing from the link, switched off so that
+/// they do not change the shared recipe
+pub fn decode(code: &str, current: &Workspace) -> Result<Workspace> {
+    let bytes = URL_SAFE_NO_PAD.decode(code.trim()).context("Der Link ist unvollständig oder beschädigt")?;
+    let mut value: Value = serde_json::from_slice(&bytes).context("Der Link enthält keine gültige Rezeptur")?;
+
+    for entry in value.get_mut("salts").and_then(Value::as_array_mut).into_iter().flatten() {
+        if let Value::String(name) = entry {
+            *entry = json!({ "predefined": name });
+        }
+    }
+
+    let mut shared = Workspace::from_value(value)?;
+    for salt in current.salts.iter().filter(|salt| salt.is_custom) {
+        if shared.salts.iter().any(|s| s.name == salt.name) {
+            continue;
+        }
+        let stock_solution = match shared.stock_solutions.contains(&salt.stock_solution) {
+            true => salt.stock_solution.clone(),
+            false => "Unassigned".to_string(),
+        };
+        shared.salts.push(Salt { enabled: false, stock_solution, ..salt.clone() });
+    }
+    Ok(Workspace { comparison_history: current.comparison_history.clone(), ..shared })
+}
+
+/// Address of the page with the code in its fragment
+pub fn link(code: &str) -> String {
+    format!("{}{}{}", browser::page_url(), FRAGMENT_PREFIX, code)
+}
+
+/// Code of the share link in the address, if any; removes it from the
+/// address so that a reload shows the edited workspace, not the link again
+pub fn take_link_code() -> Option<String> {
+    let code = browser::fragment()?.strip_prefix(FRAGMENT_PREFIX)?.to_string();
+    browser::clear_fragment();
+    Some(code)
+}
+
+#[cfg(target_arch = "wasm32")]
+mod browser {
+    use web_sys::wasm_bindgen::JsValue;
+
+    pub fn fragment() -> Option<String> {
+        web_sys::window()?.location().hash().ok().filter(|hash| !hash.is_empty())
+    }
+
+    /// Current address without its fragment
+    pub fn page_url() -> String {
+        let Some(location) = web_sys::window().map(|window| window.location()) else {
+            return String::new();
+        };
+        [location.origin(), location.pathname(), location.search()]
+            .into_iter()
+            .map(|part| part.unwrap_or_default())
+            .collect()
+    }
+
+    pub fn clear_fragment() {
+        if let Some(history) = web_sys::window().and_then(|window| window.history().ok()) {
+            let _ = history.replace_state_with_url(&JsValue::NULL, "", Some(&page_url()));
+        }
+    }
+}
+
+/// Outside the browser there is no address to read or share
+#[cfg(not(target_arch = "wasm32"))]
+mod browser {
+    pub fn fragment() -> Option<String> {
+        None
+    }
+
+    pub fn page_url() -> String {
+        String::new()
+    }
+
+    pub fn clear_fragment() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Nutrient, NutrientVector};
+
+    #[test]
+    fn round_trip_gives_the_same_workspace() {
+        let mut workspace = Workspace::default();
+        workspace.salts[0].stock_solution = "B".to_string();
+        let acid = workspace.salts.iter_mut().find(|salt| !salt.enabled).unwrap();
+        acid.stock_solution = "A".to_string();
+        workspace.salts.push(Salt::custom("Fe-EDTA".to_string(), String::new(), NutrientVector::from_pairs(&[(Nutrient::Fe, 0.13)])));
+        workspace.targets.ph = crate::models::Range::new(5.5, 6.5);
+
+        let shared = decode(&encode(&workspace).unwrap(), &Workspace::default()).unwrap();
+        assert_eq!(shared.salts, workspace.salts);
+        assert_eq!(shared.to_value().unwrap(), workspace.to_value().unwrap());
+    }
+
+    #[test]
+    fn enabled_salts_keep_the_shared_values() {
+        // Stands for a salt database that changed after the link was made
+        let mut workspace = Workspace::default();
+        let salt = workspace.salts.iter_mut().find(|salt| salt.name == "KNO₃").unwrap();
+        salt.composition = NutrientVector::from_pairs(&[(Nutrient::K, 0.38), (Nutrient::No3, 0.6)]);
+        let shared_salt = salt.clone();
+
+        let shared = decode(&encode(&workspace).unwrap(), &Workspace::default()).unwrap();
+        assert!(shared.salts.contains(&shared_salt));
+    }
+
+    #[test]
+    fn custom_salts_missing_from_the_link_are_kept_switched_off() {
+        let mut current = Workspace::default();
+        current.salts.push(Salt::custom("Eigenes Salz".to_string(), String::new(), NutrientVector::zero()));
+
+        let shared = decode(&encode(&Workspace::default()).unwrap(), &current).unwrap();
+        let kept = shared.salts.iter().find(|salt| salt.name == "Eigenes Salz").unwrap();
+        assert!(!kept.enabled);
+    }
+
+    #[test]
+    fn damaged_codes_are_rejected() {
+        assert!(decode("nicht base64!", &Workspace::default()).is_err());
+        assert!(decode(&URL_SAFE_NO_PAD.encode("[1, 2]"), &Workspace::default()).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+//! Solves one problem across a range of a single parameter
+//!
+//! Produces the comparison series used for trials, e.g. the recipe at every
+//! NH₄⁺ share from 0 to 100 %. Points whose bounds cannot be met keep the
+//! reason and get the closest recipe instead.
+
+use anyhow::{bail, Result};
+
+use crate::models::{Nutrient, NutrientTargets, OptimizationResult, Range};
+use crate::optimizer::{format_number, Objective, OptimizationProblem};
+
+/// Most points a sweep may have
+pub const MAX_POINTS: usize = 101;
+
+/// Target value varied by a sweep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SweepParameter {
+    /// Share of NH₄⁺ in total nitrogen (%)
+    Nh4Ratio,
+    /// Total nitrogen, fixed at each value
+    Nitrogen,
+    /// Lower limit of a nutrient, in its target unit
+    Min(Nutrient),
+    /// Upper limit of a nutrient, in its target unit
+    Max(Nutrient),
+    /// Both limits of a nutrient at the same value
+    Fixed(Nutrient),
+    /// Upper EC limit of the final solution (mS/cm)
+    EcMax,
+}
+
+impl SweepParameter {
+    /// Every parameter a sweep can vary, in display order
+    pub fn all() -> Vec<SweepParameter> {
+        let mut parameters = vec![SweepParameter::Nh4Ratio, SweepParameter::Nitrogen, SweepParameter::EcMax];
+        for nutrient in Nutrient::ALL.into_iter().filter(|n| !n.is_nitrogen()) {
+            parameters.extend([SweepParameter::Min(nutrient), SweepParameter::Max(nutrient), SweepParameter::Fixed(nutrient)]);
+        }
+        parameters
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            SweepParameter::Nh4Ratio => "NH₄⁺-Anteil".to_string(),
+            SweepParameter::Nitrogen => "N fest".to_string(),
+            SweepParameter::Min(nutrient) => format!("{} min", nutrient.symbol()),
+            SweepParameter::Max(nutrient) => format!("{} max", nutrient.symbol()),
+            SweepParameter::Fixed(nutrient) => format!("{} fest", nutrient.symbol()),
+            SweepParameter::EcMax => "EC max".to_string(),
+        }
+    }
+
+    /// Unit the values are given in, which depends on the targets' units
+    pub fn unit_label(self, targets: &NutrientTargets) -> String {
+        match self {
+            SweepParameter::Nh4Ratio => "%".to_string(),
+            SweepParameter::Nitrogen => targets.nitrogen_unit().label().to_string(),
+            SweepParameter::Min(nutrient) | SweepParameter::Max(nutrient) | SweepParameter::Fixed(nutrient) => {
+                targets.get(nutrient).unit.label_for(nutrient)
+            }
+            SweepParameter::EcMax => "mS cm⁻¹".to_string(),
+        }
+    }
+
+    /// Current value in the targets; infinite for an open upper limit
+    pub fn value(self, targets: &NutrientTargets) -> f64 {
+        match self {
+            SweepParameter::Nh4Ratio => targets.nh4_ratio.unwrap_or(0.0) * 100.0,
+            SweepParameter::Nitrogen => targets.nitrogen.min,
+            SweepParameter::Min(nutrient) | SweepParameter::Fixed(nutrient) => targets.get(nutrient).range.min,
+            SweepParameter::Max(nutrient) => targets.get(nutrient).range.max,
+            SweepParameter::EcMax => targets.ec_max.unwrap_or(f64::INFINITY),
+        }
+    }
+
+    /// Sets the parameter; a min above the max (or the reverse) moves the other limit along
+    pub fn apply(self, targets: &mut NutrientTargets, value: f64) {
+        match self {
+            SweepParameter::Nh4Ratio => targets.nh4_ratio = Some((value / 100.0).clamp(0.0, 1.0)),
+            SweepParameter::Nitrogen => targets.nitrogen = Range::exact(value),
+            SweepParameter::Min(nutrient) => {
+                let range = targets.get(nutrient).range;
+                targets.set_range(nutrient, Range::new(value, range.max.max(value)));
+            }
+            SweepParameter::Max(nutrient) => {
+                let range = targets.get(nutrient).range;
+                targets.set_range(nutrient, Range::new(range.min.min(value), value));
+            }
+            SweepParameter::Fixed(nutrient) => targets.set_range(nutrient, Range::exact(value)),
+            SweepParameter::EcMax => targets.ec_max = Some(value),
+        }
+    }
+
+    /// Row label of a point, e.g. "K⁺ max 20 g l⁻¹"
+    pub fn describe(self, targets: &NutrientTargets, value: f64) -> String {
+        format!("{} {} {}", self.label(), format_number(value), self.unit_label(targets))
+    }
+}
+
+/// A range of values for one parameter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sweep {
+    pub parameter: SweepParameter,
+    pub from: f64,
+    pub to: f64,
+    pub step: f64,
+}
+
+/// Recipe at one value of a sweep
+#[derive(Debug, Clone)]
+pub struct SweepPoint {
+    pub value: f64,
+    /// Recipe at this value; the closest recipe if the bounds cannot be met,
+    /// `None` if not even that could be found
+    pub result: Option<OptimizationResult>,
+    /// Why the point failed, `None` for feasible points; infeasible points are
+    /// not diagnosed, which would take one solve per bound and point
+    pub error: Option<String>,
+}
+
+impl SweepPoint {
+    pub fn is_feasible(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl Sweep {
+    /// Values from `from` to `to` (both included) in steps of `step`
+    pub fn values(&self) -> Result<Vec<f64>> {
+        if !self.from.is_finite() || !self.to.is_finite() {
+            bail!("Start- und Endwert müssen Zahlen sein");
+        }
+        if self.step.is_nan() || self.step <= 0.0 {
+            bail!("Die Schrittweite muss größer als 0 sein");
+        }
+        let (low, high) = (self.from.min(self.to), self.from.max(self.to));
+        // Tolerate rounding so that e.g. 0 to 1 in steps of 0.1 ends at 1
+        let count = ((high - low) / self.step + 1e-9).floor() as usize + 1;
+        if count > MAX_POINTS {
+            bail!("Zu viele Schritte ({}), höchstens {} sind möglich", count, MAX_POINTS);
+        }
+        let direction = if self.to < self.from { -1.0 } else { 1.0 };
+        Ok((0..count).map(|i| self.from + direction * i as f64 * self.step).collect())
+    }
+
+    /// Solves the problem at every value of the sweep
+    pub fn run(&self, problem: &OptimizationProblem) -> Result<Vec<SweepPoint>> {
+        Ok(self.values()?
+            .into_iter()
+            .map(|value| {
+                let mut problem = problem.clone();
+                self.parameter.apply(&mut problem.targets, value);
+                match problem.solve() {
+                    Ok(result) => SweepPoint { value, result: Some(result), error: None },
+                    Err(e) => SweepPoint {
+                        value,
+                        result: problem.objective(Objective::Deviation).solve().ok(),
+                        error: Some(e.to_string()),
+                    },
+                }
+            })
+            .collect())
+    }
+}